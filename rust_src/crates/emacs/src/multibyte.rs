@@ -67,6 +67,12 @@ impl LispStringRef {
         s.size
     }
 
+    /// Whether the string is multibyte, as `multibyte-string-p`.
+    pub fn is_multibyte(self) -> bool {
+        let s = unsafe { self.u.s };
+        s.size_byte >= 0
+    }
+
     pub fn as_slice(&self) -> &[u8] {
         unsafe { slice::from_raw_parts(self.u.s.data as *const u8, self.len_bytes() as usize) }
     }
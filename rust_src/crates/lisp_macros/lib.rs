@@ -161,15 +161,23 @@ pub fn lisp_fn(attr_ts: TokenStream, fn_ts: TokenStream) -> TokenStream {
 }
 
 #[proc_macro_attribute]
-pub fn async_stream(_attr_ts: TokenStream, fn_ts: TokenStream) -> TokenStream {
+pub fn async_stream(attr_ts: TokenStream, fn_ts: TokenStream) -> TokenStream {
     let fn_item = syn::parse(fn_ts.clone()).unwrap();
     let function = function::parse(&fn_item).unwrap();
     let name = &function.name;
     let async_name = concat_idents("call_", &name.to_string());
 
+    // Arguments such as `name` and `c_name` are passed on to the lisp_fn.
+    let attr: proc_macro2::TokenStream = attr_ts.into();
+    let lisp_fn_attr = if attr.is_empty() {
+        quote! { #[lisp_fn] }
+    } else {
+        quote! { #[lisp_fn(#attr)] }
+    };
+
     let tokens = quote! {
 
-    #[lisp_fn]
+    #lisp_fn_attr
     pub fn #async_name (handler: emacs::lisp::LispObject) -> emacs::lisp::LispObject {
         crate::ng_async::rust_worker(handler, |s| {
         ::futures::executor::block_on(#name(s))
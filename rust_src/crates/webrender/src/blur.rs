@@ -0,0 +1,135 @@
+//! Blurring what is behind a window, so that text stays readable over
+//! the transparent parts of a frame.
+//!
+//! The blur is done by the compositor of the window system, not by
+//! webrender: KWin blurs behind windows with `_KDE_NET_WM_BLUR_BEHIND_REGION`
+//! on X11, macOS behind windows given a background blur radius, and
+//! Windows 11 behind windows with an acrylic system backdrop.  Other
+//! compositors, and Wayland, have no way for a client to ask for it.
+//!
+//! Only the parts of a window its contents leave transparent show the
+//! blur, so a window that is drawn opaque looks the same blurred or not.
+
+use winit::window::Window;
+
+use crate::event_loop::Platform;
+
+/// The radius macOS blurs with when none is given, in points.
+pub const DEFAULT_BLUR_RADIUS: u32 = 20;
+
+/// Blur what is behind WINDOW on _PLATFORM by RADIUS, or stop blurring it
+/// if RADIUS is None.  Only macOS honors the radius; the other window
+/// systems blur by their own.  Return false if the window system can't
+/// blur behind windows.
+pub fn set_blur(window: &Window, _platform: Platform, radius: Option<u32>) -> bool {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    return match _platform {
+        Platform::X11 => x11_set_blur(window, radius.is_some()),
+        _ => false,
+    };
+    #[cfg(target_os = "macos")]
+    return macos_set_blur(window, radius.unwrap_or(0));
+    #[cfg(windows)]
+    return windows_set_blur(window, radius.is_some());
+}
+
+// Set or delete `_KDE_NET_WM_BLUR_BEHIND_REGION` of WINDOW.  No region
+// blurs the whole window.  KWin interns the atom while its blur effect
+// is loaded, so a missing atom means there is nothing to blur.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn x11_set_blur(window: &Window, blur: bool) -> bool {
+    use winit::platform::unix::WindowExtUnix;
+    use xcb::{x, Xid, XidNew};
+
+    let window_id = match window.xlib_window() {
+        Some(window_id) => unsafe { x::Window::new(window_id as u32) },
+        None => return false,
+    };
+    let (connection, _) = match xcb::Connection::connect(None) {
+        Ok(connection) => connection,
+        Err(err) => {
+            log::warn!("Failed to connect to X11 to blur a window: {err:?}");
+            return false;
+        }
+    };
+
+    let cookie = connection.send_request(&x::InternAtom {
+        only_if_exists: true,
+        name: b"_KDE_NET_WM_BLUR_BEHIND_REGION",
+    });
+    let atom = match connection.wait_for_reply(cookie) {
+        Ok(reply) if !reply.atom().is_none() => reply.atom(),
+        _ => return false,
+    };
+
+    if blur {
+        connection.send_request(&x::ChangeProperty {
+            mode: x::PropMode::Replace,
+            window: window_id,
+            property: atom,
+            r#type: x::ATOM_CARDINAL,
+            data: &[] as &[u32],
+        });
+    } else {
+        connection.send_request(&x::DeleteProperty {
+            window: window_id,
+            property: atom,
+        });
+    }
+    connection.flush().is_ok()
+}
+
+// Give WINDOW a background blur RADIUS, 0 for none.  AppKit has no
+// public way to blur behind a window drawn by GL, short of putting an
+// NSVisualEffectView under its content view; the window server call
+// used by Terminal and most terminal emulators takes a radius, which
+// that view doesn't.
+#[cfg(target_os = "macos")]
+fn macos_set_blur(window: &Window, radius: u32) -> bool {
+    use cocoa::{appkit::NSWindow, base::id};
+    use std::os::raw::c_void;
+    use winit::platform::macos::WindowExtMacOS;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGSMainConnectionID() -> *mut c_void;
+        fn CGSSetWindowBackgroundBlurRadius(
+            connection: *mut c_void,
+            window_number: isize,
+            radius: i64,
+        ) -> i32;
+    }
+
+    unsafe {
+        let window_number = NSWindow::windowNumber(window.ns_window() as id);
+        CGSSetWindowBackgroundBlurRadius(CGSMainConnectionID(), window_number, radius as i64) == 0
+    }
+}
+
+// Give WINDOW the acrylic backdrop of transient windows, or none.
+// `DWMWA_SYSTEMBACKDROP_TYPE` is new in Windows 11 22H2, and setting it
+// fails before.
+#[cfg(windows)]
+fn windows_set_blur(window: &Window, blur: bool) -> bool {
+    use windows_sys::Win32::Graphics::Dwm::DwmSetWindowAttribute;
+    use winit::platform::windows::WindowExtWindows;
+
+    const DWMWA_SYSTEMBACKDROP_TYPE: u32 = 38;
+    const DWMSBT_NONE: i32 = 1;
+    const DWMSBT_TRANSIENTWINDOW: i32 = 3;
+
+    let backdrop = if blur {
+        DWMSBT_TRANSIENTWINDOW
+    } else {
+        DWMSBT_NONE
+    };
+    let result = unsafe {
+        DwmSetWindowAttribute(
+            window.hwnd() as _,
+            DWMWA_SYSTEMBACKDROP_TYPE,
+            &backdrop as *const i32 as *const _,
+            std::mem::size_of::<i32>() as u32,
+        )
+    };
+    result == 0
+}
@@ -0,0 +1,763 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    mem,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use copypasta::ClipboardProvider;
+use once_cell::sync::Lazy;
+use winit::event_loop::EventLoopProxy;
+#[cfg(all(unix, not(target_os = "macos")))]
+use xcb::{x, Xid, XidNew};
+
+use crate::event_loop::Platform;
+use crate::util::RecoverableMutex;
+
+/// The target every clipboard provider can serve, as UTF-8 text.
+pub const TEXT_TARGET: &str = "text/plain;charset=utf-8";
+
+#[cfg(all(unix, not(target_os = "macos")))]
+const LOAD_TIMEOUT: Duration = Duration::from_secs(1);
+
+// The clipboard manager reads the whole clipboard before it answers.
+#[cfg(all(unix, not(target_os = "macos")))]
+const SAVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Background reads may transfer megabytes in many increments.
+#[cfg(all(unix, not(target_os = "macos")))]
+const BACKGROUND_LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// User event sent through the event loop when a background clipboard
+/// read has finished.
+pub const CLIPBOARD_READ_EVENT: i32 = 1;
+
+/// User event sent through the event loop when the watched clipboard
+/// has changed.
+pub const CLIPBOARD_CHANGED_EVENT: i32 = 2;
+
+static NEXT_READ_ID: AtomicU64 = AtomicU64::new(0);
+
+// Background reads that finished, by read ID, with the text read or None
+// if the clipboard couldn't be read.
+static FINISHED_READS: Lazy<RecoverableMutex<Vec<(u64, Option<String>)>>> =
+    Lazy::new(|| RecoverableMutex::new("FINISHED_READS", Vec::new()));
+
+/// A new ID for a background clipboard read.
+pub fn next_read_id() -> u64 {
+    NEXT_READ_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Take the background reads that have finished since the last call.
+pub fn take_finished_reads() -> Vec<(u64, Option<String>)> {
+    mem::take(&mut *FINISHED_READS.lock())
+}
+
+/// How long to wait before trying to connect a clipboard provider again.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+pub type ClipboardResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Connects to the platform's clipboard.
+pub type ConnectClipboard = fn() -> ClipboardResult<Box<dyn ClipboardProvider>>;
+
+/// A clipboard kept in process, used while the platform's clipboard is
+/// unavailable, so copy and paste still work within the session.
+///
+/// With a CONNECT function, the platform's clipboard is tried again on
+/// access, at most every `RETRY_INTERVAL`, and used from then on.
+pub struct FallbackClipboard {
+    contents: String,
+    connect: Option<ConnectClipboard>,
+    last_attempt: Instant,
+    provider: Option<Box<dyn ClipboardProvider>>,
+}
+
+impl FallbackClipboard {
+    pub fn new(connect: Option<ConnectClipboard>) -> Self {
+        FallbackClipboard {
+            contents: String::new(),
+            connect,
+            last_attempt: Instant::now(),
+            provider: None,
+        }
+    }
+
+    fn provider(&mut self) -> Option<&mut Box<dyn ClipboardProvider>> {
+        if self.provider.is_none() {
+            let connect = self.connect?;
+
+            if self.last_attempt.elapsed() < RETRY_INTERVAL {
+                return None;
+            }
+            self.last_attempt = Instant::now();
+
+            match connect() {
+                Ok(provider) => {
+                    log::info!("Connected to the clipboard");
+                    self.provider = Some(provider);
+                }
+                Err(err) => log::debug!("Clipboard still unavailable: {err}"),
+            }
+        }
+
+        self.provider.as_mut()
+    }
+}
+
+impl ClipboardProvider for FallbackClipboard {
+    fn get_contents(&mut self) -> ClipboardResult<String> {
+        match self.provider() {
+            Some(provider) => provider.get_contents(),
+            None => Ok(self.contents.clone()),
+        }
+    }
+
+    fn set_contents(&mut self, contents: String) -> ClipboardResult<()> {
+        match self.provider() {
+            Some(provider) => provider.set_contents(contents),
+            None => {
+                self.contents = contents;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A selection Emacs has set the clipboard for.
+pub struct OwnedSelection {
+    pub contents: String,
+    /// When ownership was taken, in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The window that owned the clipboard once it was set, where owners
+    /// can be asked.
+    pub owner: Option<u32>,
+}
+
+impl OwnedSelection {
+    pub fn new(contents: String, owner: Option<u32>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis() as u64);
+
+        OwnedSelection {
+            contents,
+            timestamp,
+            owner,
+        }
+    }
+}
+
+/// Most X11 cut buffers, `CUT_BUFFER0` to `CUT_BUFFER7`.
+pub const CUT_BUFFERS: usize = 8;
+
+/// The text Emacs last put on the clipboard, most recent first, so recent
+/// kills can be yanked back after other clients took the clipboard.  The
+/// history is empty, and the clipboard holds a single entry as usual,
+/// unless it is given a size.
+#[derive(Default)]
+pub struct ClipboardHistory {
+    size: usize,
+    entries: VecDeque<String>,
+}
+
+impl ClipboardHistory {
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Keep the SIZE most recent entries, dropping older ones.
+    pub fn set_size(&mut self, size: usize) {
+        self.size = size;
+        self.entries.truncate(size);
+    }
+
+    /// Remember CONTENTS as the most recent entry, unless the history is
+    /// off or CONTENTS is already the most recent.  Return whether it was
+    /// remembered.
+    pub fn push(&mut self, contents: &str) -> bool {
+        if self.size == 0 || self.entries.front().map(String::as_str) == Some(contents) {
+            return false;
+        }
+
+        self.entries.truncate(self.size - 1);
+        self.entries.push_front(contents.to_owned());
+        true
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter()
+    }
+}
+
+/// Access to clipboard targets beyond UTF-8 text.
+///
+/// copypasta only deals with text, so on X11 we keep a clipboard
+/// connection of our own to list targets and convert the selection to
+/// a specific one.  Elsewhere only `TEXT_TARGET` is available.
+pub struct ClipboardTargets {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    x11: Option<x11_clipboard::Clipboard>,
+}
+
+impl ClipboardTargets {
+    pub fn new(_platform: Platform) -> Self {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            let x11 = if let Platform::Wayland(_) = _platform {
+                None
+            } else {
+                x11_clipboard::Clipboard::new()
+                    .map_err(|err| log::warn!("Failed to open X11 clipboard: {err:?}"))
+                    .ok()
+            };
+
+            ClipboardTargets { x11 }
+        }
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        {
+            ClipboardTargets {}
+        }
+    }
+
+    /// Names of the targets the clipboard owner offers, or None if they
+    /// can't be listed on this platform.
+    pub fn targets(&self) -> Option<Vec<String>> {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            if let Some(clipboard) = &self.x11 {
+                let atoms = &clipboard.getter.atoms;
+                let data = clipboard
+                    .load(atoms.clipboard, atoms.targets, atoms.property, LOAD_TIMEOUT)
+                    .map_err(|err| log::warn!("Failed to read clipboard targets: {err:?}"))
+                    .ok()?;
+
+                // Send every request before waiting for any reply, so
+                // the names take one round trip.
+                let connection = &clipboard.getter.connection;
+                let cookies: Vec<_> = data
+                    .chunks_exact(4)
+                    .map(|atom| u32::from_ne_bytes([atom[0], atom[1], atom[2], atom[3]]))
+                    .map(|atom| {
+                        let atom = unsafe { x::Atom::new(atom) };
+                        connection.send_request(&x::GetAtomName { atom })
+                    })
+                    .collect();
+                let targets = cookies
+                    .into_iter()
+                    .filter_map(|cookie| connection.wait_for_reply(cookie).ok())
+                    .map(|reply| reply.name().to_utf8().into_owned())
+                    .collect();
+
+                return Some(targets);
+            }
+        }
+
+        None
+    }
+
+    /// Whether any client owns the clipboard, or None if that can't be
+    /// asked on this platform.
+    pub fn has_owner(&self) -> Option<bool> {
+        self.owner().map(|owner| owner != 0)
+    }
+
+    /// The window owning the clipboard, 0 if none, or None if that can't
+    /// be asked on this platform.
+    pub fn owner(&self) -> Option<u32> {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            if let Some(clipboard) = &self.x11 {
+                let connection = &clipboard.getter.connection;
+                let cookie = connection.send_request(&x::GetSelectionOwner {
+                    selection: clipboard.getter.atoms.clipboard,
+                });
+                let reply = connection.wait_for_reply(cookie).ok()?;
+
+                return Some(reply.owner().resource_id());
+            }
+        }
+
+        None
+    }
+
+    /// Read the clipboard as text on a connection and thread of its own,
+    /// so a large selection doesn't hold up the event loop.  Once done,
+    /// the result is queued for `take_finished_reads` under ID and
+    /// `CLIPBOARD_READ_EVENT` is sent through PROXY.
+    /// Return false if reads can't be done in the background on this
+    /// platform.
+    pub fn spawn_read(&self, _id: u64, _proxy: EventLoopProxy<i32>) -> bool {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            if self.x11.is_some() {
+                let spawned = std::thread::Builder::new()
+                    .name("webrender-clipboard".to_owned())
+                    .spawn(move || {
+                        let contents = read_x11_text();
+
+                        FINISHED_READS.lock().push((_id, contents));
+                        let _ = _proxy.send_event(CLIPBOARD_READ_EVENT);
+                    });
+
+                return spawned
+                    .map_err(|err| log::warn!("Failed to spawn clipboard thread: {err}"))
+                    .is_ok();
+            }
+        }
+
+        false
+    }
+
+    /// The clipboard contents converted to TARGET, or None if they
+    /// can't be converted on this platform.
+    pub fn get(&self, target: &str) -> Option<Vec<u8>> {
+        self.load(target)?
+            .map_err(|err| log::warn!("Failed to read clipboard as {target}: {err}"))
+            .ok()
+    }
+
+    /// Convert the clipboard contents to TARGET, or None if they can't be
+    /// converted on this platform.
+    pub fn load(&self, _target: &str) -> Option<ClipboardResult<Vec<u8>>> {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            if let Some(clipboard) = &self.x11 {
+                let atoms = &clipboard.getter.atoms;
+                let load = || -> ClipboardResult<Vec<u8>> {
+                    let target = clipboard
+                        .getter
+                        .get_atom(_target)
+                        .map_err(|err| format!("{err:?}"))?;
+                    let data = clipboard
+                        .load(atoms.clipboard, target, atoms.property, LOAD_TIMEOUT)
+                        .map_err(|err| format!("{err:?}"))?;
+                    Ok(data)
+                };
+
+                return Some(load());
+            }
+        }
+
+        None
+    }
+
+    /// Take the clipboard, offering DATA as TARGET, or return None if
+    /// targets can't be offered on this platform.  X clients ask for text
+    /// as `UTF8_STRING`, so `TEXT_TARGET` is offered as that.
+    pub fn store(&self, _target: &str, _data: &[u8]) -> Option<ClipboardResult<()>> {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            if let Some(clipboard) = &self.x11 {
+                let atoms = &clipboard.setter.atoms;
+                let store = || -> ClipboardResult<()> {
+                    let target = if _target == TEXT_TARGET {
+                        atoms.utf8_string
+                    } else {
+                        clipboard
+                            .setter
+                            .get_atom(_target)
+                            .map_err(|err| format!("{err:?}"))?
+                    };
+                    clipboard
+                        .store(atoms.clipboard, target, _data.to_vec())
+                        .map_err(|err| format!("{err:?}"))?;
+                    Ok(())
+                };
+
+                return Some(store());
+            }
+        }
+
+        None
+    }
+
+    /// Have the clipboard manager take a copy of the clipboard, as ICCCM's
+    /// `SAVE_TARGETS` asks, so its contents stay after their owner exits.
+    /// Return whether it took one, or None if there is no clipboard
+    /// manager running or none on this platform.
+    pub fn save_to_manager(&self) -> Option<bool> {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            if let Some(clipboard) = &self.x11 {
+                let context = &clipboard.getter;
+                let manager = context.get_atom("CLIPBOARD_MANAGER").ok()?;
+                let cookie = context
+                    .connection
+                    .send_request(&x::GetSelectionOwner { selection: manager });
+                let owner = context.connection.wait_for_reply(cookie).ok()?;
+                if owner.owner().is_none() {
+                    return None;
+                }
+
+                // The manager converts the clipboard to every target its
+                // owner offers while this waits.
+                let save_targets = context.get_atom("SAVE_TARGETS").ok()?;
+                let saved =
+                    clipboard.load(manager, save_targets, context.atoms.property, SAVE_TIMEOUT);
+
+                return Some(
+                    saved
+                        .map_err(|err| log::warn!("Failed to save the clipboard: {err:?}"))
+                        .is_ok(),
+                );
+            }
+        }
+
+        None
+    }
+
+    /// Rotate the X11 cut buffers and put CONTENTS in `CUT_BUFFER0`, as
+    /// `XStoreBuffer` does, so older X clients and later sessions can
+    /// read recent kills.  Return false if there are no cut buffers on
+    /// this platform, or they couldn't be written.
+    pub fn push_cut_buffer(&self, _contents: &str) -> bool {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            if let Some(clipboard) = &self.x11 {
+                let context = &clipboard.setter;
+                let connection = &context.connection;
+                let root = match root_window(context) {
+                    Some(root) => root,
+                    None => return false,
+                };
+
+                // Properties can only be rotated once they all exist.
+                for buffer in cut_buffer_atoms() {
+                    connection.send_request(&x::ChangeProperty {
+                        mode: x::PropMode::Append,
+                        window: root,
+                        property: buffer,
+                        r#type: x::ATOM_STRING,
+                        data: &[] as &[u8],
+                    });
+                }
+                connection.send_request(&x::RotateProperties {
+                    window: root,
+                    delta: 1,
+                    atoms: &cut_buffer_atoms(),
+                });
+
+                // Clients read cut buffers as Latin-1 `STRING`, so text
+                // that doesn't fit it is stored as `UTF8_STRING`.
+                let (type_, data) = match encode_latin1(_contents) {
+                    Some(data) => (x::ATOM_STRING, data),
+                    None => (context.atoms.utf8_string, _contents.as_bytes().to_vec()),
+                };
+                connection.send_request(&x::ChangeProperty {
+                    mode: x::PropMode::Replace,
+                    window: root,
+                    property: x::ATOM_CUT_BUFFER0,
+                    r#type: type_,
+                    data: &data,
+                });
+
+                return connection
+                    .flush()
+                    .map_err(|err| log::warn!("Failed to store the cut buffer: {err:?}"))
+                    .is_ok();
+            }
+        }
+
+        false
+    }
+
+    /// The text of the X11 cut buffers, most recent first, or None if
+    /// there are none on this platform.  Empty cut buffers are left out.
+    pub fn cut_buffers(&self) -> Option<Vec<String>> {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            if let Some(clipboard) = &self.x11 {
+                let context = &clipboard.getter;
+                let connection = &context.connection;
+                let root = root_window(context)?;
+
+                // Send every request before waiting for any reply, so
+                // the buffers take one round trip.
+                let cookies: Vec<_> = cut_buffer_atoms()
+                    .iter()
+                    .map(|&buffer| {
+                        connection.send_request(&x::GetProperty {
+                            delete: false,
+                            window: root,
+                            property: buffer,
+                            r#type: x::ATOM_ANY,
+                            long_offset: 0,
+                            long_length: u32::MAX,
+                        })
+                    })
+                    .collect();
+                let buffers = cookies
+                    .into_iter()
+                    .filter_map(|cookie| connection.wait_for_reply(cookie).ok())
+                    .filter(|reply| !reply.value::<u8>().is_empty())
+                    .map(|reply| {
+                        let data = reply.value::<u8>();
+                        if reply.r#type() == x::ATOM_STRING {
+                            data.iter().map(|&byte| byte as char).collect()
+                        } else {
+                            String::from_utf8_lossy(data).into_owned()
+                        }
+                    })
+                    .collect();
+
+                return Some(buffers);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn root_window(context: &x11_clipboard::Context) -> Option<x::Window> {
+    let setup = context.connection.get_setup();
+    let screen = setup.roots().nth(context.screen as usize)?;
+    Some(screen.root())
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn cut_buffer_atoms() -> [x::Atom; CUT_BUFFERS] {
+    [
+        x::ATOM_CUT_BUFFER0,
+        x::ATOM_CUT_BUFFER1,
+        x::ATOM_CUT_BUFFER2,
+        x::ATOM_CUT_BUFFER3,
+        x::ATOM_CUT_BUFFER4,
+        x::ATOM_CUT_BUFFER5,
+        x::ATOM_CUT_BUFFER6,
+        x::ATOM_CUT_BUFFER7,
+    ]
+}
+
+// TEXT as Latin-1, or None if it has characters Latin-1 doesn't.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn encode_latin1(text: &str) -> Option<Vec<u8>> {
+    text.chars()
+        .map(|c| u8::try_from(u32::from(c)).ok())
+        .collect()
+}
+
+/// Watches the clipboard for changes on a thread of its own, sending
+/// `CLIPBOARD_CHANGED_EVENT` through the event loop for each change.
+///
+/// Where the platform counts clipboard changes, as the change count of
+/// the macOS pasteboard and the clipboard sequence number on Windows,
+/// the count is polled.  On X11 the clipboard text is read and compared
+/// by its hash.  Wayland clipboards can't be watched.  Changes made by
+/// Emacs itself are reported too.
+#[derive(Default)]
+pub struct ClipboardWatcher {
+    interval: Option<Duration>,
+    // Set to stop the thread of the running watch.
+    stop: Option<Arc<AtomicBool>>,
+}
+
+impl ClipboardWatcher {
+    /// How often the clipboard is checked, or None if it isn't watched.
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    /// Check the clipboard of PLATFORM for changes every INTERVAL and
+    /// tell the event loop of PROXY about them, or stop watching if
+    /// INTERVAL is None.  Return false if the clipboard can't be
+    /// watched.
+    pub fn set_interval(
+        &mut self,
+        interval: Option<Duration>,
+        platform: Platform,
+        proxy: EventLoopProxy<i32>,
+    ) -> bool {
+        if let Some(stop) = self.stop.take() {
+            stop.store(true, Ordering::Relaxed);
+        }
+        self.interval = None;
+
+        let interval = match interval {
+            Some(interval) => interval,
+            None => return true,
+        };
+
+        if !can_watch(platform) {
+            return false;
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+
+        let spawned = thread::Builder::new()
+            .name("webrender-clipboard-watch".to_owned())
+            .spawn(move || {
+                let mut change_marker = ChangeMarker::new();
+                let mut last = change_marker.get();
+
+                while !stopped.load(Ordering::Relaxed) {
+                    thread::sleep(interval);
+
+                    let marker = change_marker.get();
+                    if marker == last || stopped.load(Ordering::Relaxed) {
+                        continue;
+                    }
+                    last = marker;
+
+                    if proxy.send_event(CLIPBOARD_CHANGED_EVENT).is_err() {
+                        break;
+                    }
+                }
+            });
+
+        match spawned {
+            Ok(_) => {
+                self.interval = Some(interval);
+                self.stop = Some(stop);
+                true
+            }
+            Err(err) => {
+                log::warn!("Failed to spawn clipboard watch thread: {err}");
+                false
+            }
+        }
+    }
+}
+
+fn can_watch(_platform: Platform) -> bool {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        matches!(_platform, Platform::X11)
+    }
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    {
+        true
+    }
+}
+
+// A value that changes whenever the clipboard does.  It is made on the
+// watching thread, as the X11 connection has to live there.
+struct ChangeMarker {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    x11: Option<x11_clipboard::Clipboard>,
+}
+
+impl ChangeMarker {
+    fn new() -> Self {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            let x11 = x11_clipboard::Clipboard::new()
+                .map_err(|err| log::warn!("Failed to open X11 clipboard to watch: {err:?}"))
+                .ok();
+
+            ChangeMarker { x11 }
+        }
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        {
+            ChangeMarker {}
+        }
+    }
+
+    fn get(&mut self) -> Option<u64> {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            use std::{
+                collections::hash_map::DefaultHasher,
+                hash::{Hash, Hasher},
+            };
+
+            let clipboard = self.x11.as_ref()?;
+            let atoms = &clipboard.getter.atoms;
+            let data = clipboard
+                .load(
+                    atoms.clipboard,
+                    atoms.utf8_string,
+                    atoms.property,
+                    LOAD_TIMEOUT,
+                )
+                .ok()?;
+
+            let mut hasher = DefaultHasher::new();
+            data.hash(&mut hasher);
+            Some(hasher.finish())
+        }
+        #[cfg(target_os = "macos")]
+        {
+            use cocoa::{appkit::NSPasteboard, base::nil};
+
+            let count = unsafe { NSPasteboard::generalPasteboard(nil).changeCount() };
+            Some(count as u64)
+        }
+        #[cfg(windows)]
+        {
+            use windows_sys::Win32::System::DataExchange::GetClipboardSequenceNumber;
+
+            Some(unsafe { GetClipboardSequenceNumber() } as u64)
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn read_x11_text() -> Option<String> {
+    let clipboard = x11_clipboard::Clipboard::new()
+        .map_err(|err| log::warn!("Failed to open X11 clipboard: {err:?}"))
+        .ok()?;
+    let atoms = &clipboard.getter.atoms;
+
+    let data = clipboard
+        .load(
+            atoms.clipboard,
+            atoms.utf8_string,
+            atoms.property,
+            BACKGROUND_LOAD_TIMEOUT,
+        )
+        .map_err(|err| log::warn!("Failed to read clipboard: {err:?}"))
+        .ok()?;
+
+    Some(String::from_utf8_lossy(&data).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fallback_clipboard_keeps_contents() {
+        let mut clipboard = FallbackClipboard::new(None);
+        assert_eq!(clipboard.get_contents().unwrap(), "");
+
+        clipboard.set_contents("copied".to_owned()).unwrap();
+        assert_eq!(clipboard.get_contents().unwrap(), "copied");
+    }
+
+    #[test]
+    fn clipboard_history_keeps_recent_entries() {
+        let mut history = ClipboardHistory::default();
+        assert!(!history.push("ignored"));
+
+        history.set_size(2);
+        for kill in ["first", "second", "second", "third"].iter() {
+            history.push(kill);
+        }
+        assert_eq!(history.entries().collect::<Vec<_>>(), ["third", "second"]);
+
+        history.set_size(1);
+        assert_eq!(history.entries().collect::<Vec<_>>(), ["third"]);
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn cut_buffers_hold_latin1_where_possible() {
+        assert_eq!(encode_latin1("café"), Some(b"caf\xe9".to_vec()));
+        assert_eq!(encode_latin1("kill ring ✓"), None);
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn only_x11_clipboard_is_watched() {
+        assert!(can_watch(Platform::X11));
+        assert!(!can_watch(Platform::Wayland(std::ptr::null_mut())));
+    }
+}
@@ -0,0 +1,130 @@
+//! Mouse pointers drawn from RGBA pixels, in place of the standard
+//! shapes.
+//!
+//! The image is in physical pixels, as it is shown, but its hotspot, the
+//! pixel a click lands on, is in logical pixels like every other position
+//! Lisp sees.  It is scaled by the scale factor of the window the pointer
+//! is built for, or clicks would land off the hotspot on HiDPI monitors.
+//!
+//! winit 0.27 can't make a pointer of an image on any platform, so
+//! windows get the standard shape nearest the image instead, with a
+//! warning.
+
+use winit::{
+    dpi::{LogicalPosition, PhysicalPosition},
+    window::CursorIcon,
+};
+
+pub struct CustomCursor {
+    // Not shown by any window system winit 0.27 supports.
+    #[allow(dead_code)]
+    pub rgba: Vec<u8>,
+    pub width: u16,
+    pub height: u16,
+    pub hotspot: LogicalPosition<f64>,
+}
+
+impl CustomCursor {
+    /// A pointer of WIDTH by HEIGHT RGBA pixels, clicking at HOTSPOT.
+    /// Return why not if RGBA isn't that size or HOTSPOT is negative.
+    pub fn new(
+        rgba: Vec<u8>,
+        width: u16,
+        height: u16,
+        hotspot: LogicalPosition<f64>,
+    ) -> Result<Self, String> {
+        let expected = width as usize * height as usize * 4;
+        if rgba.len() != expected {
+            return Err(format!(
+                "Cursor data is {} bytes, expected {} for a {}x{} RGBA image",
+                rgba.len(),
+                expected,
+                width,
+                height
+            ));
+        }
+        if !(hotspot.x >= 0.0 && hotspot.y >= 0.0) {
+            return Err(format!(
+                "Cursor hotspot ({}, {}) is outside the image",
+                hotspot.x, hotspot.y
+            ));
+        }
+
+        Ok(CustomCursor {
+            rgba,
+            width,
+            height,
+            hotspot,
+        })
+    }
+
+    /// The hotspot in the physical pixels of a window with SCALE_FACTOR,
+    /// kept on the image.
+    pub fn physical_hotspot(&self, scale_factor: f64) -> PhysicalPosition<u16> {
+        let hotspot: PhysicalPosition<f64> = self.hotspot.to_physical(scale_factor);
+
+        PhysicalPosition::new(
+            (hotspot.x.round() as u16).min(self.width.saturating_sub(1)),
+            (hotspot.y.round() as u16).min(self.height.saturating_sub(1)),
+        )
+    }
+
+    /// The standard shape nearest the pointer in a window with
+    /// SCALE_FACTOR, judged by where it clicks: the crosshair for a
+    /// hotspot in the middle third of the image, as of a target, or else
+    /// the arrow, which clicks near its corner.
+    pub fn fallback(&self, scale_factor: f64) -> CursorIcon {
+        let hotspot = self.physical_hotspot(scale_factor);
+        let in_middle = |at: u16, size: u16| {
+            let at = at as u32 * 3;
+            at >= size as u32 && at < size as u32 * 2
+        };
+
+        if in_middle(hotspot.x, self.width) && in_middle(hotspot.y, self.height) {
+            CursorIcon::Crosshair
+        } else {
+            CursorIcon::Arrow
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(width: u16, height: u16, x: f64, y: f64) -> CustomCursor {
+        let rgba = vec![0; width as usize * height as usize * 4];
+        CustomCursor::new(rgba, width, height, LogicalPosition::new(x, y)).unwrap()
+    }
+
+    #[test]
+    fn hotspot_is_scaled_and_kept_on_the_image() {
+        let cursor = cursor(32, 32, 4.0, 10.0);
+        assert_eq!(cursor.physical_hotspot(1.0), PhysicalPosition::new(4, 10));
+        assert_eq!(cursor.physical_hotspot(2.0), PhysicalPosition::new(8, 20));
+        assert_eq!(cursor.physical_hotspot(4.0), PhysicalPosition::new(16, 31));
+    }
+
+    #[test]
+    fn pixels_must_fill_the_image() {
+        let hotspot = LogicalPosition::new(0.0, 0.0);
+        assert!(CustomCursor::new(vec![0; 15], 2, 2, hotspot).is_err());
+        assert!(CustomCursor::new(vec![0; 16], 2, 2, hotspot).is_ok());
+        let outside = LogicalPosition::new(-1.0, 0.0);
+        assert!(CustomCursor::new(vec![0; 16], 2, 2, outside).is_err());
+    }
+
+    #[test]
+    fn fallback_by_hotspot() {
+        assert_eq!(cursor(24, 24, 0.0, 0.0).fallback(1.0), CursorIcon::Arrow);
+        assert_eq!(
+            cursor(24, 24, 12.0, 12.0).fallback(1.0),
+            CursorIcon::Crosshair
+        );
+        // Half way across the image, scaled.
+        assert_eq!(
+            cursor(48, 48, 12.0, 12.0).fallback(2.0),
+            CursorIcon::Crosshair
+        );
+    }
+}
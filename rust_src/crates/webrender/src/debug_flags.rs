@@ -0,0 +1,48 @@
+use webrender::api::DebugFlags;
+
+// The WebRender debug flags Lisp can set, by the name Lisp knows them by.
+const FLAGS: [(&str, DebugFlags); 10] = [
+    ("profiler", DebugFlags::PROFILER_DBG),
+    ("render-targets", DebugFlags::RENDER_TARGET_DBG),
+    ("texture-cache", DebugFlags::TEXTURE_CACHE_DBG),
+    ("gpu-time-queries", DebugFlags::GPU_TIME_QUERIES),
+    ("gpu-sample-queries", DebugFlags::GPU_SAMPLE_QUERIES),
+    ("disable-batching", DebugFlags::DISABLE_BATCHING),
+    ("echo-driver-messages", DebugFlags::ECHO_DRIVER_MESSAGES),
+    ("gpu-cache", DebugFlags::GPU_CACHE_DBG),
+    ("picture-caching", DebugFlags::PICTURE_CACHING_DBG),
+    ("show-overdraw", DebugFlags::SHOW_OVERDRAW),
+];
+
+/// The debug flag called NAME, if there is one.
+pub fn debug_flag(name: &str) -> Option<DebugFlags> {
+    FLAGS
+        .iter()
+        .find(|(flag_name, _)| *flag_name == name)
+        .map(|(_, flag)| *flag)
+}
+
+/// The names of the flags set in FLAGS.  Flags Lisp can't name are left
+/// out.
+pub fn debug_flag_names(flags: DebugFlags) -> impl Iterator<Item = &'static str> {
+    FLAGS
+        .into_iter()
+        .filter(move |(_, flag)| flags.contains(*flag))
+        .map(|(name, _)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        let flags = debug_flag("profiler").unwrap() | debug_flag("show-overdraw").unwrap();
+        assert_eq!(debug_flag("no-such-flag"), None);
+
+        assert_eq!(
+            debug_flag_names(flags | DebugFlags::SMART_PROFILER).collect::<Vec<_>>(),
+            vec!["profiler", "show-overdraw"]
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! Telling input devices apart by the `DeviceId` winit reports, so Lisp
+//! can know whether a pen or a mouse produced input.
+//!
+//! Raw device events arrive at a high rate, so they are only buffered for
+//! Emacs while tracking is turned on, which it is not by default.  So is
+//! the `AxisMotion` of graphics tablets and other absolute pointing
+//! devices, which only makes the device known.  Which axis an index
+//! stands for is up to the platform and the device, and its values are
+//! raw, like the valuators of XInput 2 on X11, in a range winit doesn't
+//! report.  They aren't taken to move the pointer: the window system
+//! moves it for these devices, and that arrives as `CursorMoved`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use once_cell::sync::Lazy;
+use winit::{
+    event::{AxisId, DeviceEvent, DeviceId, Event, WindowEvent},
+    window::WindowId,
+};
+
+use crate::event_loop::GUIEvent;
+use crate::util::RecoverableMutex;
+
+static TRACKING: AtomicBool = AtomicBool::new(false);
+
+// Devices in the order they were first seen; a device's name is its
+// index here.
+static SEEN_DEVICES: Lazy<RecoverableMutex<Vec<DeviceId>>> =
+    Lazy::new(|| RecoverableMutex::new("SEEN_DEVICES", Vec::new()));
+
+pub fn is_tracking() -> bool {
+    TRACKING.load(Ordering::Relaxed)
+}
+
+/// Turn buffering of device events on or off.  Turning it off forgets
+/// the devices seen so far.
+pub fn set_tracking(tracking: bool) {
+    TRACKING.store(tracking, Ordering::Relaxed);
+
+    if !tracking {
+        SEEN_DEVICES.lock().clear();
+    }
+}
+
+/// The name Emacs knows DEVICE_ID by, registering it if it is new.
+pub fn note_device(device_id: DeviceId) -> String {
+    let mut seen = SEEN_DEVICES.lock();
+
+    let index = match seen.iter().position(|id| *id == device_id) {
+        Some(index) => index,
+        None => {
+            seen.push(device_id);
+            seen.len() - 1
+        }
+    };
+
+    device_name(index)
+}
+
+/// Names of the devices seen since tracking was turned on.
+pub fn device_names() -> Vec<String> {
+    (0..SEEN_DEVICES.lock().len()).map(device_name).collect()
+}
+
+fn device_name(index: usize) -> String {
+    format!("wr-device-{index}")
+}
+
+// Whether EVENT identifies a device without flooding Emacs: axis motion
+// and keys already arrive as window events.
+fn is_buffered_device_event(event: &DeviceEvent) -> bool {
+    matches!(
+        event,
+        DeviceEvent::Added
+            | DeviceEvent::Removed
+            | DeviceEvent::MouseMotion { .. }
+            | DeviceEvent::Button { .. }
+    )
+}
+
+/// Push the device event EVENT of DEVICE_ID to BUFFER, returning whether
+/// anything was pushed.  Raw motion is added to motion of the same device
+/// at the end of BUFFER, so Emacs reads at most one motion event for each
+/// burst.
+pub fn buffer_device_event(
+    buffer: &mut Vec<GUIEvent>,
+    device_id: DeviceId,
+    event: DeviceEvent,
+) -> bool {
+    if !is_tracking() || !is_buffered_device_event(&event) {
+        return false;
+    }
+
+    if let DeviceEvent::MouseMotion { delta: (dx, dy) } = event {
+        if let Some(Event::DeviceEvent {
+            device_id: last_id,
+            event: DeviceEvent::MouseMotion { delta },
+        }) = buffer.last_mut()
+        {
+            if *last_id == device_id {
+                delta.0 += dx;
+                delta.1 += dy;
+                return true;
+            }
+        }
+    }
+
+    buffer.push(Event::DeviceEvent { device_id, event });
+    true
+}
+
+/// Push the `AxisMotion` of DEVICE_ID over WINDOW_ID to BUFFER, returning
+/// whether anything was pushed.  Axes report where they are rather than
+/// how far they moved, so motion along an axis replaces motion along the
+/// same axis among the axis motion of the device at the end of BUFFER.
+pub fn buffer_axis_motion(
+    buffer: &mut Vec<GUIEvent>,
+    window_id: WindowId,
+    device_id: DeviceId,
+    axis: AxisId,
+    value: f64,
+) -> bool {
+    if !is_tracking() {
+        return false;
+    }
+
+    push_axis_motion(buffer, window_id, device_id, axis, value);
+    true
+}
+
+fn push_axis_motion(
+    buffer: &mut Vec<GUIEvent>,
+    window_id: WindowId,
+    device_id: DeviceId,
+    axis: AxisId,
+    value: f64,
+) {
+    for e in buffer.iter_mut().rev() {
+        match e {
+            Event::WindowEvent {
+                window_id: last_window,
+                event:
+                    WindowEvent::AxisMotion {
+                        device_id: last_device,
+                        axis: last_axis,
+                        value: last_value,
+                    },
+            } if *last_window == window_id && *last_device == device_id => {
+                if *last_axis == axis {
+                    *last_value = value;
+                    return;
+                }
+            }
+            _ => break,
+        }
+    }
+
+    buffer.push(Event::WindowEvent {
+        window_id,
+        event: WindowEvent::AxisMotion {
+            device_id,
+            axis,
+            value,
+        },
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_motion_is_coalesced() {
+        let device_id = unsafe { DeviceId::dummy() };
+        let mut buffer = Vec::new();
+
+        assert!(!buffer_device_event(
+            &mut buffer,
+            device_id,
+            DeviceEvent::MouseMotion { delta: (1.0, 2.0) }
+        ));
+
+        set_tracking(true);
+        for _ in 0..3 {
+            buffer_device_event(
+                &mut buffer,
+                device_id,
+                DeviceEvent::MouseMotion { delta: (1.0, 2.0) },
+            );
+        }
+        set_tracking(false);
+
+        match buffer.as_slice() {
+            [Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            }] => assert_eq!(*delta, (3.0, 6.0)),
+            _ => panic!("Raw motion wasn't coalesced"),
+        }
+    }
+
+    #[test]
+    fn axis_motion_is_coalesced_by_axis() {
+        let window_id = unsafe { WindowId::dummy() };
+        let device_id = unsafe { DeviceId::dummy() };
+        let mut buffer = Vec::new();
+
+        for (axis, value) in [(0, 1.0), (1, 2.0), (0, 3.0), (1, 4.0), (2, 0.5)] {
+            push_axis_motion(&mut buffer, window_id, device_id, axis, value);
+        }
+
+        let axes: Vec<_> = buffer
+            .iter()
+            .map(|e| match e {
+                Event::WindowEvent {
+                    event: WindowEvent::AxisMotion { axis, value, .. },
+                    ..
+                } => (*axis, *value),
+                _ => panic!("Expected axis motion"),
+            })
+            .collect();
+        assert_eq!(axes, vec![(0, 3.0), (1, 4.0), (2, 0.5)]);
+    }
+}
@@ -1,9 +1,29 @@
+//! The winit event loop, and `wr_select1`, which waits on it together
+//! with Emacs's file descriptors.
+//!
+//! How window events are pumped depends on the platform:
+//!
+//! - macOS and Windows: `wr_select1` runs the event loop on the main
+//!   thread with `run_return`, as macOS only allows it there.
+//! - Linux and the BSDs, on X11 or Wayland: the event loop runs on a
+//!   thread of its own, see `event_thread`.
+//! - Anywhere else, like iOS, Android or the web, `run_return` is missing
+//!   or unreliable and winit can't run off the main thread, so
+//!   `wr_select1` only waits on Emacs's descriptors and no window system
+//!   can be opened.
+
 use errno::{set_errno, Errno};
 use nix::sys::signal::{self, Signal};
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+use std::cell::RefCell;
 use std::{
-    cell::RefCell,
-    ptr,
-    sync::Mutex,
+    cell::Cell,
+    collections::HashMap,
+    fmt, ptr,
+    sync::{
+        atomic::{AtomicBool, AtomicI32, Ordering},
+        MutexGuard,
+    },
     time::{Duration, Instant},
 };
 
@@ -23,18 +43,45 @@ use once_cell::sync::Lazy;
 #[cfg(all(feature = "wayland", not(any(target_os = "macos", windows))))]
 use winit::platform::wayland::EventLoopWindowTargetExtWayland;
 use winit::{
-    event::{Event, StartCause, WindowEvent},
-    event_loop::{ControlFlow, EventLoop, EventLoopProxy},
+    dpi::{LogicalPosition, PhysicalPosition},
+    event::{Event, WindowEvent},
+    event_loop::{EventLoopProxy, EventLoopWindowTarget},
     monitor::MonitorHandle,
+    window::{Window, WindowBuilder, WindowId},
+};
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+use winit::{
+    event::StartCause,
+    event_loop::{ControlFlow, EventLoop},
     platform::run_return::EventLoopExtRunReturn,
-    window::Window,
-    window::WindowId,
 };
 
+use surfman::Adapter;
 use surfman::Connection;
 use surfman::SurfaceType;
+use webrender::api::DebugFlags;
 use webrender_surfman::WebrenderSurfman;
 
+use crate::clipboard::{
+    ClipboardHistory, ClipboardResult, ClipboardTargets, ClipboardWatcher, ConnectClipboard,
+    FallbackClipboard, OwnedSelection, CLIPBOARD_CHANGED_EVENT, CLIPBOARD_READ_EVENT, CUT_BUFFERS,
+    TEXT_TARGET,
+};
+use crate::devices::{buffer_axis_motion, buffer_device_event};
+#[cfg(all(unix, not(target_os = "macos")))]
+use crate::event_thread::{wait_for_resize, EventThread, ThreadEvent};
+#[cfg(target_os = "macos")]
+use crate::idle_sleep::IdleSleep;
+use crate::input_throttle::{is_key_event, is_throttled_event, InputThrottle};
+use crate::key_repeat::KeyRepeat;
+use crate::modifiers::ModifierRemap;
+use crate::select_stats::SELECT_STATS;
+use crate::util::RecoverableMutex;
+use crate::wakeup_events::WakeupEvents;
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+use crate::watchdog::{Watchdog, WATCHDOG_EVENT};
+use crate::window_state::{WindowRegistry, WindowState};
+
 use emacs::bindings::{inhibit_window_system, make_timespec, thread_select};
 
 pub type GUIEvent = Event<'static, i32>;
@@ -50,61 +97,261 @@ pub enum Platform {
 
 unsafe impl Send for Platform {}
 
+impl Platform {
+    /// The window system EVENT_LOOP is connected to.  Other code asks
+    /// `WrEventLoop::platform` rather than detecting it again.
+    pub fn detect(_event_loop: &EventLoopWindowTarget<i32>) -> Self {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            if _event_loop.is_wayland() {
+                let display = _event_loop.wayland_display().unwrap_or(ptr::null_mut());
+                return Platform::Wayland(display);
+            }
+
+            Platform::X11
+        }
+        #[cfg(target_os = "macos")]
+        {
+            Platform::MacOS
+        }
+        #[cfg(windows)]
+        {
+            Platform::Windows
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::X11 => "x11",
+            Platform::Wayland(_) => "wayland",
+            Platform::MacOS => "macos",
+            Platform::Windows => "windows",
+        }
+    }
+}
+
+/// The surfman adapter webrender renders with. It is selected with the
+/// `NG_WR_ADAPTER` environment variable, one of `default`, `hardware`,
+/// `low-power` or `software`.  ANGLE on Windows is chosen at build time
+/// with the `angle` feature.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AdapterPreference {
+    Default,
+    Hardware,
+    LowPower,
+    Software,
+}
+
+impl AdapterPreference {
+    // Order in which adapters are tried when creating one fails. We
+    // start from the preferred adapter and only move down the list.
+    const FALLBACK_ORDER: [AdapterPreference; 4] = [
+        AdapterPreference::Default,
+        AdapterPreference::Hardware,
+        AdapterPreference::LowPower,
+        AdapterPreference::Software,
+    ];
+
+    pub fn from_env() -> Self {
+        match std::env::var("NG_WR_ADAPTER").as_deref() {
+            Err(_) | Ok("") | Ok("default") => AdapterPreference::Default,
+            Ok("hardware") => AdapterPreference::Hardware,
+            Ok("low-power") => AdapterPreference::LowPower,
+            Ok("software") => AdapterPreference::Software,
+            Ok(other) => {
+                log::warn!("Unknown NG_WR_ADAPTER {other:?}, using the default adapter");
+                AdapterPreference::Default
+            }
+        }
+    }
+
+    fn create(self, connection: &Connection) -> Result<Adapter, surfman::Error> {
+        match self {
+            AdapterPreference::Default => connection.create_adapter(),
+            AdapterPreference::Hardware => connection.create_hardware_adapter(),
+            AdapterPreference::LowPower => connection.create_low_power_adapter(),
+            AdapterPreference::Software => connection.create_software_adapter(),
+        }
+    }
+
+    /// Create the preferred adapter, falling back to the adapters after
+    /// it in `FALLBACK_ORDER`. Return the adapter together with the kind
+    /// that was actually created.
+    pub fn create_adapter(
+        self,
+        connection: &Connection,
+    ) -> Result<(Adapter, AdapterPreference), surfman::Error> {
+        let start = Self::FALLBACK_ORDER
+            .iter()
+            .position(|p| *p == self)
+            .unwrap_or(0);
+
+        let mut last_err = surfman::Error::NoAdapterFound;
+        for preference in &Self::FALLBACK_ORDER[start..] {
+            match preference.create(connection) {
+                Ok(adapter) => return Ok((adapter, *preference)),
+                Err(err) => {
+                    log::warn!("Failed to create {preference:?} adapter: {err:?}");
+                    last_err = err;
+                }
+            }
+        }
+
+        Err(last_err)
+    }
+}
+
+/// Why webrender couldn't be set up to render to a window.
+#[derive(Debug)]
+pub enum WebrenderInitError {
+    Connection(surfman::Error),
+    Adapter(surfman::Error),
+    NativeWidget(surfman::Error),
+    Surface(surfman::Error),
+}
+
+impl fmt::Display for WebrenderInitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WebrenderInitError::Connection(err) => {
+                write!(f, "Failed to connect to the display server: {err:?}")
+            }
+            WebrenderInitError::Adapter(err) => {
+                write!(f, "No usable graphics adapter (see NG_WR_ADAPTER): {err:?}")
+            }
+            WebrenderInitError::NativeWidget(err) => {
+                write!(f, "Failed to render to the window: {err:?}")
+            }
+            WebrenderInitError::Surface(err) => {
+                write!(f, "Failed to create a rendering surface: {err:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WebrenderInitError {}
+
 pub struct WrEventLoop {
     clipboard: Box<dyn ClipboardProvider>,
+    clipboard_targets: ClipboardTargets,
+    clipboard_watcher: ClipboardWatcher,
+    clipboard_history: ClipboardHistory,
+    // Selections Emacs owns, by name.
+    selections: HashMap<String, OwnedSelection>,
+    platform: Platform,
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
     el: EventLoop<i32>,
+    #[cfg(all(unix, not(target_os = "macos")))]
+    event_thread: EventThread,
     pub connection: Option<Connection>,
+    adapter_preference: AdapterPreference,
+    windows: WindowRegistry,
+    key_repeat: KeyRepeat,
+    modifier_remap: ModifierRemap,
+    wakeup_events: WakeupEvents,
+    debug_flags: DebugFlags,
+    vsync: bool,
+    input_throttle: InputThrottle,
+    #[cfg(target_os = "macos")]
+    idle_sleep: IdleSleep,
+    watchdog_interval: Option<Duration>,
+    // Spawned the first time `run_return` is guarded.
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    watchdog: Option<Watchdog>,
 }
 
 unsafe impl Send for WrEventLoop {}
 unsafe impl Sync for WrEventLoop {}
 
 impl WrEventLoop {
-    pub fn el(&self) -> &EventLoop<i32> {
-        &self.el
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    pub fn build_window(&self, window_builder: WindowBuilder) -> Window {
+        window_builder.build(&self.el).unwrap()
     }
 
-    pub fn connection(&mut self) -> &Connection {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn build_window(&self, window_builder: WindowBuilder) -> Window {
+        self.event_thread
+            .call(move |target| window_builder.build(target))
+            .unwrap()
+    }
+
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    pub fn connection(&mut self) -> Result<&Connection, WebrenderInitError> {
         if self.connection.is_none() {
-            self.open_native_display();
+            self.open_native_display()?;
         }
-        self.connection.as_ref().unwrap()
+        Ok(self.connection.as_ref().unwrap())
     }
 
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
     pub fn create_proxy(&self) -> EventLoopProxy<i32> {
         self.el.create_proxy()
     }
 
-    pub fn new_webrender_surfman(&mut self, window: &Window) -> WebrenderSurfman {
-        let connection = self.connection();
-        let adapter = connection
-            .create_adapter()
-            .expect("Failed to create adapter");
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn create_proxy(&self) -> EventLoopProxy<i32> {
+        self.event_thread.create_proxy()
+    }
+
+    pub fn new_webrender_surfman(
+        &mut self,
+        window: &Window,
+    ) -> Result<WebrenderSurfman, WebrenderInitError> {
+        self.new_webrender_surfman_with(window, self.adapter_preference)
+    }
+
+    /// Like `new_webrender_surfman`, but starting from ADAPTER_PREFERENCE
+    /// rather than the adapter `NG_WR_ADAPTER` selects.
+    pub fn new_webrender_surfman_with(
+        &mut self,
+        window: &Window,
+        adapter_preference: AdapterPreference,
+    ) -> Result<WebrenderSurfman, WebrenderInitError> {
+        let connection = self.connection()?;
+        let (adapter, _) = adapter_preference
+            .create_adapter(connection)
+            .map_err(WebrenderInitError::Adapter)?;
         let native_widget = connection
             .create_native_widget_from_winit_window(&window)
-            .expect("Failed to create native widget");
+            .map_err(WebrenderInitError::NativeWidget)?;
         let surface_type = SurfaceType::Widget { native_widget };
-        let webrender_surfman = WebrenderSurfman::create(&connection, &adapter, surface_type)
-            .expect("Failed to create WR surfman");
 
-        webrender_surfman
+        WebrenderSurfman::create(&connection, &adapter, surface_type)
+            .map_err(WebrenderInitError::Surface)
     }
 
-    pub fn open_native_display(&mut self) -> &Option<Connection> {
-        let window_builder = winit::window::WindowBuilder::new().with_visible(false);
-        let window = window_builder.build(&self.el).unwrap();
+    // Connect surfman to the display, through a hidden window as surfman
+    // needs one.  Only frames need the connection, so it is opened when
+    // the first is created: clipboard and monitor queries go through the
+    // event loop alone, and work without a GPU, as in a daemon that
+    // hasn't created a frame yet.
+    fn open_native_display(&mut self) -> Result<&Connection, WebrenderInitError> {
+        let window_builder = WindowBuilder::new().with_visible(false);
+        let window = self.build_window(window_builder);
 
         // Initialize surfman
         let connection =
-            Connection::from_winit_window(&window).expect("Failed to create connection");
+            Connection::from_winit_window(&window).map_err(WebrenderInitError::Connection)?;
 
-        self.connection = Some(connection);
+        // Probe the adapter once so the one in use shows up in the log at
+        // startup, rather than when the first frame is created.
+        match self.adapter_preference.create_adapter(&connection) {
+            Ok((_, used)) => log::info!(
+                "Webrender adapter: {used:?} (requested {:?})",
+                self.adapter_preference
+            ),
+            Err(err) => log::error!("No usable webrender adapter: {err:?}"),
+        }
 
-        &self.connection
+        Ok(self.connection.insert(connection))
     }
 
-    pub fn wait_for_window_resize(&mut self, target_window_id: WindowId) {
-        let deadline = Instant::now() + Duration::from_millis(100);
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    fn wait_for_window_resize(&mut self, target_window_id: WindowId, deadline: Instant) {
         self.el.run_return(|e, _, control_flow| match e {
             Event::NewEvents(StartCause::Init) => {
                 *control_flow = ControlFlow::WaitUntil(deadline);
@@ -125,75 +372,692 @@ impl WrEventLoop {
         });
     }
 
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
     pub fn get_available_monitors(&self) -> impl Iterator<Item = MonitorHandle> {
         self.el.available_monitors()
     }
 
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn get_available_monitors(&self) -> impl Iterator<Item = MonitorHandle> {
+        self.event_thread
+            .call(|target| target.available_monitors().collect::<Vec<_>>())
+            .into_iter()
+    }
+
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
     pub fn get_primary_monitor(&self) -> MonitorHandle {
         self.el
             .primary_monitor()
             .unwrap_or_else(|| -> MonitorHandle { self.get_available_monitors().next().unwrap() })
     }
 
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn get_primary_monitor(&self) -> MonitorHandle {
+        self.event_thread
+            .call(|target| target.primary_monitor())
+            .unwrap_or_else(|| -> MonitorHandle { self.get_available_monitors().next().unwrap() })
+    }
+
+    /// Monitor INDEX in the order of `display-monitor-attributes-list`.
+    /// An index out of range stands for the primary monitor.
+    pub fn get_monitor(&self, index: usize) -> MonitorHandle {
+        let monitors: Vec<_> = self.get_available_monitors().collect();
+        let primary_monitor = self.get_primary_monitor();
+        let primary_index = monitors
+            .iter()
+            .position(|monitor| monitor.name() == primary_monitor.name())
+            .unwrap_or(0);
+        let has_size: Vec<_> = monitors
+            .iter()
+            .map(|monitor| monitor.size().width > 0)
+            .collect();
+
+        match monitor_attribute_order(&has_size, primary_index).get(index) {
+            Some(&index) => monitors[index].clone(),
+            None => {
+                log::warn!("No monitor {index}, using the primary monitor");
+                self.get_primary_monitor()
+            }
+        }
+    }
+
     pub fn get_clipboard(&mut self) -> &mut Box<dyn ClipboardProvider> {
         &mut self.clipboard
     }
+
+    /// Set the clipboard to CONTENTS on behalf of SELECTION, taking
+    /// ownership of SELECTION.
+    pub fn own_selection(&mut self, selection: &str, contents: String) {
+        if let Err(err) = self.clipboard.set_contents(contents.clone()) {
+            log::warn!("Failed to set the clipboard: {err}");
+        }
+        self.remember_clipboard(&contents);
+        let owner = self.clipboard_targets.owner().filter(|&owner| owner != 0);
+        self.selections
+            .insert(selection.to_owned(), OwnedSelection::new(contents, owner));
+    }
+
+    /// Whether Emacs still owns SELECTION, judged by the window owning
+    /// the clipboard without reading it.  None if owners can't be asked
+    /// on this platform, or couldn't be when SELECTION was set.
+    pub fn owns_selection(&self, selection: &str) -> Option<bool> {
+        let current = self.clipboard_targets.owner()?;
+
+        match self.selections.get(selection) {
+            Some(owned) => owned.owner.map(|owner| owner == current),
+            None => Some(false),
+        }
+    }
+
+    /// When Emacs took ownership of SELECTION, if it still owns it.
+    /// Ownership is lost once another client owns the clipboard, or
+    /// where owners can't be asked, once it holds anything else.
+    pub fn selection_owned_since(&mut self, selection: &str) -> Option<u64> {
+        let timestamp = self.selections.get(selection)?.timestamp;
+
+        let owned = match self.owns_selection(selection) {
+            Some(owned) => owned,
+            None => {
+                let contents = self.clipboard.get_contents().ok();
+                contents.as_ref() == self.selections.get(selection).map(|owned| &owned.contents)
+            }
+        };
+
+        if owned {
+            return Some(timestamp);
+        }
+
+        self.selections.remove(selection);
+        None
+    }
+
+    /// Have the clipboard manager take a copy of the clipboard, if Emacs
+    /// owns it, so it outlives Emacs.  Return whether the manager took
+    /// one; it can't where there is no clipboard manager.
+    pub fn save_clipboard_to_manager(&mut self) -> bool {
+        if self.selection_owned_since("CLIPBOARD").is_none() {
+            return false;
+        }
+
+        self.clipboard_targets.save_to_manager().unwrap_or(false)
+    }
+
+    /// Whether the clipboard has an owner.  Where owners can't be
+    /// queried, whether the clipboard has any contents.
+    pub fn selection_exists(&mut self) -> bool {
+        self.clipboard_targets.has_owner().unwrap_or_else(|| {
+            self.clipboard
+                .get_contents()
+                .map_or(false, |contents| !contents.is_empty())
+        })
+    }
+
+    /// Targets the clipboard can be read as.  Where targets can't be
+    /// listed, only the text target is offered.
+    pub fn clipboard_targets(&self) -> Vec<String> {
+        self.clipboard_targets
+            .targets()
+            .unwrap_or_else(|| vec![TEXT_TARGET.to_owned()])
+    }
+
+    /// Start reading the clipboard as text in the background under ID,
+    /// returning false if it has to be read with `get_clipboard`.
+    pub fn read_clipboard_in_background(&self, id: u64) -> bool {
+        self.clipboard_targets.spawn_read(id, self.create_proxy())
+    }
+
+    /// How often the clipboard is checked for changes, or None if it
+    /// isn't watched.
+    pub fn clipboard_watch_interval(&self) -> Option<Duration> {
+        self.clipboard_watcher.interval()
+    }
+
+    /// Check the clipboard for changes every INTERVAL, or stop with None.
+    /// Return false if the clipboard can't be watched on this platform.
+    pub fn set_clipboard_watch_interval(&mut self, interval: Option<Duration>) -> bool {
+        let proxy = self.create_proxy();
+        self.clipboard_watcher
+            .set_interval(interval, self.platform, proxy)
+    }
+
+    /// Connect to the clipboard of the window system again, as after its
+    /// own connection to the X server broke.  The Wayland clipboard goes
+    /// through the connection of the event loop, so it is only set up
+    /// again on it: nothing survives the compositor going away.
+    /// Return false if it can't be connected to, in which case a clipboard
+    /// of our own is used until it can.  Reads in the background and the
+    /// clipboard watch use connections of their own and are unaffected.
+    pub fn reconnect_clipboard(&mut self) -> bool {
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        let (platform, clipboard, clipboard_targets) = {
+            let platform = Platform::detect(&self.el);
+            (
+                platform,
+                connect_platform_clipboard(platform),
+                ClipboardTargets::new(platform),
+            )
+        };
+        #[cfg(all(unix, not(target_os = "macos")))]
+        let (platform, clipboard, clipboard_targets) = self.event_thread.call(|target| {
+            let platform = Platform::detect(target);
+            (
+                platform,
+                connect_platform_clipboard(platform),
+                ClipboardTargets::new(platform),
+            )
+        });
+
+        self.platform = platform;
+        self.clipboard_targets = clipboard_targets;
+
+        match clipboard {
+            Ok(clipboard) => {
+                self.clipboard = clipboard;
+                true
+            }
+            Err(err) => {
+                log::warn!("Failed to reconnect to the clipboard: {err}");
+                self.clipboard = fallback_clipboard(platform);
+                false
+            }
+        }
+    }
+
+    /// The clipboard contents as TARGET, or None if unavailable.
+    pub fn clipboard_get_target(&mut self, target: &str) -> Option<Vec<u8>> {
+        if let Some(data) = self.clipboard_targets.get(target) {
+            return Some(data);
+        }
+
+        if target == TEXT_TARGET {
+            return self.clipboard.get_contents().ok().map(String::into_bytes);
+        }
+
+        None
+    }
+
+    /// The clipboard contents as TARGET, undecoded.  Text is read as it
+    /// is, without checking it is valid UTF-8, where the platform allows.
+    pub fn clipboard_bytes(&mut self, target: &str) -> ClipboardResult<Vec<u8>> {
+        match self.clipboard_targets.load(target) {
+            Some(Ok(data)) => return Ok(data),
+            Some(Err(err)) if target != TEXT_TARGET => return Err(err),
+            _ => {}
+        }
+
+        if target == TEXT_TARGET {
+            return self.clipboard.get_contents().map(String::into_bytes);
+        }
+
+        Err(format!("The clipboard can't be read as {target}").into())
+    }
+
+    /// Set the clipboard to DATA as TARGET.  Where only text can be put on
+    /// the clipboard, TARGET must be `TEXT_TARGET` and DATA valid UTF-8.
+    pub fn set_clipboard_bytes(&mut self, target: &str, data: Vec<u8>) -> ClipboardResult<()> {
+        if let Some(text) = (target == TEXT_TARGET)
+            .then(|| std::str::from_utf8(&data).ok())
+            .flatten()
+        {
+            self.remember_clipboard(text);
+        }
+
+        if let Some(stored) = self.clipboard_targets.store(target, &data) {
+            return stored;
+        }
+
+        if target != TEXT_TARGET {
+            return Err(format!("The clipboard can't hold {target}").into());
+        }
+
+        let text = String::from_utf8(data)
+            .map_err(|_| "The clipboard can only hold UTF-8 text on this platform")?;
+        self.clipboard.set_contents(text)
+    }
+
+    /// How many recent clipboard entries are kept, 0 if none are.
+    pub fn clipboard_history_size(&self) -> usize {
+        self.clipboard_history.size()
+    }
+
+    /// Keep the SIZE most recent clipboard entries, or none if SIZE is 0.
+    pub fn set_clipboard_history_size(&mut self, size: usize) {
+        self.clipboard_history.set_size(size);
+    }
+
+    /// The recent clipboard entries, most recent first.  On X11 they are
+    /// read from the cut buffers, which outlive Emacs and are shared with
+    /// other clients, so at most `CUT_BUFFERS` of them are kept there.
+    pub fn clipboard_history(&self) -> Vec<String> {
+        let size = self.clipboard_history.size();
+        if size == 0 {
+            return Vec::new();
+        }
+
+        match self.clipboard_targets.cut_buffers() {
+            Some(mut buffers) => {
+                buffers.truncate(size.min(CUT_BUFFERS));
+                buffers
+            }
+            None => self.clipboard_history.entries().cloned().collect(),
+        }
+    }
+
+    // Add CONTENTS, just put on the clipboard, to the history.
+    fn remember_clipboard(&mut self, contents: &str) {
+        if self.clipboard_history.push(contents) {
+            self.clipboard_targets.push_cut_buffer(contents);
+        }
+    }
+
+    pub fn register_window(&mut self, window: &Window) {
+        self.windows.register(window.id(), window.scale_factor());
+
+        // Only Windows reports the theme before it changes.
+        #[cfg(windows)]
+        if let Some(state) = self.windows.get_mut(&window.id()) {
+            use winit::platform::windows::WindowExtWindows;
+            state.theme = Some(window.theme());
+        }
+
+        #[cfg(target_os = "macos")]
+        self.idle_sleep.set_monitor(window.id(), window.current_monitor());
+    }
+
+    pub fn unregister_window(&mut self, window_id: &WindowId) {
+        self.windows.unregister(window_id);
+        self.key_repeat.forget_window(window_id);
+
+        #[cfg(target_os = "macos")]
+        self.idle_sleep.forget_window(window_id);
+    }
+
+    pub fn key_repeat(&self) -> Option<(Duration, Duration)> {
+        self.key_repeat.rate()
+    }
+
+    pub fn set_key_repeat(&mut self, rate: Option<(Duration, Duration)>) {
+        self.key_repeat.set_rate(rate);
+    }
+
+    pub fn set_modifier_remap(&mut self, modifier_remap: ModifierRemap) {
+        self.modifier_remap = modifier_remap;
+    }
+
+    pub fn wakeup_events(&self) -> WakeupEvents {
+        self.wakeup_events
+    }
+
+    pub fn set_wakeup_events(&mut self, wakeup_events: WakeupEvents) {
+        self.wakeup_events = wakeup_events;
+    }
+
+    /// The WebRender debug flags of every renderer, including those of
+    /// frames created later.
+    pub fn debug_flags(&self) -> DebugFlags {
+        self.debug_flags
+    }
+
+    pub fn set_debug_flags(&mut self, debug_flags: DebugFlags) {
+        self.debug_flags = debug_flags;
+    }
+
+    /// Whether presents wait for the vertical blank of the display, in
+    /// windows created later as well.
+    pub fn vsync(&self) -> bool {
+        self.vsync
+    }
+
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.vsync = vsync;
+    }
+
+    /// How long past its deadline `run_return` may run before the
+    /// watchdog makes it exit, or None without a watchdog.
+    pub fn watchdog_interval(&self) -> Option<Duration> {
+        self.watchdog_interval
+    }
+
+    pub fn set_watchdog_interval(&mut self, interval: Option<Duration>) {
+        self.watchdog_interval = interval;
+    }
+
+    /// The minimum interval between reports of pointer motion and other
+    /// input that only leads to redisplay, or None without a limit.
+    pub fn report_interval(&self) -> Option<Duration> {
+        self.input_throttle.interval()
+    }
+
+    pub fn set_report_interval(&mut self, interval: Option<Duration>) {
+        self.input_throttle.set_interval(interval);
+    }
+
+    /// How long input is held back to be reported in batches, or None
+    /// if it is reported as it arrives, and whether keys are too.
+    pub fn input_batch(&self) -> (Option<Duration>, bool) {
+        self.input_throttle.batch()
+    }
+
+    pub fn set_input_batch(&mut self, batch: Option<Duration>, keys: bool) {
+        self.input_throttle.set_batch(batch, keys);
+    }
+
+    /// The scale factor last applied to WINDOW_ID.
+    pub fn scale_factor(&self, window_id: &WindowId) -> f64 {
+        self.windows.scale_factor(window_id)
+    }
+
+    /// The window the pointer is over, if any.
+    pub fn pointer_window(&self) -> Option<WindowId> {
+        self.windows.pointer_window()
+    }
+
+    /// Where the pointer is in WINDOW_ID, in logical pixels, or None if
+    /// it isn't over the window.
+    pub fn pointer_position(&self, window_id: &WindowId) -> Option<LogicalPosition<f64>> {
+        self.windows.pointer_position(window_id)
+    }
+
+    pub fn window_state(&self, window_id: &WindowId) -> Option<&WindowState> {
+        self.windows.get(window_id)
+    }
+
+    pub fn window_state_mut(&mut self, window_id: &WindowId) -> Option<&mut WindowState> {
+        self.windows.get_mut(window_id)
+    }
+
+    /// Convert a logical position to physical pixels using the current
+    /// scale factor of WINDOW_ID.
+    pub fn logical_to_physical(
+        &self,
+        window_id: &WindowId,
+        position: LogicalPosition<f64>,
+    ) -> PhysicalPosition<f64> {
+        self.windows.logical_to_physical(window_id, position)
+    }
+
+    /// Convert a physical position, e.g. from `CursorMoved`, to logical
+    /// pixels using the current scale factor of WINDOW_ID.
+    pub fn physical_to_logical(
+        &self,
+        window_id: &WindowId,
+        position: PhysicalPosition<f64>,
+    ) -> LogicalPosition<f64> {
+        self.windows.physical_to_logical(window_id, position)
+    }
 }
 
-fn build_clipboard(_event_loop: &EventLoop<i32>) -> Box<dyn ClipboardProvider> {
-    #[cfg(all(unix, not(target_os = "macos")))]
+// The indices of the monitors that `make_monitor_attribute_list` lists,
+// in its order: the primary monitor at PRIMARY_INDEX first, then the
+// others in reverse, skipping those without HAS_SIZE.
+fn monitor_attribute_order(has_size: &[bool], primary_index: usize) -> Vec<usize> {
+    let primary_index = Some(primary_index).filter(|&index| has_size.get(index) == Some(&true));
+
+    primary_index
+        .into_iter()
+        .chain(
+            (0..has_size.len())
+                .rev()
+                .filter(|&index| has_size[index] && Some(index) != primary_index),
+        )
+        .collect()
+}
+
+// Track the window state winit doesn't let us query. Minimizing is
+// reported as a zero sized resize on some platforms, while regaining
+// focus or a real size means the window has been restored.  The window
+// under the pointer is tracked from crossing events.
+fn update_window_state(windows: &mut WindowRegistry, window_id: WindowId, event: &WindowEvent) {
+    if let WindowEvent::ScaleFactorChanged {
+        scale_factor,
+        new_inner_size,
+    } = event
     {
-        if _event_loop.is_wayland() {
-            let wayland_display = _event_loop
-                .wayland_display()
-                .expect("Fetch Wayland display failed");
-            let (_, clipboard) = unsafe { create_clipboards_from_external(wayland_display) };
-            Box::new(clipboard)
-        } else {
-            Box::new(X11ClipboardContext::<Clipboard>::new().unwrap())
+        windows.scale_factor_changed(window_id, *scale_factor, **new_inner_size, Instant::now());
+        return;
+    }
+
+    match event {
+        WindowEvent::CursorEntered { .. } => windows.pointer_entered(window_id),
+        WindowEvent::CursorLeft { .. } => windows.pointer_left(window_id),
+        _ => {}
+    }
+
+    let state = match windows.get_mut(&window_id) {
+        Some(state) => state,
+        None => return,
+    };
+
+    match event {
+        WindowEvent::Resized(size) => {
+            state.minimized = size.width == 0 || size.height == 0;
+        }
+        WindowEvent::Focused(focused) => {
+            state.focused = *focused;
+            state.minimized &= !focused;
+        }
+        WindowEvent::Occluded(occluded) => {
+            state.occluded = *occluded;
+            state.needs_redraw |= !occluded;
+        }
+        WindowEvent::ThemeChanged(theme) => {
+            state.theme = Some(*theme);
         }
+        // Every motion is seen here, before any is dropped or merged on
+        // the way to Emacs.
+        WindowEvent::CursorMoved { position, .. } => {
+            state.pointer = Some(*position);
+        }
+        WindowEvent::CursorLeft { .. } => {
+            state.pointer = None;
+        }
+        _ => {}
+    }
+}
+
+// Connect to the clipboard of a platform that needs no display handle.
+fn connect_clipboard() -> ClipboardResult<Box<dyn ClipboardProvider>> {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        Ok(Box::new(X11ClipboardContext::<Clipboard>::new()?))
     }
     #[cfg(target_os = "windows")]
     {
-        return Box::new(WindowsClipboardContext::new().unwrap());
+        return Ok(Box::new(WindowsClipboardContext::new()?));
     }
     #[cfg(target_os = "macos")]
     {
-        return Box::new(OSXClipboardContext::new().unwrap());
+        return Ok(Box::new(OSXClipboardContext::new()?));
+    }
+}
+
+// Connect to the clipboard of PLATFORM.
+fn connect_platform_clipboard(_platform: Platform) -> ClipboardResult<Box<dyn ClipboardProvider>> {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        if let Platform::Wayland(wayland_display) = _platform {
+            if wayland_display.is_null() {
+                return Err("No Wayland display".into());
+            }
+
+            let (_, clipboard) = unsafe { create_clipboards_from_external(wayland_display) };
+            return Ok(Box::new(clipboard));
+        }
     }
+
+    connect_clipboard()
 }
 
-pub static EVENT_LOOP: Lazy<Mutex<WrEventLoop>> = Lazy::new(|| {
+// Without access to the platform's clipboard, e.g. with no X server or
+// in a sandbox, fall back to a clipboard of our own rather than
+// failing to start.
+fn build_clipboard(platform: Platform) -> Box<dyn ClipboardProvider> {
+    match connect_platform_clipboard(platform) {
+        Ok(clipboard) => clipboard,
+        Err(err) => {
+            log::warn!("Failed to open the clipboard, using a local clipboard: {err}");
+            fallback_clipboard(platform)
+        }
+    }
+}
+
+// A clipboard of our own for PLATFORM.  Only clipboards that need no
+// display handle are tried again later.
+fn fallback_clipboard(platform: Platform) -> Box<dyn ClipboardProvider> {
+    let connect = match platform {
+        Platform::Wayland(_) => None,
+        _ => Some(connect_clipboard as ConnectClipboard),
+    };
+
+    Box::new(FallbackClipboard::new(connect))
+}
+
+// Every `WrEventLoop` field is valid on its own and is only updated by
+// single assignments or map inserts and removals, so a panic while the
+// lock is held (typically a failed surfman `expect`) can at worst lose
+// that one update.  Recovering keeps one failed call from making every
+// later `wr_select1` panic.
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub static EVENT_LOOP: Lazy<RecoverableMutex<WrEventLoop>> = Lazy::new(|| {
     let el = winit::event_loop::EventLoopBuilder::<i32>::with_user_event().build();
-    let clipboard = build_clipboard(&el);
+    let platform = Platform::detect(&el);
+    let clipboard = build_clipboard(platform);
+    let clipboard_targets = ClipboardTargets::new(platform);
     let connection = None;
 
-    Mutex::new(WrEventLoop {
-        clipboard,
-        el,
-        connection,
-    })
+    RecoverableMutex::new(
+        "EVENT_LOOP",
+        WrEventLoop {
+            clipboard,
+            clipboard_targets,
+            clipboard_watcher: ClipboardWatcher::default(),
+            clipboard_history: ClipboardHistory::default(),
+            selections: HashMap::new(),
+            platform,
+            el,
+            connection,
+            adapter_preference: AdapterPreference::from_env(),
+            windows: WindowRegistry::default(),
+            key_repeat: KeyRepeat::new(),
+            modifier_remap: ModifierRemap::default(),
+            wakeup_events: WakeupEvents::default(),
+            debug_flags: DebugFlags::empty(),
+            vsync: false,
+            input_throttle: InputThrottle::new(),
+            #[cfg(target_os = "macos")]
+            idle_sleep: IdleSleep::default(),
+            watchdog_interval: None,
+            watchdog: None,
+        },
+    )
 });
 
-pub static EVENT_BUFFER: Lazy<Mutex<Vec<GUIEvent>>> = Lazy::new(|| Mutex::new(Vec::new()));
+#[cfg(all(unix, not(target_os = "macos")))]
+pub static EVENT_LOOP: Lazy<RecoverableMutex<WrEventLoop>> = Lazy::new(|| {
+    let event_thread = EventThread::spawn();
+    let (platform, clipboard, clipboard_targets) = event_thread.call(|target| {
+        let platform = Platform::detect(target);
+        (
+            platform,
+            build_clipboard(platform),
+            ClipboardTargets::new(platform),
+        )
+    });
+    let connection = None;
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
-pub struct FdSet(pub *mut fd_set);
+    RecoverableMutex::new(
+        "EVENT_LOOP",
+        WrEventLoop {
+            clipboard,
+            clipboard_targets,
+            clipboard_watcher: ClipboardWatcher::default(),
+            clipboard_history: ClipboardHistory::default(),
+            selections: HashMap::new(),
+            platform,
+            event_thread,
+            connection,
+            adapter_preference: AdapterPreference::from_env(),
+            windows: WindowRegistry::default(),
+            key_repeat: KeyRepeat::new(),
+            modifier_remap: ModifierRemap::default(),
+            wakeup_events: WakeupEvents::default(),
+            debug_flags: DebugFlags::empty(),
+            vsync: false,
+            input_throttle: InputThrottle::new(),
+            watchdog_interval: None,
+        },
+    )
+});
+
+/// Whether window events can be pumped on this platform, see the
+/// module documentation.
+pub const EVENT_LOOP_SUPPORTED: bool = cfg!(any(
+    windows,
+    target_os = "macos",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+));
+
+static WARNED_UNSUPPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether Emacs was told not to use the window system, as with `-nw`.
+/// `EVENT_LOOP` must not be started then: `wr_select1` never runs it,
+/// so windows would never see their events.
+pub fn window_system_inhibited() -> bool {
+    unsafe { inhibit_window_system }
+}
+
+// A `Vec` stays valid whatever a panic interrupts, at worst missing the
+// events being pushed or drained.
+pub static EVENT_BUFFER: Lazy<RecoverableMutex<Vec<GUIEvent>>> =
+    Lazy::new(|| RecoverableMutex::new("EVENT_BUFFER", Vec::new()));
+
+/// An `fd_set` allocated here or borrowed from a caller.  Only sets
+/// allocated here are cleared and freed on drop: the sets passed to
+/// `wr_select1` belong to Emacs.
+#[derive(Debug, Eq, Hash, PartialEq)]
+pub struct FdSet {
+    set: *mut fd_set,
+    owned: bool,
+}
 
 unsafe impl Send for FdSet {}
 unsafe impl Sync for FdSet {}
 
 impl FdSet {
+    /// A new, empty set.
+    pub fn new() -> Self {
+        let set = Box::into_raw(Box::new(unsafe { std::mem::zeroed::<fd_set>() }));
+        unsafe { libc::FD_ZERO(set) };
+
+        FdSet { set, owned: true }
+    }
+
+    /// Wrap SET, which stays owned by the caller.
+    pub fn borrowed(set: *mut fd_set) -> Self {
+        FdSet { set, owned: false }
+    }
+
+    pub fn as_ptr(&self) -> *mut fd_set {
+        self.set
+    }
+
     fn clear(&self) {
-        if self.0 != ptr::null_mut() {
-            unsafe { libc::FD_ZERO(self.0) };
+        if self.set != ptr::null_mut() {
+            unsafe { libc::FD_ZERO(self.set) };
         }
     }
 }
 
 impl Drop for FdSet {
     fn drop(&mut self) {
-        self.clear()
+        if self.owned {
+            self.clear();
+            drop(unsafe { Box::from_raw(self.set) });
+        }
     }
 }
 
@@ -203,6 +1067,353 @@ pub struct Timespec(pub *mut timespec);
 unsafe impl Send for Timespec {}
 unsafe impl Sync for Timespec {}
 
+impl Timespec {
+    /// The time the timespec holds, or None if it is null.
+    pub fn duration(&self) -> Option<Duration> {
+        if self.0.is_null() {
+            return None;
+        }
+
+        let timespec = unsafe { &*self.0 };
+        Some(Duration::new(
+            timespec.tv_sec.max(0) as u64,
+            timespec.tv_nsec.clamp(0, 999_999_999) as u32,
+        ))
+    }
+
+    /// Store DURATION in the timespec, unless it is null.
+    pub fn set(&self, duration: Duration) {
+        if self.0.is_null() {
+            return;
+        }
+
+        unsafe {
+            (*self.0).tv_sec = duration.as_secs() as _;
+            (*self.0).tv_nsec = duration.subsec_nanos() as _;
+        }
+    }
+}
+
+/// How long `wr_select1` waits when Emacs passes no timeout at all.
+/// Emacs always passes one, but the deadlines computed from the timeout
+/// need a bound.
+const UNBOUNDED_WAIT: Duration = Duration::from_secs(60 * 60 * 24);
+
+// The signal raised to tell Emacs that input is waiting, which is the
+// one its input handler is installed for.
+static WAKEUP_SIGNAL: AtomicI32 = AtomicI32::new(libc::SIGIO);
+
+/// Raise SIGNAL instead of SIGIO to tell Emacs that input is waiting, as
+/// where Emacs reads input on SIGPOLL.  Call it before the event loop
+/// starts.  Fall back to SIGIO, with a warning, if SIGNAL isn't one Emacs
+/// reads input on, or nothing handles it, since raising it would then
+/// queue an event of its own, like the `sigusr1' of SIGUSR1, kill Emacs
+/// or be lost.  Return the signal used.
+pub fn set_wakeup_signal(signal: Signal) -> Signal {
+    let signal = if !is_input_signal(signal) {
+        log::warn!("{signal} doesn't have Emacs read input, using SIGIO");
+        Signal::SIGIO
+    } else if is_handled(signal) {
+        signal
+    } else {
+        log::warn!("{signal} has no handler to wake Emacs, using SIGIO");
+        Signal::SIGIO
+    };
+
+    WAKEUP_SIGNAL.store(signal as i32, Ordering::Relaxed);
+    signal
+}
+
+// Whether Emacs reads input on SIGNAL, SIGIO or SIGPOLL where they
+// differ.
+fn is_input_signal(signal: Signal) -> bool {
+    let signal = signal as i32;
+
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "solaris",
+        target_os = "illumos"
+    ))]
+    if signal == libc::SIGPOLL {
+        return true;
+    }
+
+    signal == libc::SIGIO
+}
+
+// Whether SIGNAL can be caught and a handler is installed for it.
+fn is_handled(signal: Signal) -> bool {
+    if matches!(signal, Signal::SIGKILL | Signal::SIGSTOP) {
+        return false;
+    }
+
+    let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+    if unsafe { libc::sigaction(signal as i32, ptr::null(), &mut action) } != 0 {
+        return false;
+    }
+    action.sa_sigaction != libc::SIG_DFL && action.sa_sigaction != libc::SIG_IGN
+}
+
+/// Set the wakeup signal from `NG_WR_WAKEUP_SIGNAL`, "SIGIO" or
+/// "SIGPOLL" by name or number, when a display is opened.
+pub fn init_wakeup_signal() {
+    let name = match std::env::var("NG_WR_WAKEUP_SIGNAL") {
+        Ok(name) => name,
+        Err(_) => return,
+    };
+
+    match parse_signal(&name) {
+        Some(signal) => {
+            set_wakeup_signal(signal);
+        }
+        None => log::warn!("NG_WR_WAKEUP_SIGNAL is not a signal: {name}, using SIGIO"),
+    }
+}
+
+fn parse_signal(name: &str) -> Option<Signal> {
+    let name = name.trim();
+    match name.parse::<i32>() {
+        Ok(number) => Signal::try_from(number).ok(),
+        Err(_) if name.starts_with("SIG") => name.parse().ok(),
+        Err(_) => format!("SIG{name}").parse().ok(),
+    }
+}
+
+// Tell Emacs that input is waiting in `EVENT_BUFFER`, returning the
+// value `wr_select1` should return.
+fn notify_input_pending() -> i32 {
+    SELECT_STATS.input_wakeup();
+
+    // notify emacs's code that a keyboard event arrived.
+    let wakeup = WAKEUP_SIGNAL.load(Ordering::Relaxed);
+    match Signal::try_from(wakeup).and_then(signal::raise) {
+        Ok(_) => {}
+        Err(err) => log::error!("wakeup signal {wakeup} err: {err:?}"),
+    };
+    /* Pretend that `select' is interrupted by a signal.  */
+    set_errno(Errno(libc::EINTR));
+    debug_assert_eq!(nix::errno::errno(), libc::EINTR);
+    -1
+}
+
+// Whether EVENT is one Emacs reads from `EVENT_BUFFER`.
+fn is_input_event(event: &WindowEvent) -> bool {
+    matches!(
+        event,
+        WindowEvent::Resized(_)
+            | WindowEvent::KeyboardInput { .. }
+            | WindowEvent::ReceivedCharacter(_)
+            | WindowEvent::ModifiersChanged(_)
+            | WindowEvent::Focused(_)
+            | WindowEvent::Occluded(_)
+            | WindowEvent::ThemeChanged(_)
+            | WindowEvent::CloseRequested
+    ) || is_mouse_event(event)
+}
+
+#[cfg(feature = "wr-mouse")]
+fn is_mouse_event(event: &WindowEvent) -> bool {
+    matches!(
+        event,
+        WindowEvent::MouseInput { .. }
+            | WindowEvent::CursorMoved { .. }
+            | WindowEvent::CursorEntered { .. }
+            | WindowEvent::CursorLeft { .. }
+            | WindowEvent::MouseWheel { .. }
+    )
+}
+
+// Builds without mouse support drop mouse events before buffering them.
+#[cfg(not(feature = "wr-mouse"))]
+fn is_mouse_event(_event: &WindowEvent) -> bool {
+    false
+}
+
+// Whether the application is suspended, from winit's `Suspended` to the
+// `Resumed` after it.  The surfaces of windows may be gone meanwhile, as
+// when the session is locked or the system sleeps, so nothing is drawn.
+static SUSPENDED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_suspended() -> bool {
+    SUSPENDED.load(Ordering::Relaxed)
+}
+
+// Note a `Suspended` or `Resumed` E, returning whether it changed
+// whether the application is suspended, so Emacs has to be told.  The
+// `Resumed` winit sends at startup changes nothing.
+pub fn note_suspension(e: &Event<i32>) -> bool {
+    let suspended = match e {
+        Event::Suspended => true,
+        Event::Resumed => false,
+        _ => return false,
+    };
+
+    if SUSPENDED.swap(suspended, Ordering::Relaxed) == suspended {
+        return false;
+    }
+    log::info!("{}", if suspended { "Suspended" } else { "Resumed" });
+    true
+}
+
+// Push E to `EVENT_BUFFER` with its modifiers remapped, unless key
+// repeat drops it, returning whether it was pushed.
+fn buffer_event(
+    mut e: GUIEvent,
+    key_repeat: &mut KeyRepeat,
+    modifier_remap: &ModifierRemap,
+) -> bool {
+    modifier_remap.remap_event(&mut e);
+
+    if !key_repeat.filter(&e, Instant::now()) {
+        return false;
+    }
+
+    EVENT_BUFFER.lock().push(e);
+    true
+}
+
+// Push a `Resized` for every window whose scale factor change has
+// settled, returning whether there were any.
+fn buffer_settled_scales(windows: &mut WindowRegistry) -> bool {
+    let settled = windows.take_settled_scales(Instant::now());
+    if settled.is_empty() {
+        return false;
+    }
+
+    EVENT_BUFFER.lock().extend(
+        settled
+            .into_iter()
+            .map(|(window_id, size)| Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Resized(size),
+            }),
+    );
+    true
+}
+
+// When the next key repeat, settled scale factor change or report of
+// input held back is due.
+fn next_wakeup(
+    windows: &WindowRegistry,
+    key_repeat: &KeyRepeat,
+    input_throttle: &InputThrottle,
+) -> Option<Instant> {
+    [
+        key_repeat.next_deadline(),
+        windows.next_scale_deadline(),
+        input_throttle.deadline(),
+    ]
+    .into_iter()
+    .flatten()
+    .min()
+}
+
+// Whether Emacs should be told now about the input in `EVENT_BUFFER`,
+// given that BUFFERED is new.  Input the throttle held back is reported
+// once it is due.
+fn should_report(input_throttle: &mut InputThrottle, buffered: bool) -> bool {
+    let now = Instant::now();
+
+    if buffered {
+        let buffer = EVENT_BUFFER.lock();
+        let urgent = buffer.iter().any(|e| !is_throttled_event(e));
+        let key = buffer.iter().any(is_key_event);
+
+        return input_throttle.should_report(urgent, key, now);
+    }
+
+    input_throttle.take_due(now)
+}
+
+// Push a `RedrawRequested` for every visible window that needs to be
+// redrawn, returning whether there were any.  They follow the input
+// buffered before them, so Emacs reads that input before redrawing.
+fn buffer_redraws(windows: &mut WindowRegistry) -> bool {
+    let redraws = windows.take_redraws();
+    if redraws.is_empty() {
+        return false;
+    }
+
+    EVENT_BUFFER
+        .lock()
+        .extend(redraws.into_iter().map(Event::RedrawRequested));
+    true
+}
+
+// Push the key repeats that are due to `EVENT_BUFFER`, returning whether
+// there were any.
+fn buffer_key_repeats(key_repeat: &mut KeyRepeat) -> bool {
+    let repeats = key_repeat.take_due(Instant::now());
+    if repeats.is_empty() {
+        return false;
+    }
+
+    EVENT_BUFFER.lock().extend(repeats);
+    true
+}
+
+// Set from `NG_WR_TRACE` when a display is opened, so `wr_select1`
+// only pays for an atomic load and a clock read when tracing is off.
+static TRACE_SELECT: AtomicBool = AtomicBool::new(false);
+
+pub fn init_select_trace() {
+    let trace = std::env::var_os("NG_WR_TRACE").is_some();
+    TRACE_SELECT.store(trace, Ordering::Relaxed);
+}
+
+/// How a `wr_select1` call was answered, for `NG_WR_TRACE`.
+#[derive(Clone, Copy, Debug)]
+enum SelectBranch {
+    // No window system; plain `thread_select`.
+    Inhibit,
+    // No event loop on this platform; plain `thread_select`.
+    Unsupported,
+    // Input was already waiting in `EVENT_BUFFER`.
+    Buffered,
+    // The event loop produced input.
+    Winit,
+    // No input from the event loop; Emacs's descriptors were polled.
+    Fallback,
+    // No input from the event loop and no window visible; waited on
+    // Emacs's descriptors.
+    Hidden,
+    // Waited on the event thread and Emacs's descriptors.
+    Thread,
+    // Called again while already selecting on this thread; plain
+    // `pselect`.
+    Reentrant,
+}
+
+thread_local! {
+    // Whether this thread is inside `select1`.  Threads are tracked
+    // apart, since Lisp threads may wait in `wr_select1` side by side.
+    static IN_SELECT: Cell<bool> = Cell::new(false);
+}
+
+// Marks this thread as inside `select1` until dropped.
+struct SelectGuard;
+
+impl SelectGuard {
+    // Mark this thread, or return None if it already was.
+    fn enter() -> Option<Self> {
+        IN_SELECT.with(|in_select| (!in_select.replace(true)).then(|| SelectGuard))
+    }
+}
+
+impl Drop for SelectGuard {
+    fn drop(&mut self) {
+        IN_SELECT.with(|in_select| in_select.set(false));
+    }
+}
+
+/// Wait like `pselect` for Emacs's descriptors, or for input from the
+/// window system, for at most TIMEOUT.  The time left of TIMEOUT is
+/// written back to it, as Linux's `select` does, so Emacs's timers can
+/// tell an early return from a full wait.  `pselect` itself never
+/// updates its timeout (glibc hides the kernel's update), and neither
+/// `select` nor `pselect` do on macOS, the BSDs or Windows, so this
+/// doesn't depend on what the wait underneath does.
 #[no_mangle]
 pub extern "C" fn wr_select1(
     nfds: i32,
@@ -212,8 +1423,91 @@ pub extern "C" fn wr_select1(
     timeout: *mut timespec,
     _sigmask: *mut sigset_t,
 ) -> i32 {
-    if unsafe { inhibit_window_system } {
-        return unsafe {
+    let start = Instant::now();
+    let requested = Timespec(timeout).duration();
+    let trace = TRACE_SELECT.load(Ordering::Relaxed);
+    let buffered_before = if trace { EVENT_BUFFER.lock().len() } else { 0 };
+
+    let (ret, branch) = select1(nfds, readfds, writefds, _exceptfds, timeout, _sigmask);
+
+    let waited = start.elapsed();
+    SELECT_STATS.call(waited, matches!(branch, SelectBranch::Fallback));
+    if let Some(requested) = requested {
+        Timespec(timeout).set(requested.saturating_sub(waited));
+    }
+
+    if trace {
+        let buffered = EVENT_BUFFER.lock().len().saturating_sub(buffered_before);
+
+        // Logging must not clobber the EINTR Emacs looks at.
+        let errno = nix::errno::errno();
+        log::info!(
+            "wr_select1: branch={branch:?} buffered={buffered} waited={waited:?} timeout={requested:?} nfds={ret}"
+        );
+        set_errno(Errno(errno));
+    }
+
+    ret
+}
+
+/// Wait up to 100ms for the window system to resize WINDOW_ID, after its
+/// size was set.  With the event thread this waits for the `Resized` to
+/// be queued without holding `EVENT_LOOP`; on macOS and Windows, which
+/// have no event thread, the event loop is run until the resize arrives.
+pub fn wait_for_window_resize(window_id: WindowId) {
+    let deadline = Instant::now() + Duration::from_millis(100);
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    wait_for_resize(window_id, deadline);
+
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    EVENT_LOOP
+        .lock()
+        .wait_for_window_resize(window_id, deadline);
+}
+
+/// Wait until window events are buffered in `EVENT_BUFFER` or TIMEOUT
+/// passes, returning whether any are.  Emacs's descriptors aren't waited
+/// on.  Window events can't be waited for by a thread already inside
+/// `wr_select1`, so this returns false at once on one.
+pub fn wait_for_events(timeout: Option<Duration>) -> bool {
+    let deadline = Instant::now() + timeout.unwrap_or(UNBOUNDED_WAIT);
+
+    while EVENT_BUFFER.lock().is_empty() {
+        let now = Instant::now();
+        if now >= deadline || IN_SELECT.with(Cell::get) {
+            return false;
+        }
+
+        let wait = deadline - now;
+        let mut timespec = unsafe { make_timespec(wait.as_secs() as _, wait.subsec_nanos() as _) };
+        wr_select1(
+            0,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            &mut timespec,
+            ptr::null_mut(),
+        );
+    }
+
+    true
+}
+
+fn select1(
+    nfds: i32,
+    readfds: *mut fd_set,
+    writefds: *mut fd_set,
+    _exceptfds: *mut fd_set,
+    timeout: *mut timespec,
+    _sigmask: *mut sigset_t,
+) -> (i32, SelectBranch) {
+    if window_system_inhibited() || !EVENT_LOOP_SUPPORTED {
+        if !EVENT_LOOP_SUPPORTED && !WARNED_UNSUPPORTED.swap(true, Ordering::Relaxed) {
+            log::warn!("Window events can't be read on this platform");
+        }
+
+        let nfds = unsafe {
             thread_select(
                 Some(pselect),
                 nfds,
@@ -224,68 +1518,471 @@ pub extern "C" fn wr_select1(
                 _sigmask,
             )
         };
+        let branch = if EVENT_LOOP_SUPPORTED {
+            SelectBranch::Inhibit
+        } else {
+            SelectBranch::Unsupported
+        };
+        return (nfds, branch);
+    }
+
+    // A call made while this thread is already selecting, say from a
+    // signal handler, would deadlock on `EVENT_LOOP` or re-enter winit,
+    // which it forbids.  Only Emacs's descriptors are waited on then.
+    let _guard = match SelectGuard::enter() {
+        Some(guard) => guard,
+        None => {
+            log::warn!("wr_select1 re-entered; waiting without the event loop");
+            let nfds = unsafe { pselect(nfds, readfds, writefds, _exceptfds, timeout, _sigmask) };
+            return (nfds, SelectBranch::Reentrant);
+        }
+    };
+
+    let mut event_loop = EVENT_LOOP.lock();
+
+    // Input buffered by an earlier call that Emacs hasn't read yet is
+    // reported right away instead of after waiting for new events,
+    // unless the throttle is holding it back.
+    if !EVENT_BUFFER.lock().is_empty() && event_loop.input_throttle.may_report(Instant::now()) {
+        return (notify_input_pending(), SelectBranch::Buffered);
     }
 
-    let mut event_loop = EVENT_LOOP.lock().unwrap();
+    let timeout = Timespec(timeout).duration().unwrap_or(UNBOUNDED_WAIT);
 
-    let deadline = Instant::now()
-        + unsafe { Duration::new((*timeout).tv_sec as u64, (*timeout).tv_nsec as u32) };
+    select_events(
+        event_loop, nfds, readfds, writefds, _exceptfds, timeout, _sigmask,
+    )
+}
+
+/// Longest wait on Emacs's file descriptors while every window is
+/// hidden.  Window events can't interrupt that wait, so this bounds how
+/// late a window becoming visible again is noticed.
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+const HIDDEN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Run the event loop on this thread until an event arrives or TIMEOUT
+// passes, then poll Emacs's file descriptors.  While every window is
+// minimized or occluded there is nothing to redraw, so the descriptors
+// are waited on instead of polled.
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+fn select_events(
+    mut event_loop: MutexGuard<WrEventLoop>,
+    nfds: i32,
+    readfds: *mut fd_set,
+    writefds: *mut fd_set,
+    _exceptfds: *mut fd_set,
+    timeout: Duration,
+    _sigmask: *mut sigset_t,
+) -> (i32, SelectBranch) {
+    let WrEventLoop {
+        ref mut el,
+        ref mut windows,
+        ref mut key_repeat,
+        ref modifier_remap,
+        ref wakeup_events,
+        ref mut input_throttle,
+        #[cfg(target_os = "macos")]
+        ref mut idle_sleep,
+        watchdog_interval,
+        ref mut watchdog,
+        ..
+    } = *event_loop;
+
+    let deadline = Instant::now() + timeout;
+
+    let watchdog = watchdog_interval.map(|interval| {
+        let watchdog = watchdog.get_or_insert_with(|| {
+            let proxy = el.create_proxy();
+            Watchdog::spawn(move || {
+                log::error!("winit run_return is stuck past its deadline, forcing it to exit");
+                let _ = proxy.send_event(WATCHDOG_EVENT);
+            })
+        });
+        watchdog.arm(deadline + interval);
+        &*watchdog
+    });
 
     let nfds_result = RefCell::new(0);
 
     // We mush run winit in main thread, because the macOS platfrom limitation.
-    event_loop.el.run_return(|e, _, control_flow| {
-        control_flow.set_wait_until(deadline);
+    el.run_return(|e, _target, control_flow| {
+        // Wake up in time for key repeats, scale factor changes and input
+        // held back.
+        let wait_until = next_wakeup(windows, key_repeat, input_throttle)
+            .map_or(deadline, |wakeup| wakeup.min(deadline));
+        control_flow.set_wait_until(wait_until);
+
+        let mut buffered = false;
 
         match e {
-            Event::WindowEvent { ref event, .. } => match event {
-                WindowEvent::Resized(_)
-                | WindowEvent::KeyboardInput { .. }
-                | WindowEvent::ReceivedCharacter(_)
-                | WindowEvent::ModifiersChanged(_)
-                | WindowEvent::MouseInput { .. }
-                | WindowEvent::CursorMoved { .. }
-                | WindowEvent::Focused(_)
-                | WindowEvent::MouseWheel { .. }
-                | WindowEvent::CloseRequested => {
-                    EVENT_BUFFER.lock().unwrap().push(e.to_static().unwrap());
-
-                    // notify emacs's code that a keyboard event arrived.
-                    match signal::raise(Signal::SIGIO) {
-                        Ok(_) => {}
-                        Err(err) => log::error!("sigio err: {err:?}"),
-                    };
-                    /* Pretend that `select' is interrupted by a signal.  */
-                    set_errno(Errno(libc::EINTR));
-                    debug_assert_eq!(nix::errno::errno(), libc::EINTR);
-                    nfds_result.replace(-1);
-                    control_flow.set_exit();
+            Event::WindowEvent {
+                ref event,
+                window_id,
+            } => {
+                update_window_state(windows, window_id, event);
+
+                #[cfg(target_os = "macos")]
+                match event {
+                    WindowEvent::Moved(position) => {
+                        idle_sleep.window_moved(window_id, *position, _target)
+                    }
+                    WindowEvent::Focused(focused) => idle_sleep.set_focused(window_id, *focused),
+                    _ => {}
                 }
-                _ => {}
-            },
+
+                match *event {
+                    _ if !wakeup_events.wakes(event) => {}
+                    WindowEvent::AxisMotion {
+                        device_id,
+                        axis,
+                        value,
+                    } => {
+                        buffered = buffer_axis_motion(
+                            &mut EVENT_BUFFER.lock(),
+                            window_id,
+                            device_id,
+                            axis,
+                            value,
+                        );
+                    }
+                    _ if is_input_event(event) => {
+                        buffered = buffer_event(e.to_static().unwrap(), key_repeat, modifier_remap);
+                    }
+                    _ => {}
+                }
+            }
+            Event::DeviceEvent { device_id, event } => {
+                buffered = buffer_device_event(&mut EVENT_BUFFER.lock(), device_id, event);
+            }
+            Event::UserEvent(event @ (CLIPBOARD_READ_EVENT | CLIPBOARD_CHANGED_EVENT)) => {
+                buffered = buffer_event(Event::UserEvent(event), key_repeat, modifier_remap);
+            }
+            Event::Suspended | Event::Resumed if note_suspension(&e) => {
+                buffered = buffer_event(e.to_static().unwrap(), key_repeat, modifier_remap);
+            }
+            Event::UserEvent(WATCHDOG_EVENT) => control_flow.set_exit(),
             Event::UserEvent(nfds) => {
                 nfds_result.replace(nfds);
                 control_flow.set_exit();
             }
+            // winit dispatches the window events of a pass, then
+            // `MainEventsCleared`, then a `RedrawRequested` for each window
+            // the system wants redrawn, then `RedrawEventsCleared`.  Windows
+            // uncovered during the pass or asked to be redrawn are noted
+            // and redrawn once, when the pass ends.
+            Event::RedrawRequested(window_id) => windows.request_redraw(window_id),
             Event::RedrawEventsCleared => {
+                buffered = buffer_redraws(windows);
                 control_flow.set_exit();
             }
             _ => {}
         };
+
+        buffered |= buffer_key_repeats(key_repeat);
+        buffered |= buffer_settled_scales(windows);
+
+        if should_report(input_throttle, buffered) {
+            nfds_result.replace(notify_input_pending());
+            control_flow.set_exit();
+        }
     });
+    if let Some(watchdog) = watchdog {
+        watchdog.disarm();
+    }
     let ret = nfds_result.into_inner();
     if ret == 0 {
+        if windows.all_hidden() {
+            let wait = next_wakeup(windows, key_repeat, input_throttle)
+                .map_or(deadline, |wakeup| wakeup.min(deadline))
+                .saturating_duration_since(Instant::now())
+                .min(HIDDEN_POLL_INTERVAL);
+            let timespec = unsafe { make_timespec(wait.as_secs() as _, wait.subsec_nanos() as _) };
+            let nfds =
+                unsafe { libc::pselect(nfds, readfds, writefds, _exceptfds, &timespec, _sigmask) };
+            log::trace!("pselect: {nfds:?}");
+            return (nfds, SelectBranch::Hidden);
+        }
+
         let timespec = unsafe { make_timespec(0, 0) };
-        // Add some delay here avoding high cpu usage on macOS
+        // Add some delay here avoding high cpu usage on macOS, one
+        // refresh of the focused window's monitor.
         #[cfg(target_os = "macos")]
-        spin_sleep::sleep(Duration::from_millis(16));
+        spin_sleep::sleep(idle_sleep.duration());
         let nfds =
             unsafe { libc::pselect(nfds, readfds, writefds, _exceptfds, &timespec, _sigmask) };
         log::trace!("pselect: {nfds:?}");
-        return nfds;
+        return (nfds, SelectBranch::Fallback);
     }
 
     log::trace!("winit event run_return: {ret:?}");
 
-    ret
+    (ret, SelectBranch::Winit)
+}
+
+// Move the events queued by the event thread to `EVENT_BUFFER`,
+// returning whether Emacs should be told about new input.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn buffer_thread_events(event_loop: &mut WrEventLoop) -> bool {
+    let WrEventLoop {
+        ref event_thread,
+        ref mut windows,
+        ref mut key_repeat,
+        ref modifier_remap,
+        ref wakeup_events,
+        ref mut input_throttle,
+        ..
+    } = *event_loop;
+
+    let mut buffered = false;
+
+    for event in event_thread.take_pending() {
+        match event {
+            ThreadEvent::ScaleFactorChanged(window_id, scale_factor, size) => {
+                windows.scale_factor_changed(window_id, scale_factor, size, Instant::now());
+            }
+            ThreadEvent::Window(e) => {
+                let input = match e {
+                    Event::WindowEvent {
+                        ref event,
+                        window_id,
+                    } => {
+                        update_window_state(windows, window_id, event);
+
+                        match *event {
+                            _ if !wakeup_events.wakes(event) => false,
+                            WindowEvent::AxisMotion {
+                                device_id,
+                                axis,
+                                value,
+                            } => {
+                                buffered |= buffer_axis_motion(
+                                    &mut EVENT_BUFFER.lock(),
+                                    window_id,
+                                    device_id,
+                                    axis,
+                                    value,
+                                );
+                                false
+                            }
+                            _ => is_input_event(event),
+                        }
+                    }
+                    Event::UserEvent(CLIPBOARD_READ_EVENT | CLIPBOARD_CHANGED_EVENT) => true,
+                    // Only the changes are sent by the event thread.
+                    Event::Suspended | Event::Resumed => true,
+                    Event::RedrawRequested(window_id) => {
+                        windows.request_redraw(window_id);
+                        false
+                    }
+                    _ => false,
+                };
+
+                if input {
+                    buffered |= buffer_event(e, key_repeat, modifier_remap);
+                }
+            }
+            ThreadEvent::Device(device_id, event) => {
+                buffered |= buffer_device_event(&mut EVENT_BUFFER.lock(), device_id, event);
+            }
+        }
+    }
+
+    buffered |= buffer_key_repeats(key_repeat);
+    buffered |= buffer_settled_scales(windows);
+    buffered |= buffer_redraws(windows);
+
+    should_report(input_throttle, buffered)
+}
+
+// Wait for Emacs's file descriptors together with the event thread's
+// wake-up pipe, so window events interrupt the wait as soon as they
+// arrive.  `EVENT_LOOP` is not held while waiting.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn select_events(
+    mut event_loop: MutexGuard<WrEventLoop>,
+    nfds: i32,
+    readfds: *mut fd_set,
+    writefds: *mut fd_set,
+    exceptfds: *mut fd_set,
+    timeout: Duration,
+    sigmask: *mut sigset_t,
+) -> (i32, SelectBranch) {
+    if buffer_thread_events(&mut event_loop) {
+        return (notify_input_pending(), SelectBranch::Thread);
+    }
+
+    // Wake up in time for key repeats, scale factor changes and input
+    // held back.
+    let timeout = next_wakeup(
+        &event_loop.windows,
+        &event_loop.key_repeat,
+        &event_loop.input_throttle,
+    )
+    .map_or(timeout, |wakeup| {
+        timeout.min(wakeup.saturating_duration_since(Instant::now()))
+    });
+    let wake_fd = event_loop.event_thread.wake_fd();
+    drop(event_loop);
+
+    let mut wake_readfds: fd_set = unsafe { std::mem::zeroed() };
+    let readfds = if readfds.is_null() {
+        unsafe { libc::FD_ZERO(&mut wake_readfds) };
+        &mut wake_readfds as *mut fd_set
+    } else {
+        readfds
+    };
+
+    let timespec = unsafe { make_timespec(timeout.as_secs() as _, timeout.subsec_nanos() as _) };
+    let mut nfds = unsafe {
+        libc::FD_SET(wake_fd, readfds);
+        libc::pselect(
+            nfds.max(wake_fd + 1),
+            readfds,
+            writefds,
+            exceptfds,
+            &timespec,
+            sigmask,
+        )
+    };
+    log::trace!("pselect: {nfds:?}");
+
+    if nfds > 0 && unsafe { libc::FD_ISSET(wake_fd, readfds) } {
+        unsafe { libc::FD_CLR(wake_fd, readfds) };
+        nfds -= 1;
+    }
+
+    // Pick up new events as well as key repeats and scale factor changes
+    // that became due while waiting.
+    if nfds >= 0 && buffer_thread_events(&mut EVENT_LOOP.lock()) {
+        let interrupted = notify_input_pending();
+        if nfds == 0 {
+            return (interrupted, SelectBranch::Thread);
+        }
+    }
+
+    (nfds, SelectBranch::Thread)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::event::{DeviceId, ElementState, KeyboardInput, ModifiersState, VirtualKeyCode};
+
+    #[test]
+    fn signal_names() {
+        assert_eq!(parse_signal("SIGUSR1"), Some(Signal::SIGUSR1));
+        assert_eq!(parse_signal("usr2"), None);
+        assert_eq!(parse_signal("USR2"), Some(Signal::SIGUSR2));
+        assert_eq!(parse_signal(&libc::SIGIO.to_string()), Some(Signal::SIGIO));
+        assert_eq!(parse_signal("SIGNOPE"), None);
+        assert_eq!(parse_signal("0"), None);
+    }
+
+    #[test]
+    fn monitors_in_attribute_list_order() {
+        assert_eq!(monitor_attribute_order(&[true; 3], 1), vec![1, 2, 0]);
+        assert_eq!(monitor_attribute_order(&[true; 3], 0), vec![0, 2, 1]);
+        assert_eq!(monitor_attribute_order(&[true, false, true], 2), vec![2, 0]);
+        assert_eq!(monitor_attribute_order(&[false, true], 0), vec![1]);
+    }
+
+    #[test]
+    fn only_input_signals_wake_emacs() {
+        assert!(is_input_signal(Signal::SIGIO));
+        assert!(!is_input_signal(Signal::SIGUSR1));
+        assert!(!is_input_signal(Signal::SIGUSR2));
+    }
+
+    #[test]
+    fn nested_select_is_detected() {
+        let guard = SelectGuard::enter();
+        assert!(guard.is_some());
+        assert!(SelectGuard::enter().is_none());
+
+        drop(guard);
+        assert!(SelectGuard::enter().is_some());
+    }
+
+    #[test]
+    fn timespec_reads_and_writes_back() {
+        assert_eq!(Timespec(ptr::null_mut()).duration(), None);
+        Timespec(ptr::null_mut()).set(Duration::from_secs(1));
+
+        let mut timeout: timespec = unsafe { std::mem::zeroed() };
+        timeout.tv_sec = 2;
+        timeout.tv_nsec = 500_000_000;
+        let timespec = Timespec(&mut timeout);
+        assert_eq!(timespec.duration(), Some(Duration::from_millis(2500)));
+
+        timespec.set(Duration::from_millis(2500).saturating_sub(Duration::from_secs(3)));
+        assert_eq!((timeout.tv_sec, timeout.tv_nsec), (0, 0));
+    }
+
+    #[test]
+    fn borrowed_fd_set_is_left_alone() {
+        let mut readfds: fd_set = unsafe { std::mem::zeroed() };
+        unsafe {
+            libc::FD_ZERO(&mut readfds);
+            libc::FD_SET(3, &mut readfds);
+        }
+
+        drop(FdSet::borrowed(&mut readfds));
+        assert!(unsafe { libc::FD_ISSET(3, &readfds) });
+
+        let owned = FdSet::new();
+        assert!(!unsafe { libc::FD_ISSET(3, owned.as_ptr()) });
+    }
+
+    #[test]
+    fn buffered_keyboard_input_keeps_scancode() {
+        #[allow(deprecated)]
+        let input = KeyboardInput {
+            scancode: 38,
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::A),
+            modifiers: ModifiersState::empty(),
+        };
+        let event: Event<i32> = Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event: WindowEvent::KeyboardInput {
+                device_id: unsafe { DeviceId::dummy() },
+                input,
+                is_synthetic: false,
+            },
+        };
+
+        EVENT_BUFFER.lock().push(event.to_static().unwrap());
+        let buffered = EVENT_BUFFER.lock().pop();
+
+        match buffered {
+            Some(Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input: buffered, ..
+                    },
+                ..
+            }) => assert_eq!(buffered, input),
+            _ => panic!("keyboard input was not buffered"),
+        }
+    }
+
+    #[cfg(feature = "wr-mouse")]
+    #[test]
+    fn cursor_crossings_are_buffered() {
+        let window_id = unsafe { WindowId::dummy() };
+        let device_id = unsafe { DeviceId::dummy() };
+
+        for event in [
+            WindowEvent::CursorEntered { device_id },
+            WindowEvent::CursorLeft { device_id },
+        ] {
+            assert!(is_input_event(&event));
+
+            let event: Event<i32> = Event::WindowEvent { window_id, event };
+            match event.to_static() {
+                Some(Event::WindowEvent { window_id: id, .. }) => assert_eq!(id, window_id),
+                _ => panic!("cursor crossing did not survive to_static"),
+            }
+        }
+    }
 }
@@ -0,0 +1,238 @@
+//! Running the winit event loop on a thread of its own.
+//!
+//! On macOS winit must run on the main thread, so `wr_select1` drives the
+//! event loop itself there.  On other Unix systems the event loop lives
+//! on a dedicated thread:
+//!
+//! - Anything that needs the event loop, like creating a window or
+//!   listing monitors, is sent to the thread as a command with
+//!   `EventThread::call`, which blocks until the command has run.
+//! - The thread queues window events for the main thread and writes to a
+//!   wake-up pipe.  `wr_select1` waits on that pipe along with Emacs's own
+//!   file descriptors, then moves queued events into `EVENT_BUFFER`.
+//!
+//! The thread never locks `EVENT_LOOP`, so the main thread may hold it
+//! while waiting for a command to finish.
+
+use std::{
+    mem,
+    os::unix::io::RawFd,
+    sync::{mpsc, Condvar, PoisonError},
+    thread,
+    time::Instant,
+};
+
+use once_cell::sync::Lazy;
+use winit::{
+    dpi::PhysicalSize,
+    event::{DeviceEvent, DeviceId, Event, WindowEvent},
+    event_loop::{EventLoopBuilder, EventLoopProxy, EventLoopWindowTarget},
+    window::WindowId,
+};
+
+use winit::platform::unix::EventLoopBuilderExtUnix;
+
+use crate::clipboard::{CLIPBOARD_CHANGED_EVENT, CLIPBOARD_READ_EVENT};
+use crate::devices;
+use crate::event_loop::{note_suspension, GUIEvent};
+use crate::util::RecoverableMutex;
+
+/// An event queued by the event thread for the main thread.
+pub enum ThreadEvent {
+    Window(GUIEvent),
+    // `ScaleFactorChanged` borrows the new size and can't be queued as is.
+    ScaleFactorChanged(WindowId, f64, PhysicalSize<u32>),
+    // Only queued while input devices are tracked.
+    Device(DeviceId, DeviceEvent),
+}
+
+// Like `EVENT_BUFFER`, a panic can't leave this `Vec` inconsistent.
+static PENDING_EVENTS: Lazy<RecoverableMutex<Vec<ThreadEvent>>> =
+    Lazy::new(|| RecoverableMutex::new("PENDING_EVENTS", Vec::new()));
+
+// Notified whenever the event thread queues to `PENDING_EVENTS`.
+static EVENTS_QUEUED: Condvar = Condvar::new();
+
+type Command = Box<dyn FnOnce(&EventLoopWindowTarget<i32>)>;
+
+// Winit objects are only used by one thread at a time: the caller of
+// `EventThread::call` blocks until the thread is done with them.
+struct AssertSend<T>(T);
+
+unsafe impl<T> Send for AssertSend<T> {}
+
+pub struct EventThread {
+    proxy: EventLoopProxy<i32>,
+    commands: mpsc::Sender<AssertSend<Command>>,
+    wake_read: RawFd,
+}
+
+impl EventThread {
+    pub fn spawn() -> Self {
+        let (wake_read, wake_write) = wake_pipe();
+        let (commands, command_rx) = mpsc::channel::<AssertSend<Command>>();
+        let (proxy_tx, proxy_rx) = mpsc::channel();
+
+        thread::Builder::new()
+            .name("webrender-events".to_owned())
+            .spawn(move || {
+                let mut builder = EventLoopBuilder::<i32>::with_user_event();
+                builder.with_any_thread(true);
+                let el = builder.build();
+
+                proxy_tx
+                    .send(el.create_proxy())
+                    .expect("Failed to start webrender event thread");
+
+                el.run(move |e, target, control_flow| {
+                    control_flow.set_wait();
+
+                    let event = match e {
+                        Event::UserEvent(
+                            event @ (CLIPBOARD_READ_EVENT | CLIPBOARD_CHANGED_EVENT),
+                        ) => ThreadEvent::Window(Event::UserEvent(event)),
+                        Event::UserEvent(_) => {
+                            while let Ok(AssertSend(command)) = command_rx.try_recv() {
+                                command(target);
+                            }
+                            return;
+                        }
+                        Event::WindowEvent {
+                            window_id,
+                            event:
+                                WindowEvent::ScaleFactorChanged {
+                                    scale_factor,
+                                    new_inner_size,
+                                },
+                        } => ThreadEvent::ScaleFactorChanged(
+                            window_id,
+                            scale_factor,
+                            *new_inner_size,
+                        ),
+                        Event::RedrawRequested(window_id) => {
+                            ThreadEvent::Window(Event::RedrawRequested(window_id))
+                        }
+                        Event::Suspended if note_suspension(&e) => {
+                            ThreadEvent::Window(Event::Suspended)
+                        }
+                        Event::Resumed if note_suspension(&e) => {
+                            ThreadEvent::Window(Event::Resumed)
+                        }
+                        Event::WindowEvent { .. } => match e.to_static() {
+                            Some(e) => ThreadEvent::Window(e),
+                            None => return,
+                        },
+                        Event::DeviceEvent { device_id, event } if devices::is_tracking() => {
+                            ThreadEvent::Device(device_id, event)
+                        }
+                        _ => return,
+                    };
+
+                    PENDING_EVENTS.lock().push(event);
+                    EVENTS_QUEUED.notify_all();
+                    wake(wake_write);
+                });
+            })
+            .expect("Failed to spawn webrender event thread");
+
+        let proxy = proxy_rx
+            .recv()
+            .expect("Failed to start webrender event thread");
+
+        EventThread {
+            proxy,
+            commands,
+            wake_read,
+        }
+    }
+
+    pub fn create_proxy(&self) -> EventLoopProxy<i32> {
+        self.proxy.clone()
+    }
+
+    /// Run F on the event thread and return its result.
+    pub fn call<R: 'static>(
+        &self,
+        f: impl FnOnce(&EventLoopWindowTarget<i32>) -> R + 'static,
+    ) -> R {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        let command: Command = Box::new(move |target| {
+            let _ = reply_tx.send(AssertSend(f(target)));
+        });
+
+        self.commands
+            .send(AssertSend(command))
+            .expect("Webrender event thread exited");
+        self.proxy
+            .send_event(0)
+            .expect("Webrender event thread exited");
+
+        reply_rx.recv().expect("Webrender event thread exited").0
+    }
+
+    /// Read end of the pipe the event thread writes to after queuing
+    /// events.
+    pub fn wake_fd(&self) -> RawFd {
+        self.wake_read
+    }
+
+    /// Take the events queued since the last call, emptying the wake-up
+    /// pipe.
+    pub fn take_pending(&self) -> Vec<ThreadEvent> {
+        let mut buf = [0u8; 64];
+        while unsafe { libc::read(self.wake_read, buf.as_mut_ptr() as *mut _, buf.len()) } > 0 {}
+
+        mem::take(&mut *PENDING_EVENTS.lock())
+    }
+}
+
+/// Block until the event thread has queued a `Resized` for WINDOW_ID, or
+/// until DEADLINE, returning whether it has.  This needs no `EventThread`,
+/// so callers needn't hold `EVENT_LOOP` while they wait.
+pub fn wait_for_resize(window_id: WindowId, deadline: Instant) -> bool {
+    let mut pending = PENDING_EVENTS.lock();
+
+    loop {
+        let resized = pending.iter().any(|e| {
+            matches!(e, ThreadEvent::Window(Event::WindowEvent {
+                window_id: id,
+                event: WindowEvent::Resized(_),
+            }) if *id == window_id)
+        });
+        let now = Instant::now();
+        if resized || now >= deadline {
+            return resized;
+        }
+
+        pending = EVENTS_QUEUED
+            .wait_timeout(pending, deadline - now)
+            .unwrap_or_else(PoisonError::into_inner)
+            .0;
+    }
+}
+
+fn wake_pipe() -> (RawFd, RawFd) {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        panic!(
+            "Failed to create webrender wake-up pipe: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+
+    for fd in fds {
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK);
+            libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        }
+    }
+
+    (fds[0], fds[1])
+}
+
+fn wake(fd: RawFd) {
+    // A full pipe already wakes the main thread, so errors are ignored.
+    unsafe { libc::write(fd, [0u8].as_ptr() as *const _, 1) };
+}
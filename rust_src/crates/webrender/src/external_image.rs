@@ -0,0 +1,107 @@
+//! Images whose pixels are GL textures produced outside of Emacs, as by
+//! a video decoder or an embedded browser, which webrender composites
+//! without copying them through the CPU.
+//!
+//! A producer registers a source, then pushes a texture to it for each
+//! new frame.  The texture must belong to a GL context sharing objects
+//! with the windows' contexts, and must be finished rendering, say by
+//! `glFinish`, before it is pushed.  Frames draw the latest texture of a
+//! source with `wr-draw-external-image`.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use gleam::gl;
+use once_cell::sync::Lazy;
+use webrender::api::{units::DeviceIntSize, ExternalImageId};
+
+/// External image ids below this are the GL textures of
+/// `TextureResourceManager`, which are u32s.
+const SOURCE_ID_BASE: u64 = 1 << 32;
+
+/// A texture pushed to a source.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExternalFrame {
+    pub texture: gl::GLuint,
+    pub size: DeviceIntSize,
+    /// Whether the texture is upside down, as one rendered by GL is.
+    pub flip: bool,
+}
+
+#[derive(Default)]
+struct Sources {
+    next_id: u32,
+    // The latest frame of each source, or None before the first.
+    frames: HashMap<u32, Option<ExternalFrame>>,
+}
+
+static SOURCES: Lazy<Mutex<Sources>> = Lazy::new(|| Mutex::new(Sources::default()));
+
+/// Register a new source, returning its id.
+pub fn register_external_image() -> u32 {
+    let mut sources = SOURCES.lock().unwrap();
+    let id = sources.next_id;
+    sources.next_id += 1;
+    sources.frames.insert(id, None);
+    id
+}
+
+/// Make FRAME the latest frame of the source ID, to be drawn the next
+/// time a window showing it renders.  Return false if there is no such
+/// source.
+pub fn push_external_image(id: u32, frame: ExternalFrame) -> bool {
+    match SOURCES.lock().unwrap().frames.get_mut(&id) {
+        Some(latest) => {
+            *latest = Some(frame);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Forget the source ID.  Windows still showing it draw nothing in its
+/// place until redrawn.  Return false if there was no such source.
+pub fn unregister_external_image(id: u32) -> bool {
+    SOURCES.lock().unwrap().frames.remove(&id).is_some()
+}
+
+/// The latest frame of the source ID, or None if it hasn't been pushed
+/// one or doesn't exist.
+pub fn latest_frame(id: u32) -> Option<ExternalFrame> {
+    SOURCES.lock().unwrap().frames.get(&id).copied().flatten()
+}
+
+/// The id webrender knows the source ID by.
+pub fn external_image_id(id: u32) -> ExternalImageId {
+    ExternalImageId(SOURCE_ID_BASE + id as u64)
+}
+
+/// The source webrender's KEY stands for, or None if KEY is a texture of
+/// `TextureResourceManager`.
+pub fn source_id(key: ExternalImageId) -> Option<u32> {
+    key.0.checked_sub(SOURCE_ID_BASE).map(|id| id as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources_keep_their_latest_frame() {
+        let id = register_external_image();
+        assert_eq!(source_id(external_image_id(id)), Some(id));
+        assert_eq!(source_id(ExternalImageId(7)), None);
+        assert_eq!(latest_frame(id), None);
+
+        let frame = ExternalFrame {
+            texture: 3,
+            size: DeviceIntSize::new(640, 480),
+            flip: true,
+        };
+        assert!(push_external_image(id, frame));
+        assert_eq!(latest_frame(id), Some(frame));
+
+        assert!(unregister_external_image(id));
+        assert!(!push_external_image(id, frame));
+        assert_eq!(latest_frame(id), None);
+    }
+}
@@ -10,7 +10,10 @@ use emacs::{
 };
 use winit::dpi::PhysicalPosition;
 
-use crate::{event_loop::EVENT_LOOP, output::OutputRef};
+use crate::{
+    event_loop::EVENT_LOOP,
+    output::{OutputRef, WindowOptions},
+};
 
 use super::{display_info::DisplayInfoRef, output::Output};
 
@@ -19,6 +22,7 @@ pub fn create_frame(
     dpyinfo: DisplayInfoRef,
     tem: LispObject,
     mut kb: KeyboardRef,
+    options: WindowOptions,
 ) -> LispFrameRef {
     let frame = if tem.eq(Qnone) || tem.is_nil() {
         unsafe { make_frame_without_minibuffer(Qnil, kb.as_mut(), display) }
@@ -35,8 +39,12 @@ pub fn create_frame(
     frame.terminal = dpyinfo.get_inner().terminal.as_mut();
     frame.set_output_method(output_method::output_wr);
 
-    let mut event_loop = EVENT_LOOP.lock().unwrap();
-    let mut output = Box::new(Output::build(&mut event_loop, frame));
+    // The event loop must be unlocked before signaling an error.
+    let output = Output::build(&mut EVENT_LOOP.lock(), frame, options);
+    let mut output = match output {
+        Ok(output) => Box::new(output),
+        Err(err) => error!("{}", err),
+    };
 
     let window_id = output.get_window().id();
 
@@ -112,6 +120,11 @@ pub fn frame_edges(frame: LispObject, type_: LispObject) -> LispObject {
 pub trait LispFrameExt {
     fn wr_output(&self) -> OutputRef;
     fn wr_display_info(&self) -> DisplayInfoRef;
+    fn set_minimized(&self, minimized: bool);
+    fn set_maximized(&self);
+    fn restore(&self);
+    fn is_minimized(&self) -> bool;
+    fn is_occluded(&self) -> bool;
 }
 
 impl LispFrameExt for LispFrameRef {
@@ -123,4 +136,62 @@ impl LispFrameExt for LispFrameRef {
     fn wr_display_info(&self) -> DisplayInfoRef {
         self.wr_output().display_info()
     }
+
+    fn set_minimized(&self, minimized: bool) {
+        let mut frame = *self;
+        let output = self.wr_output();
+
+        if minimized {
+            output.minimize();
+        } else {
+            output.restore();
+        }
+
+        frame.set_iconified(minimized);
+        frame.set_visible(!minimized as u32);
+
+        let window_id = output.get_window().id();
+        if let Some(state) = EVENT_LOOP.lock().window_state_mut(&window_id) {
+            state.minimized = minimized;
+            if !minimized {
+                state.maximized = false;
+            }
+        }
+    }
+
+    fn set_maximized(&self) {
+        let output = self.wr_output();
+
+        output.maximize();
+
+        let window_id = output.get_window().id();
+        if let Some(state) = EVENT_LOOP.lock().window_state_mut(&window_id) {
+            state.minimized = false;
+            state.maximized = true;
+        }
+    }
+
+    fn restore(&self) {
+        self.set_minimized(false);
+    }
+
+    // winit can't always read back the minimized state of a window, so
+    // rely on the flag we maintain from events and our own requests.
+    fn is_minimized(&self) -> bool {
+        let window_id = self.wr_output().get_window().id();
+
+        EVENT_LOOP
+            .lock()
+            .window_state(&window_id)
+            .map_or(false, |state| state.minimized)
+    }
+
+    fn is_occluded(&self) -> bool {
+        let window_id = self.wr_output().get_window().id();
+
+        EVENT_LOOP
+            .lock()
+            .window_state(&window_id)
+            .map_or(false, |state| state.occluded)
+    }
 }
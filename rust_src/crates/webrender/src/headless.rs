@@ -0,0 +1,84 @@
+//! Off-screen rendering without a display server, for rendering tests.
+
+use std::rc::Rc;
+
+use euclid::default::Size2D;
+use gleam::gl;
+use surfman::{Connection, GLApi, SurfaceType};
+use webrender::api::units::{DeviceIntRect, DeviceIntSize};
+use webrender_surfman::WebrenderSurfman;
+
+use crate::readback::read_pixels;
+
+/// Create a WebRender surface of WIDTH x HEIGHT pixels that isn't backed
+/// by a window.  A software adapter is preferred, so this works on
+/// machines without a GPU.
+pub fn create_headless_surfman(
+    width: i32,
+    height: i32,
+) -> Result<WebrenderSurfman, surfman::Error> {
+    let connection = Connection::new()?;
+    let adapter = connection
+        .create_software_adapter()
+        .or_else(|_| connection.create_adapter())?;
+    let surface_type = SurfaceType::Generic {
+        size: Size2D::new(width, height),
+    };
+
+    let webrender_surfman = WebrenderSurfman::create(&connection, &adapter, surface_type)?;
+    webrender_surfman.make_gl_context_current()?;
+
+    Ok(webrender_surfman)
+}
+
+/// GL bindings for the context of WEBRENDER_SURFMAN.
+pub fn load_gl(webrender_surfman: &WebrenderSurfman) -> Rc<dyn gl::Gl> {
+    match webrender_surfman.connection().gl_api() {
+        GLApi::GL => unsafe { gl::GlFns::load_with(|s| webrender_surfman.get_proc_address(s)) },
+        GLApi::GLES => unsafe { gl::GlesFns::load_with(|s| webrender_surfman.get_proc_address(s)) },
+    }
+}
+
+/// Read back what has been rendered to WEBRENDER_SURFMAN as RGBA rows,
+/// from top to bottom.
+pub fn read_framebuffer(
+    webrender_surfman: &WebrenderSurfman,
+    gl: &dyn gl::Gl,
+) -> Result<Vec<u8>, surfman::Error> {
+    let info = webrender_surfman
+        .context_surface_info()?
+        .ok_or(surfman::Error::Failed)?;
+    let rect = DeviceIntRect::from_size(DeviceIntSize::new(info.size.width, info.size.height));
+
+    // Generic surfaces count rows from the bottom, as GL does.
+    let pixels = read_pixels(webrender_surfman, gl, rect, false)?;
+    Ok(pixels.map(|(_, pixels)| pixels).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clear_color_reads_back() {
+        let webrender_surfman = match create_headless_surfman(4, 2) {
+            Ok(webrender_surfman) => webrender_surfman,
+            Err(err) => {
+                eprintln!("No headless surface, skipping: {err:?}");
+                return;
+            }
+        };
+        let gl = load_gl(&webrender_surfman);
+
+        let info = webrender_surfman.context_surface_info().unwrap().unwrap();
+        gl.bind_framebuffer(gl::FRAMEBUFFER, info.framebuffer_object);
+        gl.clear_color(1.0, 0.0, 0.0, 1.0);
+        gl.clear(gl::COLOR_BUFFER_BIT);
+
+        let pixels = read_framebuffer(&webrender_surfman, &*gl).unwrap();
+        assert_eq!(pixels.len(), 4 * 2 * 4);
+        assert!(pixels
+            .chunks_exact(4)
+            .all(|pixel| pixel == [255, 0, 0, 255]));
+    }
+}
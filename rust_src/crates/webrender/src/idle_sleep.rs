@@ -0,0 +1,98 @@
+use std::{collections::HashMap, time::Duration};
+
+use winit::{
+    dpi::PhysicalPosition, event_loop::EventLoopWindowTarget, monitor::MonitorHandle,
+    window::WindowId,
+};
+
+/// Sleep used when the refresh rate of a window's monitor is unknown.
+const DEFAULT_IDLE_SLEEP: Duration = Duration::from_millis(16);
+
+/// Shortest sleep, so monitors reporting absurd refresh rates don't turn
+/// the idle wait into a busy loop.
+const MIN_IDLE_SLEEP: Duration = Duration::from_millis(4);
+
+/// How long `wr_select1` sleeps on macOS when the event loop had no
+/// input: one refresh of the monitor the focused window is on, so idle
+/// redraws keep up with high refresh rate displays.
+#[derive(Default)]
+pub struct IdleSleep {
+    // Refresh rate of each window's monitor, in millihertz.
+    refresh_rates: HashMap<WindowId, Option<u32>>,
+    focused: Option<WindowId>,
+}
+
+impl IdleSleep {
+    pub fn set_monitor(&mut self, window_id: WindowId, monitor: Option<MonitorHandle>) {
+        self.refresh_rates.insert(
+            window_id,
+            monitor.and_then(|monitor| monitor.refresh_rate_millihertz()),
+        );
+    }
+
+    /// Look up the monitor WINDOW_ID was moved to at POSITION.
+    pub fn window_moved(
+        &mut self,
+        window_id: WindowId,
+        position: PhysicalPosition<i32>,
+        target: &EventLoopWindowTarget<i32>,
+    ) {
+        let monitor = target.available_monitors().find(|monitor| {
+            let origin = monitor.position();
+            let size = monitor.size();
+
+            (origin.x..origin.x + size.width as i32).contains(&position.x)
+                && (origin.y..origin.y + size.height as i32).contains(&position.y)
+        });
+
+        if monitor.is_some() {
+            self.set_monitor(window_id, monitor);
+        }
+    }
+
+    pub fn set_focused(&mut self, window_id: WindowId, focused: bool) {
+        if focused {
+            self.focused = Some(window_id);
+        } else if self.focused == Some(window_id) {
+            self.focused = None;
+        }
+    }
+
+    pub fn forget_window(&mut self, window_id: &WindowId) {
+        self.refresh_rates.remove(window_id);
+        if self.focused.as_ref() == Some(window_id) {
+            self.focused = None;
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        let refresh_rate = self
+            .focused
+            .and_then(|window_id| self.refresh_rates.get(&window_id).copied().flatten());
+
+        frame_interval(refresh_rate)
+    }
+}
+
+/// One refresh at REFRESH_RATE millihertz, at least `MIN_IDLE_SLEEP`.
+fn frame_interval(refresh_rate: Option<u32>) -> Duration {
+    match refresh_rate {
+        Some(millihertz) if millihertz > 0 => {
+            Duration::from_micros(1_000_000_000 / millihertz as u64).max(MIN_IDLE_SLEEP)
+        }
+        _ => DEFAULT_IDLE_SLEEP,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_interval_follows_refresh_rate() {
+        assert_eq!(frame_interval(None), DEFAULT_IDLE_SLEEP);
+        assert_eq!(frame_interval(Some(0)), DEFAULT_IDLE_SLEEP);
+        assert_eq!(frame_interval(Some(120_000)), Duration::from_micros(8333));
+        assert_eq!(frame_interval(Some(1_000_000)), MIN_IDLE_SLEEP);
+    }
+}
@@ -1,24 +1,27 @@
+#[cfg(feature = "wr-mouse")]
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, TouchPhase};
 use winit::{
     dpi::PhysicalPosition,
-    event::{
-        ElementState, ModifiersState, MouseButton, MouseScrollDelta, TouchPhase, VirtualKeyCode,
-    },
+    event::{ModifiersState, ScanCode, VirtualKeyCode},
 };
 
+#[cfg(feature = "wr-mouse")]
+use emacs::sys::EmacsModifiers::{down_modifier, up_modifier};
 use emacs::{
     bindings::{event_kind, input_event, scroll_bar_part},
     globals::{Qnil, Qt},
     lisp::LispObject,
-    sys::EmacsModifiers::{
-        ctrl_modifier, down_modifier, meta_modifier, shift_modifier, super_modifier, up_modifier,
-    },
+    sys::EmacsModifiers::{ctrl_modifier, meta_modifier, shift_modifier, super_modifier},
 };
 
 pub struct InputProcessor {
     modifiers: ModifiersState,
     suppress_chars: bool,
+    // Hardware scancode of the last key pressed, for physical key layouts.
+    last_scancode: Option<ScanCode>,
     cursor_positon: PhysicalPosition<f64>,
 
+    #[cfg(feature = "wr-mouse")]
     total_delta: PhysicalPosition<f64>,
 }
 
@@ -27,8 +30,10 @@ impl InputProcessor {
         InputProcessor {
             modifiers: ModifiersState::empty(),
             suppress_chars: false,
+            last_scancode: None,
             cursor_positon: PhysicalPosition::new(0.0, 0.0),
 
+            #[cfg(feature = "wr-mouse")]
             total_delta: PhysicalPosition::new(0.0, 0.0),
         }
     }
@@ -58,9 +63,14 @@ impl InputProcessor {
 
     pub fn key_pressed(
         &mut self,
-        key_code: VirtualKeyCode,
+        key_code: Option<VirtualKeyCode>,
+        scancode: ScanCode,
         top_frame: LispObject,
     ) -> Option<input_event> {
+        self.last_scancode = Some(scancode);
+
+        let key_code = key_code?;
+
         if winit_keycode_emacs_key_name(key_code).is_null() {
             return None;
         }
@@ -85,10 +95,15 @@ impl InputProcessor {
         Some(iev)
     }
 
+    pub fn last_scancode(&self) -> Option<ScanCode> {
+        self.last_scancode
+    }
+
     pub fn key_released(&mut self) {
         self.suppress_chars = false;
     }
 
+    #[cfg(feature = "wr-mouse")]
     pub fn mouse_pressed(
         &self,
         button: MouseButton,
@@ -125,6 +140,7 @@ impl InputProcessor {
         Some(iev)
     }
 
+    #[cfg(feature = "wr-mouse")]
     pub fn mouse_wheel_scrolled(
         &mut self,
         delta: MouseScrollDelta,
@@ -204,6 +220,7 @@ impl InputProcessor {
         Some(iev)
     }
 
+    #[cfg(feature = "wr-mouse")]
     pub fn cursor_move(&mut self, position: PhysicalPosition<f64>) {
         self.cursor_positon = position;
     }
@@ -0,0 +1,208 @@
+use std::time::{Duration, Instant};
+
+use winit::event::{Event, WindowEvent};
+
+use crate::event_loop::GUIEvent;
+
+/// Limits how often Emacs is told about buffered input that mostly
+/// leads to redisplay, like pointer motion, scrolling, resizing and
+/// requests to redraw.
+///
+/// With a minimum interval, such input arriving sooner than the interval
+/// after the last report stays buffered and is reported once the
+/// interval has passed.  Other input, like keys and clicks, is always
+/// reported right away, taking any deferred input along.
+///
+/// With a batch window, any input is held back until the window has
+/// passed since the first input of the batch arrived, so a burst of
+/// input is read by Emacs in one go.  Keys are reported right away
+/// unless they are batched too.
+pub struct InputThrottle {
+    interval: Option<Duration>,
+    last_report: Option<Instant>,
+    batch: Option<Duration>,
+    batch_keys: bool,
+    batch_start: Option<Instant>,
+    deferred: bool,
+}
+
+impl InputThrottle {
+    pub fn new() -> Self {
+        InputThrottle {
+            interval: None,
+            last_report: None,
+            batch: None,
+            batch_keys: false,
+            batch_start: None,
+            deferred: false,
+        }
+    }
+
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    /// Set the minimum INTERVAL between reports, or None for no limit.
+    pub fn set_interval(&mut self, interval: Option<Duration>) {
+        self.interval = interval;
+    }
+
+    /// The batch window, and whether keys are batched.
+    pub fn batch(&self) -> (Option<Duration>, bool) {
+        (self.batch, self.batch_keys)
+    }
+
+    /// Hold input back for the batch window BATCH, or None to report it
+    /// as it arrives.  KEYS says whether keys are held back too.
+    pub fn set_batch(&mut self, batch: Option<Duration>, keys: bool) {
+        self.batch = batch;
+        self.batch_keys = keys;
+        if batch.is_none() {
+            self.batch_start = None;
+        }
+    }
+
+    /// Whether newly buffered input should be reported to Emacs at NOW.
+    /// URGENT input isn't held back by the minimum interval, and KEY
+    /// input not by the batch window unless keys are batched.
+    pub fn should_report(&mut self, urgent: bool, key: bool, now: Instant) -> bool {
+        let throttled = match (self.interval, self.last_report) {
+            (Some(interval), Some(last_report)) => !urgent && now < last_report + interval,
+            _ => false,
+        };
+        let batched = match self.batch {
+            Some(batch) if self.batch_keys || !key => {
+                now < *self.batch_start.get_or_insert(now) + batch
+            }
+            _ => false,
+        };
+
+        if throttled || batched {
+            self.deferred = true;
+            return false;
+        }
+
+        self.report(now);
+        true
+    }
+
+    fn report(&mut self, now: Instant) {
+        self.last_report = Some(now);
+        self.batch_start = None;
+        self.deferred = false;
+    }
+
+    /// When input held back is due to be reported, if any.
+    pub fn deadline(&self) -> Option<Instant> {
+        if !self.deferred {
+            return None;
+        }
+
+        let throttled = match (self.interval, self.last_report) {
+            (Some(interval), Some(last_report)) => Some(last_report + interval),
+            _ => None,
+        };
+        let batched = match (self.batch, self.batch_start) {
+            (Some(batch), Some(batch_start)) => Some(batch_start + batch),
+            _ => None,
+        };
+        throttled.max(batched)
+    }
+
+    /// Whether input held back is due at NOW, in which case it counts as
+    /// reported.
+    pub fn take_due(&mut self, now: Instant) -> bool {
+        match self.deadline() {
+            Some(deadline) if deadline <= now => {
+                self.report(now);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether input already buffered may be reported at NOW: it isn't
+    /// held back, or has been held back long enough.
+    pub fn may_report(&mut self, now: Instant) -> bool {
+        self.deadline().is_none() || self.take_due(now)
+    }
+}
+
+/// Whether buffered EVENT is a key, which the batch window of an
+/// `InputThrottle` only holds back if keys are batched.
+pub fn is_key_event(event: &GUIEvent) -> bool {
+    matches!(
+        event,
+        Event::WindowEvent {
+            event: WindowEvent::KeyboardInput { .. }
+                | WindowEvent::ReceivedCharacter(_)
+                | WindowEvent::ModifiersChanged(_),
+            ..
+        }
+    )
+}
+
+/// Whether buffered EVENT may be held back by the minimum interval of an
+/// `InputThrottle`.
+pub fn is_throttled_event(event: &GUIEvent) -> bool {
+    match event {
+        Event::WindowEvent { event, .. } => matches!(
+            event,
+            WindowEvent::CursorMoved { .. }
+                | WindowEvent::AxisMotion { .. }
+                | WindowEvent::MouseWheel { .. }
+                | WindowEvent::Resized(_)
+                | WindowEvent::Occluded(_)
+        ),
+        Event::DeviceEvent { .. } | Event::RedrawRequested(_) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn motion_is_held_back_until_the_interval_passes() {
+        let interval = Duration::from_millis(100);
+        let mut throttle = InputThrottle::new();
+        throttle.set_interval(Some(interval));
+
+        let start = Instant::now();
+        assert!(throttle.should_report(false, false, start));
+
+        let soon = start + interval / 2;
+        assert!(!throttle.should_report(false, false, soon));
+        assert_eq!(throttle.deadline(), Some(start + interval));
+        assert!(!throttle.take_due(soon));
+
+        assert!(throttle.take_due(start + interval));
+        assert_eq!(throttle.deadline(), None);
+
+        // Keys aren't held back.
+        assert!(throttle.should_report(true, true, start + interval + interval / 2));
+    }
+
+    #[test]
+    fn input_is_batched_from_the_first_event() {
+        let batch = Duration::from_millis(10);
+        let mut throttle = InputThrottle::new();
+        throttle.set_batch(Some(batch), false);
+
+        let start = Instant::now();
+        assert!(!throttle.should_report(true, false, start));
+        assert!(!throttle.should_report(false, false, start + batch / 2));
+        assert_eq!(throttle.deadline(), Some(start + batch));
+
+        // Keys aren't batched unless asked to be, and take the batch along.
+        assert!(throttle.should_report(true, true, start + batch / 2));
+        assert_eq!(throttle.deadline(), None);
+
+        throttle.set_batch(Some(batch), true);
+        let later = start + batch * 2;
+        assert!(!throttle.should_report(true, true, later));
+        assert!(throttle.take_due(later + batch));
+        assert_eq!(throttle.deadline(), None);
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use winit::{
+    event::{ElementState, Event, KeyboardInput, ScanCode, WindowEvent},
+    window::WindowId,
+};
+
+use crate::event_loop::GUIEvent;
+
+/// Key repeat handling for keyboard events.
+///
+/// Without a configured rate we follow the platform, forwarding whatever
+/// winit delivers.  With a rate, repeats generated by the platform are
+/// dropped and a held key is repeated after `delay` every `interval`, so
+/// keys repeat the same way on every platform.
+pub struct KeyRepeat {
+    rate: Option<(Duration, Duration)>,
+    held: HashMap<WindowId, HeldKeys>,
+}
+
+#[derive(Default)]
+struct HeldKeys {
+    keys: HashMap<ScanCode, HeldKey>,
+    // Only the most recently pressed key repeats, and characters
+    // received after a key press are the text of that key.
+    last: Option<ScanCode>,
+    // Set while a platform repeat is dropped, so its text is dropped too.
+    suppress_chars: bool,
+}
+
+struct HeldKey {
+    events: Vec<GUIEvent>,
+    next_repeat: Instant,
+}
+
+impl KeyRepeat {
+    pub fn new() -> Self {
+        KeyRepeat {
+            rate: None,
+            held: HashMap::new(),
+        }
+    }
+
+    pub fn rate(&self) -> Option<(Duration, Duration)> {
+        self.rate
+    }
+
+    /// Set the repeat (DELAY, INTERVAL), or None to follow the platform.
+    pub fn set_rate(&mut self, rate: Option<(Duration, Duration)>) {
+        self.rate = rate;
+        self.held.clear();
+    }
+
+    pub fn forget_window(&mut self, window_id: &WindowId) {
+        self.held.remove(window_id);
+    }
+
+    /// Track held keys from EVENT, returning whether EVENT should be
+    /// passed on to Emacs.
+    pub fn filter(&mut self, event: &GUIEvent, now: Instant) -> bool {
+        let delay = match self.rate {
+            Some((delay, _)) => delay,
+            None => return true,
+        };
+
+        let (window_id, window_event) = match event {
+            Event::WindowEvent { window_id, event } => (*window_id, event),
+            _ => return true,
+        };
+
+        let held = self.held.entry(window_id).or_default();
+
+        match window_event {
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    scancode, state, ..
+                },
+                is_synthetic,
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    if held.keys.contains_key(scancode) {
+                        // The platform repeats this key on its own.
+                        held.suppress_chars = true;
+                        return false;
+                    }
+
+                    held.suppress_chars = false;
+
+                    // Synthetic presses are sent for keys already held
+                    // when a window gains focus; don't start repeating them.
+                    if !is_synthetic {
+                        held.keys.insert(
+                            *scancode,
+                            HeldKey {
+                                events: vec![event.clone()],
+                                next_repeat: now + delay,
+                            },
+                        );
+                        held.last = Some(*scancode);
+                    }
+
+                    true
+                }
+                ElementState::Released => {
+                    held.keys.remove(scancode);
+                    if held.last == Some(*scancode) {
+                        held.last = None;
+                    }
+                    held.suppress_chars = false;
+
+                    true
+                }
+            },
+            WindowEvent::ReceivedCharacter(_) => {
+                if held.suppress_chars {
+                    return false;
+                }
+
+                if let Some(key) = held.last.and_then(|scancode| held.keys.get_mut(&scancode)) {
+                    key.events.push(event.clone());
+                }
+
+                true
+            }
+            WindowEvent::Focused(false) => {
+                held.keys.clear();
+                held.last = None;
+                held.suppress_chars = false;
+
+                true
+            }
+            _ => true,
+        }
+    }
+
+    /// When the next synthesized repeat is due, if any.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.held
+            .values()
+            .filter_map(|held| held.last.and_then(|scancode| held.keys.get(&scancode)))
+            .map(|key| key.next_repeat)
+            .min()
+    }
+
+    /// Return the events of every repeat that is due at NOW.
+    pub fn take_due(&mut self, now: Instant) -> Vec<GUIEvent> {
+        let interval = match self.rate {
+            Some((_, interval)) => interval,
+            None => return Vec::new(),
+        };
+
+        let mut due = Vec::new();
+
+        for held in self.held.values_mut() {
+            if let Some(key) = held.last.and_then(|scancode| held.keys.get_mut(&scancode)) {
+                if key.next_repeat <= now {
+                    due.extend(key.events.iter().cloned());
+                    key.next_repeat = now + interval;
+                }
+            }
+        }
+
+        due
+    }
+}
@@ -0,0 +1,107 @@
+//! Layers drawn over the frame in a window, each with a display list of
+//! its own, so that transient things such as tooltips can be redrawn
+//! without redisplaying the frame under them.
+//!
+//! WebRender renders only one document of a renderer, so a layer is a
+//! pipeline of the window's document rather than a document of its own.
+//! The window's display list embeds the layers with iframes over the
+//! frame, lowest z-order first, which also has hit testing find the
+//! items of higher layers first.  When only a layer changes, its display
+//! list is sent on its own, and the window is drawn again from the copy
+//! of its last frame, which never has the layers in it.
+
+use webrender::api::PipelineId;
+
+struct Layer {
+    id: u32,
+    pipeline_id: PipelineId,
+    z_order: i32,
+}
+
+/// The layers of a window, bottom first.
+#[derive(Default)]
+pub struct Layers {
+    layers: Vec<Layer>,
+    last_id: u32,
+}
+
+impl Layers {
+    /// Add a layer at Z_ORDER, over the layers at the same or a lower
+    /// one, and return its id.
+    pub fn add(&mut self, z_order: i32) -> u32 {
+        self.last_id += 1;
+        let id = self.last_id;
+        let index = self
+            .layers
+            .iter()
+            .position(|layer| layer.z_order > z_order)
+            .unwrap_or(self.layers.len());
+
+        // The frame itself is pipeline 0.
+        self.layers.insert(
+            index,
+            Layer {
+                id,
+                pipeline_id: PipelineId(0, id),
+                z_order,
+            },
+        );
+        id
+    }
+
+    /// Remove the layer ID, returning its pipeline.
+    pub fn remove(&mut self, id: u32) -> Option<PipelineId> {
+        let index = self.layers.iter().position(|layer| layer.id == id)?;
+        Some(self.layers.remove(index).pipeline_id)
+    }
+
+    pub fn pipeline_id(&self, id: u32) -> Option<PipelineId> {
+        self.layers
+            .iter()
+            .find(|layer| layer.id == id)
+            .map(|layer| layer.pipeline_id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// The pipelines of the layers, in the order they are drawn.
+    pub fn pipeline_ids(&self) -> impl Iterator<Item = PipelineId> + '_ {
+        self.layers.iter().map(|layer| layer.pipeline_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_are_drawn_by_z_order() {
+        let mut layers = Layers::default();
+        let top = layers.add(1);
+        let bottom = layers.add(-1);
+        let middle = layers.add(0);
+        let over_middle = layers.add(0);
+
+        let order: Vec<_> = layers.pipeline_ids().collect();
+        let expected: Vec<_> = [bottom, middle, over_middle, top]
+            .iter()
+            .map(|id| layers.pipeline_id(*id).unwrap())
+            .collect();
+        assert_eq!(order, expected);
+    }
+
+    #[test]
+    fn removed_layers_are_gone() {
+        let mut layers = Layers::default();
+        let id = layers.add(0);
+        assert_eq!(layers.remove(id), Some(PipelineId(0, id)));
+        assert_eq!(layers.remove(id), None);
+        assert_eq!(layers.pipeline_id(id), None);
+        assert!(layers.is_empty());
+
+        // Ids aren't reused, so a stale one can't remove a new layer.
+        assert_ne!(layers.add(0), id);
+    }
+}
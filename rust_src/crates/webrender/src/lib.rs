@@ -10,21 +10,47 @@ extern crate colors;
 
 pub mod color;
 pub mod display_info;
+pub mod external_image;
 pub mod font;
 pub mod frame;
+#[cfg(any(test, feature = "headless"))]
+pub mod headless;
 pub mod input;
 pub mod output;
 pub mod term;
 
+mod blur;
+mod clipboard;
 mod cursor;
+mod custom_cursor;
+mod debug_flags;
+mod devices;
 mod draw_canvas;
 mod event;
 mod event_loop;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod event_thread;
 mod font_db;
 mod fringe;
+#[cfg(target_os = "macos")]
+mod idle_sleep;
 mod image;
+mod input_throttle;
+mod key_repeat;
+mod layer;
+mod modifiers;
+mod monitor_id;
+mod overlay;
+mod readback;
+mod select_stats;
+mod swap_interval;
 mod texture;
 mod util;
+mod wakeup_events;
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+mod watchdog;
+mod window_state;
+mod work_area;
 mod wrterm;
 
 mod platform {
@@ -0,0 +1,88 @@
+use winit::event::{Event, ModifiersState, WindowEvent};
+
+use crate::event_loop::GUIEvent;
+
+const MODIFIERS: [ModifiersState; 4] = [
+    ModifiersState::SHIFT,
+    ModifiersState::CTRL,
+    ModifiersState::ALT,
+    ModifiersState::LOGO,
+];
+
+/// A remapping of modifier keys, applied to events before they are
+/// buffered for Emacs.  Each modifier maps to the modifiers it stands
+/// for; the default maps every modifier to itself.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ModifierRemap {
+    targets: [ModifiersState; 4],
+}
+
+impl Default for ModifierRemap {
+    fn default() -> Self {
+        ModifierRemap { targets: MODIFIERS }
+    }
+}
+
+impl ModifierRemap {
+    /// Make the modifier FROM act as TO.
+    pub fn set(&mut self, from: ModifiersState, to: ModifiersState) {
+        for (modifier, target) in MODIFIERS.iter().zip(self.targets.iter_mut()) {
+            if from.contains(*modifier) {
+                *target = to;
+            }
+        }
+    }
+
+    pub fn apply(&self, state: ModifiersState) -> ModifiersState {
+        MODIFIERS
+            .iter()
+            .zip(self.targets.iter())
+            .filter(|(modifier, _)| state.contains(**modifier))
+            .fold(ModifiersState::empty(), |state, (_, target)| {
+                state | *target
+            })
+    }
+
+    /// Rewrite the modifiers EVENT carries, both modifier changes and the
+    /// modifiers snapshot of input events, so chords stay consistent.
+    #[allow(deprecated)]
+    pub fn remap_event(&self, event: &mut GUIEvent) {
+        if *self == Self::default() {
+            return;
+        }
+
+        let event = match event {
+            Event::WindowEvent { event, .. } => event,
+            _ => return,
+        };
+
+        match event {
+            WindowEvent::ModifiersChanged(state) => *state = self.apply(*state),
+            WindowEvent::KeyboardInput { input, .. } => {
+                input.modifiers = self.apply(input.modifiers);
+            }
+            WindowEvent::MouseInput { modifiers, .. }
+            | WindowEvent::MouseWheel { modifiers, .. }
+            | WindowEvent::CursorMoved { modifiers, .. } => *modifiers = self.apply(*modifiers),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swap_alt_and_logo() {
+        let mut remap = ModifierRemap::default();
+        remap.set(ModifiersState::ALT, ModifiersState::LOGO);
+        remap.set(ModifiersState::LOGO, ModifiersState::ALT);
+
+        assert_eq!(
+            remap.apply(ModifiersState::ALT | ModifiersState::CTRL),
+            ModifiersState::LOGO | ModifiersState::CTRL
+        );
+        assert_eq!(remap.apply(ModifiersState::LOGO), ModifiersState::ALT);
+    }
+}
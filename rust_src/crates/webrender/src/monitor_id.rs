@@ -0,0 +1,82 @@
+//! Identifiers for monitors that Lisp can save and match against the
+//! monitors of a later session.
+//!
+//! winit has no identity for monitors beyond a handle that lasts one
+//! session, and their names may be missing or shared, so an identifier
+//! hashes a monitor's name together with its position and size.  That is
+//! only as stable as the platform keeps those: rearranging monitors or
+//! changing their resolution changes the identifiers.
+
+use winit::monitor::MonitorHandle;
+
+// What a monitor's identifier is made from.
+type MonitorKey = (Option<String>, (i32, i32), (u32, u32));
+
+/// The identifier of each of MONITORS, in order.  Monitors that are
+/// alike in name, position and size, as cloned displays, are told apart
+/// by their order among each other.
+pub fn monitor_ids(monitors: &[MonitorHandle]) -> Vec<String> {
+    let keys: Vec<MonitorKey> = monitors
+        .iter()
+        .map(|monitor| {
+            let position = monitor.position();
+            let size = monitor.size();
+            (
+                monitor.name(),
+                (position.x, position.y),
+                (size.width, size.height),
+            )
+        })
+        .collect();
+
+    ids_of_keys(&keys)
+}
+
+fn ids_of_keys(keys: &[MonitorKey]) -> Vec<String> {
+    keys.iter()
+        .enumerate()
+        .map(|(index, key)| {
+            let id = format!("{:016x}", hash_key(key));
+            match keys[..index].iter().filter(|other| *other == key).count() {
+                0 => id,
+                clones => format!("{id}-{clones}"),
+            }
+        })
+        .collect()
+}
+
+// FNV-1a, which unlike the hasher of the standard library is guaranteed
+// to hash the same way in every build.
+fn hash_key((name, (x, y), (width, height)): &MonitorKey) -> u64 {
+    let name = name.as_deref().unwrap_or("");
+    let geometry = [
+        x.to_le_bytes(),
+        y.to_le_bytes(),
+        width.to_le_bytes(),
+        height.to_le_bytes(),
+    ];
+
+    name.bytes()
+        .chain([0])
+        .chain(geometry.into_iter().flatten())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cloned_monitors_are_told_apart() {
+        let laptop = (Some("eDP-1".to_owned()), (0, 0), (1920, 1080));
+        let clone = (None, (1920, 0), (1920, 1080));
+        let ids = ids_of_keys(&[clone.clone(), laptop.clone(), clone.clone()]);
+
+        assert_eq!(ids[0].len(), 16);
+        assert_ne!(ids[0], ids[1]);
+        assert_eq!(ids[2], format!("{}-1", ids[0]));
+        assert_eq!(ids_of_keys(&[laptop])[0], ids[1]);
+    }
+}
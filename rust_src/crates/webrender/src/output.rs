@@ -1,20 +1,27 @@
-use std::{cell::RefCell, rc::Rc, sync::Arc};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    sync::Arc,
+};
 
 use euclid::default::Size2D;
 use gleam::gl;
-use log::warn;
+use image::RgbaImage;
+use log::{info, warn};
 use std::collections::HashMap;
 
 use std::{
     ops::{Deref, DerefMut},
     ptr,
+    time::Duration,
 };
 use surfman::GLApi;
 use webrender_surfman::WebrenderSurfman;
 use winit::{
     self,
-    dpi::PhysicalSize,
-    window::{CursorIcon, Window},
+    dpi::{LogicalSize, PhysicalSize},
+    monitor::MonitorHandle,
+    window::{CursorGrabMode, CursorIcon, Fullscreen, Icon, UserAttentionType, Window},
 };
 
 #[cfg(all(feature = "wayland", not(any(target_os = "macos", windows))))]
@@ -27,7 +34,14 @@ use emacs::{
     frame::LispFrameRef,
 };
 
-use crate::event_loop::WrEventLoop;
+use crate::custom_cursor::CustomCursor;
+use crate::event_loop::{is_suspended, AdapterPreference, WebrenderInitError, WrEventLoop};
+use crate::external_image::{external_image_id, latest_frame};
+use crate::layer::Layers;
+use crate::overlay::{self, Overlay};
+use crate::readback;
+use crate::swap_interval::set_swap_interval;
+use crate::work_area::work_area;
 
 use super::texture::TextureResourceManager;
 use super::util::HandyDandyRectBuilder;
@@ -39,6 +53,54 @@ use super::{
 #[cfg(all(feature = "wayland", not(any(target_os = "macos", windows))))]
 use emacs::{bindings::globals, multibyte::LispStringRef};
 
+/// Overlay drawn over the window while it flashes as a visible bell.
+const FLASH_COLOR: ColorF = ColorF {
+    r: 0.5,
+    g: 0.5,
+    b: 0.5,
+    a: 0.5,
+};
+
+/// `GL_CONTEXT_LOST`, the error GL calls fail with once the context was
+/// reset, as by a GPU reset or a driver update.
+const GL_CONTEXT_LOST: gl::GLenum = 0x0507;
+
+/// Presents that fail in a row before the context is taken to be lost,
+/// where the driver doesn't report the reset through GL.
+const FAILED_PRESENTS_BEFORE_RESET: u32 = 3;
+
+/// How the window of a new frame is created.
+#[derive(Clone, Debug, Default)]
+pub struct WindowOptions {
+    /// The monitor to open the window on.  The window opens at the top
+    /// left corner of the monitor's work area.  Without a monitor, the
+    /// window system places the window.
+    pub monitor: Option<MonitorHandle>,
+    /// Open the window as an overlay, as for a child frame.  The overlay
+    /// is placed where it says rather than on a monitor.
+    pub overlay: Option<Overlay>,
+    /// The size the window can't be resized below, not counting its
+    /// decorations.
+    pub min_size: Option<LogicalSize<u32>>,
+    /// The size the window can't be resized beyond, not counting its
+    /// decorations.
+    pub max_size: Option<LogicalSize<u32>>,
+}
+
+/// Whether a window can have both the size limits MIN_SIZE and MAX_SIZE,
+/// which it can't with a minimum larger than its maximum either way.
+pub fn size_limits_fit(
+    min_size: Option<LogicalSize<u32>>,
+    max_size: Option<LogicalSize<u32>>,
+) -> bool {
+    match (min_size, max_size) {
+        (Some(min_size), Some(max_size)) => {
+            min_size.width <= max_size.width && min_size.height <= max_size.height
+        }
+        _ => true,
+    }
+}
+
 pub struct Output {
     // Extend `wr_output` struct defined in `wrterm.h`
     pub output: wr_output,
@@ -60,6 +122,15 @@ pub struct Output {
     font_render_mode: Option<FontRenderMode>,
     allow_mipmaps: bool,
 
+    // winit can't read a window's title back.
+    title: Option<String>,
+    // Whether the window asked for attention since it last had focus.
+    attention_requested: bool,
+    // winit can't read a window's size limits back either.
+    min_inner_size: Option<LogicalSize<u32>>,
+    max_inner_size: Option<LogicalSize<u32>>,
+    overlay: Option<Overlay>,
+
     pub render_api: RenderApi,
     pub document_id: DocumentId,
     pipeline_id: PipelineId,
@@ -67,8 +138,14 @@ pub struct Output {
 
     display_list_builder: Option<DisplayListBuilder>,
     previous_frame_image: Option<ImageKey>,
+    layers: Layers,
+    // The images of the external image sources drawn in the window, and
+    // the size they were added with.
+    external_images: HashMap<u32, (ImageKey, DeviceIntSize)>,
 
     pub background_color: ColorF,
+    // What the renderer clears the window to before drawing.
+    clear_color: ColorF,
     pub cursor_color: ColorF,
     pub cursor_foreground_color: ColorF,
 
@@ -83,14 +160,39 @@ pub struct Output {
     window: winit::window::Window,
     webrender_surfman: WebrenderSurfman,
     gl: Rc<dyn gl::Gl>,
+    // Set by GL calls that fail because the context was lost.
+    context_lost: Rc<Cell<bool>>,
+    failed_presents: u32,
 
     frame: LispFrameRef,
 }
 
 impl Output {
-    pub fn build(event_loop: &mut WrEventLoop, frame: LispFrameRef) -> Self {
+    pub fn build(
+        event_loop: &mut WrEventLoop,
+        frame: LispFrameRef,
+        options: WindowOptions,
+    ) -> Result<Self, WebrenderInitError> {
         let window_builder = winit::window::WindowBuilder::new().with_visible(true);
 
+        let window_builder = match (options.overlay, options.monitor) {
+            (Some(overlay), _) => overlay.window_builder(window_builder),
+            (None, Some(monitor)) => {
+                let work_area = work_area(&monitor, event_loop.platform());
+                window_builder.with_position(work_area.position)
+            }
+            (None, None) => window_builder,
+        };
+
+        let window_builder = match options.min_size {
+            Some(size) => window_builder.with_min_inner_size(size),
+            None => window_builder,
+        };
+        let window_builder = match options.max_size {
+            Some(size) => window_builder.with_max_inner_size(size),
+            None => window_builder,
+        };
+
         #[cfg(all(feature = "wayland", not(any(target_os = "macos", windows))))]
         let window_builder = {
             let invocation_name: LispStringRef = unsafe { globals.Vinvocation_name.into() };
@@ -98,45 +200,30 @@ impl Output {
             window_builder.with_name(invocation_name, "")
         };
 
-        let window = window_builder.build(&event_loop.el()).unwrap();
-        let webrender_surfman = event_loop.new_webrender_surfman(&window);
-
-        // Get GL bindings
-        let gl = match webrender_surfman.connection().gl_api() {
-            GLApi::GL => unsafe { gl::GlFns::load_with(|s| webrender_surfman.get_proc_address(s)) },
-            GLApi::GLES => unsafe {
-                gl::GlesFns::load_with(|s| webrender_surfman.get_proc_address(s))
-            },
-        };
-
-        let gl = gl::ErrorCheckingGl::wrap(gl);
-
-        // Make sure the gl context is made current.
-        webrender_surfman.make_gl_context_current().unwrap();
-
-        let webrender_opts = webrender::WebRenderOptions {
-            clear_color: ColorF::new(1.0, 1.0, 1.0, 1.0),
-            ..webrender::WebRenderOptions::default()
+        let window = event_loop.build_window(window_builder);
+        if let Some(overlay) = options.overlay {
+            overlay.window_built(&window);
+        }
+        event_loop.register_window(&window);
+        let webrender_surfman = match event_loop.new_webrender_surfman(&window) {
+            Ok(webrender_surfman) => webrender_surfman,
+            Err(err) => {
+                event_loop.unregister_window(&window.id());
+                return Err(err);
+            }
         };
 
-        let notifier = Box::new(Notifier::new(event_loop.create_proxy()));
-        let (mut renderer, sender) =
-            webrender::create_webrender_instance(gl.clone(), notifier, webrender_opts, None)
-                .unwrap();
-
-        let texture_resources = Rc::new(RefCell::new(TextureResourceManager::new(
-            gl.clone(),
-            sender.create_api(),
-        )));
-
-        let external_image_handler = texture_resources.borrow_mut().new_external_image_handler();
-
-        renderer.set_external_image_handler(external_image_handler);
+        let (gl, context_lost) = Self::load_gl(&webrender_surfman);
+        if event_loop.vsync() {
+            set_swap_interval(&webrender_surfman, 1);
+        }
+        let clear_color = ColorF::new(1.0, 1.0, 1.0, 1.0);
+        let (renderer, texture_resources, api) =
+            Self::create_renderer(event_loop, gl.clone(), clear_color);
 
         let epoch = Epoch(0);
         let pipeline_id = PipelineId(0, 0);
 
-        let api = sender.create_api();
         let device_size = {
             let size = window.inner_size();
             DeviceIntSize::new(size.width as i32, size.height as i32)
@@ -152,6 +239,11 @@ impl Output {
             font_instances: HashMap::new(),
             font_render_mode: None,
             allow_mipmaps: false,
+            title: None,
+            attention_requested: false,
+            min_inner_size: options.min_size,
+            max_inner_size: options.max_size,
+            overlay: options.overlay,
             render_api: api,
             document_id,
             pipeline_id,
@@ -159,18 +251,162 @@ impl Output {
             epoch,
             display_list_builder: None,
             previous_frame_image: None,
+            layers: Layers::default(),
+            external_images: HashMap::new(),
             background_color: ColorF::WHITE,
+            clear_color,
             cursor_color: ColorF::BLACK,
             cursor_foreground_color: ColorF::WHITE,
             renderer,
             webrender_surfman,
+            context_lost,
+            failed_presents: 0,
             texture_resources,
             frame,
         };
 
         Self::build_mouse_cursors(&mut output);
 
-        output
+        Ok(output)
+    }
+
+    // Load the GL bindings of WEBRENDER_SURFMAN's context, and make it
+    // current.  The flag returned is set once a GL call fails because
+    // the context was lost.  Other GL errors panic, unless the context
+    // was lost already: calls into a lost context fail with whatever
+    // error the driver likes, without a robust context, which surfman
+    // can't ask for.
+    fn load_gl(webrender_surfman: &WebrenderSurfman) -> (Rc<dyn gl::Gl>, Rc<Cell<bool>>) {
+        let gl = match webrender_surfman.connection().gl_api() {
+            GLApi::GL => unsafe { gl::GlFns::load_with(|s| webrender_surfman.get_proc_address(s)) },
+            GLApi::GLES => unsafe {
+                gl::GlesFns::load_with(|s| webrender_surfman.get_proc_address(s))
+            },
+        };
+
+        let context_lost = Rc::new(Cell::new(false));
+        let lost = context_lost.clone();
+        let gl = gl::ErrorReactingGl::wrap(gl, move |_gl: &dyn gl::Gl, name: &str, err| {
+            if err == GL_CONTEXT_LOST {
+                lost.set(true);
+            } else if !lost.get() {
+                panic!("Caught GL error {:x} at {}", err, name);
+            }
+        });
+
+        // Make sure the gl context is made current.
+        webrender_surfman.make_gl_context_current().unwrap();
+
+        (gl, context_lost)
+    }
+
+    // Create a renderer drawing with GL, which must be current, with the
+    // texture resources it reads external images from and an API to
+    // send it display lists.
+    fn create_renderer(
+        event_loop: &WrEventLoop,
+        gl: Rc<dyn gl::Gl>,
+        clear_color: ColorF,
+    ) -> (Renderer, Rc<RefCell<TextureResourceManager>>, RenderApi) {
+        let webrender_opts = webrender::WebRenderOptions {
+            clear_color,
+            ..webrender::WebRenderOptions::default()
+        };
+
+        let notifier = Box::new(Notifier::new(event_loop.create_proxy()));
+        let (mut renderer, sender) =
+            webrender::create_webrender_instance(gl.clone(), notifier, webrender_opts, None)
+                .unwrap();
+
+        let texture_resources = Rc::new(RefCell::new(TextureResourceManager::new(
+            gl,
+            sender.create_api(),
+        )));
+
+        let external_image_handler = texture_resources.borrow_mut().new_external_image_handler();
+
+        renderer.set_external_image_handler(external_image_handler);
+        renderer.set_debug_flags(event_loop.debug_flags());
+
+        (renderer, texture_resources, sender.create_api())
+    }
+
+    /// Whether the GL context of the window was lost, as on a GPU reset,
+    /// a driver update or a switch of GPUs, so nothing drawn shows.
+    pub fn context_lost(&self) -> bool {
+        self.context_lost.get() || self.failed_presents >= FAILED_PRESENTS_BEFORE_RESET
+    }
+
+    /// Recreate the surface, GL context and renderer of the window after
+    /// the context was lost or the system resumed, with the adapter
+    /// `NG_WR_ADAPTER` selects or else in software.  The document is
+    /// recreated along with the renderer that held it, and so are the
+    /// fonts and images it held: the window has to be redrawn from
+    /// scratch, with images reloaded.
+    pub fn reset_context(
+        &mut self,
+        event_loop: &mut WrEventLoop,
+    ) -> Result<(), WebrenderInitError> {
+        let webrender_surfman = match event_loop.new_webrender_surfman(&self.window) {
+            Ok(webrender_surfman) => webrender_surfman,
+            Err(err) => {
+                warn!(
+                    "Failed to recreate GL context ({}), falling back to software",
+                    err
+                );
+                event_loop.new_webrender_surfman_with(&self.window, AdapterPreference::Software)?
+            }
+        };
+
+        let (gl, context_lost) = Self::load_gl(&webrender_surfman);
+        if event_loop.vsync() {
+            set_swap_interval(&webrender_surfman, 1);
+        }
+        let (renderer, texture_resources, api) =
+            Self::create_renderer(event_loop, gl.clone(), self.clear_color);
+
+        let old_renderer = std::mem::replace(&mut self.renderer, renderer);
+        let old_texture_resources =
+            std::mem::replace(&mut self.texture_resources, texture_resources);
+        let old_webrender_surfman =
+            std::mem::replace(&mut self.webrender_surfman, webrender_surfman);
+        if self.context_lost() {
+            // The GL objects of the old renderer went with its context,
+            // and deleting them would only fail, or hang some drivers:
+            // leak the renderer instead.
+            std::mem::forget(old_texture_resources);
+            std::mem::forget(old_renderer);
+        } else {
+            // Tear down the old renderer with its own context current.
+            if let Err(err) = old_webrender_surfman.make_gl_context_current() {
+                warn!("Failed to make old GL context current: {:?}", err);
+            }
+            old_texture_resources.borrow_mut().clear();
+            drop(old_texture_resources);
+            old_renderer.deinit();
+        }
+        drop(old_webrender_surfman);
+
+        self.gl = gl;
+        self.context_lost = context_lost;
+        self.failed_presents = 0;
+        self.ensure_context_is_current();
+
+        self.display_list_builder = None;
+        self.previous_frame_image = None;
+        self.document_id = api.add_document(self.get_deivce_size());
+        self.render_api = api;
+        self.fonts.clear();
+        self.font_instances.clear();
+        self.external_images.clear();
+        self.clear_layers();
+        // Fringe bitmaps are cached for the whole display; other frames
+        // just create theirs again.
+        self.display_info().get_inner().fringe_bitmap_caches.clear();
+
+        info!("Recreated GL context of window {:?}", self.window.id());
+
+        Ok(())
     }
 
     fn copy_framebuffer_to_texture(&self, device_rect: DeviceIntRect) -> ImageKey {
@@ -246,7 +482,16 @@ impl Output {
     }
 
     pub fn show_window(&self) {
-        self.get_window().set_visible(true);
+        if self.overlay.is_some() {
+            overlay::show(self.get_window());
+        } else {
+            self.get_window().set_visible(true);
+        }
+    }
+
+    /// How the window was opened as an overlay, if it was.
+    pub fn overlay(&self) -> Option<Overlay> {
+        self.overlay
     }
     pub fn hide_window(&self) {
         self.get_window().set_visible(false);
@@ -256,8 +501,156 @@ impl Output {
         self.get_window().set_maximized(true);
     }
 
-    pub fn set_title(&self, title: &str) {
+    pub fn minimize(&self) {
+        self.get_window().set_minimized(true);
+    }
+
+    pub fn restore(&self) {
+        let window = self.get_window();
+        window.set_minimized(false);
+        window.set_maximized(false);
+        window.set_visible(true);
+    }
+
+    pub fn set_fullscreen(&self, fullscreen: Option<Fullscreen>) {
+        self.get_window().set_fullscreen(fullscreen);
+    }
+
+    pub fn set_title(&mut self, title: &str) {
         self.get_window().set_title(title);
+        self.title = Some(title.to_owned());
+    }
+
+    /// The title last set with `set_title`.
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Ask the window system to draw the user's attention to the window,
+    /// e.g. by bouncing its dock icon, unless it already has focus.
+    pub fn request_user_attention(&mut self, attention: UserAttentionType) {
+        self.get_window().request_user_attention(Some(attention));
+        self.attention_requested = true;
+    }
+
+    /// Withdraw a request for attention once the window has focus.  Not
+    /// every window manager does this by itself.
+    pub fn clear_user_attention(&mut self) {
+        if self.attention_requested {
+            self.get_window().request_user_attention(None);
+            self.attention_requested = false;
+        }
+    }
+
+    /// Flash the window as a visible bell: show the last frame under a
+    /// translucent overlay for DURATION, then show it as it was.
+    pub fn flash(&mut self, duration: Duration) {
+        self.flush();
+
+        let image_key = match self.previous_frame_image {
+            Some(image_key) => image_key,
+            None => return,
+        };
+
+        let layout_size = Self::get_size(&self.get_window());
+        let rect = LayoutRect::from_size(layout_size);
+
+        for overlay in [Some(FLASH_COLOR), None] {
+            let mut builder = self.new_builder(Some((image_key, rect)));
+            self.push_layers(&mut builder);
+
+            if let Some(color) = overlay {
+                let space_and_clip = SpaceAndClipInfo::root_scroll(self.pipeline_id);
+                builder.push_rect(
+                    &CommonItemProperties::new(rect, space_and_clip),
+                    rect,
+                    color,
+                );
+            }
+
+            // Neither frame replaces the last one, so the overlay never
+            // ends up in later redisplay.
+            self.render(builder);
+            self.present();
+
+            if overlay.is_some() {
+                std::thread::sleep(duration);
+            }
+        }
+    }
+
+    /// Read back the pixels of RECT of the window, as last displayed.
+    /// RECT is clamped to the window; return None if nothing of it is
+    /// left or nothing has been displayed yet.
+    pub fn read_pixels(
+        &mut self,
+        rect: DeviceIntRect,
+    ) -> Result<Option<RgbaImage>, surfman::Error> {
+        self.flush();
+
+        let image_key = match self.previous_frame_image {
+            Some(image_key) => image_key,
+            None => return Ok(None),
+        };
+
+        // The presented frame is gone from the framebuffer, so render it
+        // again from the copy kept for redisplay, without presenting it.
+        self.webrender_surfman.make_gl_context_current()?;
+        let layout_size = Self::get_size(&self.get_window());
+        let mut builder = self.new_builder(Some((image_key, LayoutRect::from_size(layout_size))));
+        self.push_layers(&mut builder);
+        self.render(builder);
+
+        let pixels = readback::read_pixels(
+            &self.webrender_surfman,
+            &*self.gl,
+            rect,
+            self.renderer.device.surface_origin_is_top_left(),
+        )?;
+
+        Ok(pixels.and_then(|(size, pixels)| {
+            RgbaImage::from_raw(size.width as u32, size.height as u32, pixels)
+        }))
+    }
+
+    /// Replace the debug flags of the renderer, redrawing the window so
+    /// overlays appear or go away.
+    pub fn set_debug_flags(&mut self, flags: DebugFlags) {
+        self.ensure_context_is_current();
+        self.renderer.set_debug_flags(flags);
+        self.get_window().request_redraw();
+    }
+
+    /// Make presents wait for the vertical blank of the display, or stop
+    /// them waiting.  Return false if the swap interval of the window's
+    /// surface can't be set.
+    pub fn set_vsync(&mut self, vsync: bool) -> bool {
+        self.ensure_context_is_current();
+        set_swap_interval(&self.webrender_surfman, vsync as i32)
+    }
+
+    // Present the rendered frame, counting presents that fail in a row.
+    fn present(&mut self) {
+        match self.webrender_surfman.present() {
+            Ok(()) => self.failed_presents = 0,
+            Err(err) => {
+                warn!("Failed to present surface: {:?}", err);
+                self.failed_presents += 1;
+            }
+        }
+    }
+
+    /// Clear the window to COLOR before anything is drawn, as before the
+    /// first redisplay or while the window is resized, and redraw it.
+    pub fn set_clear_color(&mut self, color: ColorF) {
+        self.clear_color = color;
+        self.ensure_context_is_current();
+        self.renderer.set_clear_color(color);
+        self.get_window().request_redraw();
+    }
+
+    pub fn set_icon(&self, icon: Icon) {
+        self.get_window().set_window_icon(Some(icon));
     }
 
     pub fn set_display_info(&mut self, mut dpyinfo: DisplayInfoRef) {
@@ -280,6 +673,52 @@ impl Output {
         self.get_window().inner_size()
     }
 
+    /// The largest width or height the window can be drawn at, in
+    /// physical pixels: its last frame is copied to a texture of its
+    /// size, which the GPU limits.
+    pub fn max_inner_size(&self) -> u32 {
+        self.renderer.device.max_texture_size() as u32
+    }
+
+    pub fn get_outer_size(&self) -> PhysicalSize<u32> {
+        self.get_window().outer_size()
+    }
+
+    /// Ask the window system to resize the window to SIZE, not counting
+    /// its decorations.  The window may not take the size, or not right
+    /// away: it is resized once `Resized` arrives.
+    pub fn set_inner_size(&self, size: PhysicalSize<u32>) {
+        self.get_window().set_inner_size(size);
+    }
+
+    /// Keep the window from being resized below SIZE, not counting its
+    /// decorations, or lift that limit if SIZE is None.  SIZE is logical,
+    /// so winit keeps the limit as the window's scale factor changes.
+    /// Return false, leaving the limit as it was, if SIZE is larger than
+    /// the window's maximum size.
+    pub fn set_min_inner_size(&mut self, size: Option<LogicalSize<u32>>) -> bool {
+        if !size_limits_fit(size, self.max_inner_size) {
+            return false;
+        }
+        self.min_inner_size = size;
+        self.get_window().set_min_inner_size(size);
+        true
+    }
+
+    /// Keep the window from being resized beyond SIZE, not counting its
+    /// decorations, or lift that limit if SIZE is None.  SIZE is logical,
+    /// so winit keeps the limit as the window's scale factor changes.
+    /// Return false, leaving the limit as it was, if SIZE is smaller
+    /// than the window's minimum size.
+    pub fn set_max_inner_size(&mut self, size: Option<LogicalSize<u32>>) -> bool {
+        if !size_limits_fit(self.min_inner_size, size) {
+            return false;
+        }
+        self.max_inner_size = size;
+        self.get_window().set_max_inner_size(size);
+        true
+    }
+
     fn get_deivce_size(&self) -> DeviceIntSize {
         let size = self.get_window().inner_size();
         DeviceIntSize::new(size.width as i32, size.height as i32)
@@ -323,56 +762,160 @@ impl Output {
 
         let builder = std::mem::replace(&mut self.display_list_builder, None);
 
-        if let Some(mut builder) = builder {
-            let layout_size = Self::get_size(&self.get_window());
+        if let Some(builder) = builder {
+            self.display_list_builder = None;
 
-            let epoch = self.epoch;
-            let mut txn = Transaction::new();
+            self.render(builder);
 
-            txn.set_display_list(epoch, None, layout_size.to_f32(), builder.end());
-            txn.set_root_pipeline(self.pipeline_id);
-            txn.generate_frame(0, RenderReasons::NONE);
+            let device_size = self.get_deivce_size();
 
-            self.display_list_builder = None;
+            self.texture_resources.borrow_mut().clear();
 
-            self.render_api.send_transaction(self.document_id, txn);
+            let image_key = self.copy_framebuffer_to_texture(DeviceIntRect::from_size(device_size));
+            self.previous_frame_image = Some(image_key);
 
-            self.render_api.flush_scene_builder();
+            // The layers go over the copy, so they don't end up in the
+            // frames drawn on it.
+            if !self.layers.is_empty() {
+                let layout_size = Self::get_size(&self.get_window());
+                let mut builder =
+                    self.new_builder(Some((image_key, LayoutRect::from_size(layout_size))));
+                self.push_layers(&mut builder);
+                self.render(builder);
+            }
 
-            let device_size = self.get_deivce_size();
+            // Perform the page flip. This will likely block for a while.
+            self.present();
+        }
+    }
 
-            // Bind the webrender framebuffer
-            self.ensure_context_is_current();
+    /// Add a layer over the frame in the window at Z_ORDER, over the
+    /// layers at the same or a lower z-order, and return its id.  The
+    /// layer shows nothing until its display list is set.
+    pub fn add_layer(&mut self, z_order: i32) -> u32 {
+        self.layers.add(z_order)
+    }
 
-            let framebuffer_object = self
-                .webrender_surfman
-                .context_surface_info()
-                .unwrap_or(None)
-                .map(|info| info.framebuffer_object)
-                .unwrap_or(0);
-            self.gl
-                .bind_framebuffer(gleam::gl::FRAMEBUFFER, framebuffer_object);
-            self.assert_gl_framebuffer_complete();
+    /// Replace what LAYER shows with the display list F draws, in the
+    /// coordinates of the window, and show it without redrawing the
+    /// frame.  Return false if there is no LAYER.
+    pub fn set_layer_display_list<F>(&mut self, layer: u32, f: F) -> bool
+    where
+        F: FnOnce(&mut DisplayListBuilder, SpaceAndClipInfo),
+    {
+        let pipeline_id = match self.layers.pipeline_id(layer) {
+            Some(pipeline_id) => pipeline_id,
+            None => return false,
+        };
 
-            self.renderer.update();
+        let layout_size = Self::get_size(&self.get_window());
+        let mut builder = DisplayListBuilder::new(pipeline_id);
+        builder.begin();
+        f(&mut builder, SpaceAndClipInfo::root_scroll(pipeline_id));
 
-            self.assert_no_gl_error();
+        let mut txn = Transaction::new();
+        txn.set_display_list(self.epoch, None, layout_size.to_f32(), builder.end());
+        self.render_api.send_transaction(self.document_id, txn);
 
-            self.renderer.render(device_size, 0).unwrap();
-            let _ = self.renderer.flush_pipeline_info();
+        self.show_layers();
+        true
+    }
 
-            self.texture_resources.borrow_mut().clear();
+    /// Remove LAYER from the window.  Return false if there is no LAYER.
+    pub fn remove_layer(&mut self, layer: u32) -> bool {
+        let pipeline_id = match self.layers.remove(layer) {
+            Some(pipeline_id) => pipeline_id,
+            None => return false,
+        };
 
-            let image_key = self.copy_framebuffer_to_texture(DeviceIntRect::from_size(device_size));
-            self.previous_frame_image = Some(image_key);
+        let mut txn = Transaction::new();
+        txn.remove_pipeline(pipeline_id);
+        self.render_api.send_transaction(self.document_id, txn);
 
-            // Perform the page flip. This will likely block for a while.
-            if let Err(err) = self.webrender_surfman.present() {
-                warn!("Failed to present surface: {:?}", err);
-            }
+        self.show_layers();
+        true
+    }
+
+    // Empty every layer, keeping their ids and z-orders, as their
+    // display lists went with the document that held them.
+    fn clear_layers(&mut self) {
+        let layout_size = Self::get_size(&self.get_window());
+        let mut txn = Transaction::new();
+        for pipeline_id in self.layers.pipeline_ids() {
+            let mut builder = DisplayListBuilder::new(pipeline_id);
+            builder.begin();
+            txn.set_display_list(self.epoch, None, layout_size.to_f32(), builder.end());
+        }
+        self.render_api.send_transaction(self.document_id, txn);
+    }
+
+    // Draw the window again from the copy of its last frame, with the
+    // layers over it.  This replaces the display list of the frame with
+    // the copy, which is why nothing the frame draws can be hit-tested.
+    // Before the first frame there is nothing to show the layers over;
+    // they are shown with it.
+    fn show_layers(&mut self) {
+        let image_key = match self.previous_frame_image {
+            Some(image_key) if !is_suspended() => image_key,
+            _ => return,
+        };
+
+        let layout_size = Self::get_size(&self.get_window());
+        let mut builder = self.new_builder(Some((image_key, LayoutRect::from_size(layout_size))));
+        self.push_layers(&mut builder);
+        self.render(builder);
+        self.present();
+    }
+
+    // Embed the layers in BUILDER, over what it has drawn so far.
+    fn push_layers(&self, builder: &mut DisplayListBuilder) {
+        let layout_size = Self::get_size(&self.get_window());
+        let rect = LayoutRect::from_size(layout_size);
+        let space_and_clip = SpaceAndClipInfo::root_scroll(self.pipeline_id);
+
+        for pipeline_id in self.layers.pipeline_ids() {
+            builder.push_iframe(rect, rect, &space_and_clip, pipeline_id, true);
         }
     }
 
+    // Render the display list of BUILDER to the window's framebuffer.
+    fn render(&mut self, mut builder: DisplayListBuilder) {
+        let layout_size = Self::get_size(&self.get_window());
+
+        let epoch = self.epoch;
+        let mut txn = Transaction::new();
+
+        txn.set_display_list(epoch, None, layout_size.to_f32(), builder.end());
+        txn.set_root_pipeline(self.pipeline_id);
+        txn.generate_frame(0, RenderReasons::NONE);
+
+        self.render_api.send_transaction(self.document_id, txn);
+
+        self.render_api.flush_scene_builder();
+
+        let device_size = self.get_deivce_size();
+
+        // Bind the webrender framebuffer
+        self.ensure_context_is_current();
+
+        let framebuffer_object = self
+            .webrender_surfman
+            .context_surface_info()
+            .unwrap_or(None)
+            .map(|info| info.framebuffer_object)
+            .unwrap_or(0);
+        self.gl
+            .bind_framebuffer(gleam::gl::FRAMEBUFFER, framebuffer_object);
+        self.assert_gl_framebuffer_complete();
+
+        self.renderer.update();
+
+        self.assert_no_gl_error();
+
+        self.renderer.render(device_size, 0).unwrap();
+        let _ = self.renderer.flush_pipeline_info();
+    }
+
     #[track_caller]
     fn assert_no_gl_error(&self) {
         debug_assert_eq!(self.gl.get_error(), gleam::gl::NO_ERROR);
@@ -389,6 +932,16 @@ impl Output {
         );
     }
 
+    /// The tags of the display items at POINT, in physical pixels from
+    /// the top left corner of the window, topmost first.  Only items of
+    /// layers are tagged.  Return None if nothing has been rendered yet.
+    pub fn hit_test(&self, point: WorldPoint) -> Option<Vec<ItemTag>> {
+        self.previous_frame_image?;
+
+        let result = self.render_api.hit_test(self.document_id, point);
+        Some(result.items.into_iter().map(|item| item.tag).collect())
+    }
+
     pub fn get_previous_frame(&self) -> Option<ImageKey> {
         self.previous_frame_image
     }
@@ -586,6 +1139,33 @@ impl Output {
         self.get_window().set_cursor_icon(cursor)
     }
 
+    /// Show CURSOR as the mouse pointer of the window, with its hotspot
+    /// scaled by SCALE_FACTOR.  Return false if the window system can't
+    /// show images as pointers, and the nearest standard shape was shown
+    /// instead, which with winit 0.27 is always.
+    pub fn set_custom_cursor(&self, cursor: &CustomCursor, scale_factor: f64) -> bool {
+        let icon = cursor.fallback(scale_factor);
+        warn!(
+            "Custom cursors are not supported by this window system, using {:?}",
+            icon
+        );
+        self.get_window().set_cursor_icon(icon);
+        false
+    }
+
+    /// Grab the pointer in MODE, confining it to the window or locking it
+    /// in place, or release it with `CursorGrabMode::None`.  Return false
+    /// if the window system can't grab the pointer in MODE.
+    pub fn set_cursor_grab(&self, mode: CursorGrabMode) -> bool {
+        match self.get_window().set_cursor_grab(mode) {
+            Ok(()) => true,
+            Err(err) => {
+                warn!("Failed to set cursor grab {:?}: {:?}", mode, err);
+                false
+            }
+        }
+    }
+
     pub fn add_image(&mut self, width: i32, height: i32, image_data: Arc<Vec<u8>>) -> ImageKey {
         let image_key = self.render_api.generate_image_key();
 
@@ -618,6 +1198,70 @@ impl Output {
         self.render_api.send_transaction(self.document_id, txn);
     }
 
+    /// Draw the latest frame of the external image source ID over RECT of
+    /// the window.  Return false, drawing nothing, if the source has no
+    /// frame yet or doesn't exist.
+    pub fn draw_external_image(&mut self, id: u32, rect: LayoutRect) -> bool {
+        let frame = match latest_frame(id) {
+            Some(frame) => frame,
+            None => {
+                // Forget the image of a source that was unregistered.
+                if let Some((image_key, _)) = self.external_images.remove(&id) {
+                    self.delete_image(image_key);
+                }
+                return false;
+            }
+        };
+
+        let image_key = self.external_image_key(id, frame.size);
+
+        self.display(|builder, space_and_clip| {
+            builder.push_image(
+                &CommonItemProperties::new(rect, space_and_clip),
+                rect,
+                ImageRendering::Auto,
+                AlphaType::PremultipliedAlpha,
+                image_key,
+                ColorF::WHITE,
+            );
+        });
+
+        true
+    }
+
+    // The image standing for the external image source ID, whose frames
+    // are SIZE, added to the document or updated if it changed size.
+    fn external_image_key(&mut self, id: u32, size: DeviceIntSize) -> ImageKey {
+        let image_key = match self.external_images.get(&id) {
+            Some(&(image_key, added_size)) if added_size == size => return image_key,
+            Some(&(image_key, _)) => image_key,
+            None => self.render_api.generate_image_key(),
+        };
+
+        let mut txn = Transaction::new();
+
+        txn.add_image(
+            image_key,
+            ImageDescriptor::new(
+                size.width,
+                size.height,
+                ImageFormat::RGBA8,
+                ImageDescriptorFlags::empty(),
+            ),
+            ImageData::External(ExternalImageData {
+                id: external_image_id(id),
+                channel_index: 0,
+                image_type: ExternalImageType::TextureHandle(ImageBufferKind::Texture2D),
+            }),
+            None,
+        );
+
+        self.render_api.send_transaction(self.document_id, txn);
+        self.external_images.insert(id, (image_key, size));
+
+        image_key
+    }
+
     pub fn delete_image(&mut self, image_key: ImageKey) {
         let mut txn = Transaction::new();
 
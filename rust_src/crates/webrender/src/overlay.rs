@@ -0,0 +1,118 @@
+//! Overlay windows, which child frames such as posframe's popups are
+//! shown in.  An overlay is undecorated, placed at an absolute position
+//! rather than by the window manager, and kept from taking the focus
+//! from the frame it pops up over.  How the focus is kept is up to each
+//! platform:
+//!
+//! - On X11 the window is override-redirect, so the window manager
+//!   leaves it alone: it is neither decorated, placed nor focused.
+//! - On macOS winit only creates plain `NSWindow`s, which can't be the
+//!   non-activating panels AppKit has for this.  The window is created
+//!   hidden at the floating level instead, and shown with `orderFront:`,
+//!   which unlike the `makeKeyAndOrderFront:` of winit doesn't make it
+//!   the key window.
+//! - On Windows the window is owned by the parent's window, which keeps
+//!   it above the parent and out of the taskbar, and has
+//!   `WS_EX_NOACTIVATE`, so clicking it doesn't activate it.
+//! - Wayland can neither place a toplevel window nor keep it from taking
+//!   the focus, so there the window is only undecorated.
+
+use winit::{
+    dpi::PhysicalPosition,
+    window::{Window, WindowBuilder, WindowId},
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Overlay {
+    /// The window the overlay pops up over.
+    pub parent: WindowId,
+    /// Where the top left corner of the overlay goes on the screen.
+    pub position: PhysicalPosition<i32>,
+    // The window that owns the overlay.
+    #[cfg(windows)]
+    parent_hwnd: isize,
+}
+
+impl Overlay {
+    /// An overlay over PARENT, placed at OFFSET from the top left corner
+    /// of PARENT's inner area.
+    pub fn over(parent: &Window, offset: PhysicalPosition<i32>) -> Self {
+        let origin = parent
+            .inner_position()
+            .unwrap_or_else(|_| PhysicalPosition::new(0, 0));
+
+        Overlay {
+            parent: parent.id(),
+            position: PhysicalPosition::new(origin.x + offset.x, origin.y + offset.y),
+            #[cfg(windows)]
+            parent_hwnd: {
+                use winit::platform::windows::WindowExtWindows;
+                parent.hwnd() as isize
+            },
+        }
+    }
+
+    /// Set WINDOW_BUILDER up to build the overlay's window.
+    pub fn window_builder(&self, window_builder: WindowBuilder) -> WindowBuilder {
+        let window_builder = window_builder
+            .with_decorations(false)
+            .with_position(self.position);
+
+        #[cfg(all(unix, not(target_os = "macos")))]
+        let window_builder = {
+            use winit::platform::unix::WindowBuilderExtUnix;
+            window_builder.with_override_redirect(true)
+        };
+
+        #[cfg(target_os = "macos")]
+        let window_builder = window_builder.with_visible(false).with_always_on_top(true);
+
+        #[cfg(windows)]
+        let window_builder = {
+            use winit::platform::windows::WindowBuilderExtWindows;
+            window_builder.with_owner_window(self.parent_hwnd as _)
+        };
+
+        window_builder
+    }
+
+    /// Finish setting up WINDOW, just built from `window_builder`.
+    pub fn window_built(&self, window: &Window) {
+        #[cfg(target_os = "macos")]
+        show(window);
+
+        #[cfg(windows)]
+        {
+            use windows_sys::Win32::UI::WindowsAndMessaging::{
+                GetWindowLongW, SetWindowLongW, GWL_EXSTYLE, WS_EX_NOACTIVATE,
+            };
+            use winit::platform::windows::WindowExtWindows;
+
+            let hwnd = window.hwnd() as _;
+            unsafe {
+                let style = GetWindowLongW(hwnd, GWL_EXSTYLE);
+                SetWindowLongW(hwnd, GWL_EXSTYLE, style | WS_EX_NOACTIVATE as i32);
+            }
+        }
+
+        #[cfg(not(any(target_os = "macos", windows)))]
+        let _ = window;
+    }
+}
+
+/// Show the overlay WINDOW without giving it the focus.
+pub fn show(window: &Window) {
+    #[cfg(target_os = "macos")]
+    {
+        use cocoa::{
+            appkit::NSWindow,
+            base::{id, nil},
+        };
+        use winit::platform::macos::WindowExtMacOS;
+
+        unsafe { NSWindow::orderFront_(window.ns_window() as id, nil) };
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    window.set_visible(true);
+}
@@ -0,0 +1,92 @@
+//! Reading rendered pixels back out of a WebRender surface.
+
+use gleam::gl;
+use webrender::api::units::{DeviceIntRect, DeviceIntSize};
+use webrender_surfman::WebrenderSurfman;
+
+/// Read the RGBA pixels of RECT out of what has been rendered to
+/// WEBRENDER_SURFMAN, as rows from top to bottom.  RECT counts from the
+/// top left corner and is clamped to the surface; return None if nothing
+/// of it is left.  ORIGIN_IS_TOP_LEFT tells whether the surface counts
+/// rows from the top too, instead of from the bottom as GL does.
+pub fn read_pixels(
+    webrender_surfman: &WebrenderSurfman,
+    gl: &dyn gl::Gl,
+    rect: DeviceIntRect,
+    origin_is_top_left: bool,
+) -> Result<Option<(DeviceIntSize, Vec<u8>)>, surfman::Error> {
+    webrender_surfman.make_gl_context_current()?;
+
+    let info = webrender_surfman
+        .context_surface_info()?
+        .ok_or(surfman::Error::Failed)?;
+    let surface_size = DeviceIntSize::new(info.size.width, info.size.height);
+
+    let rect = match clamp_rect(rect, surface_size) {
+        Some(rect) => rect,
+        None => return Ok(None),
+    };
+    let size = rect.size();
+
+    let y = if origin_is_top_left {
+        rect.min.y
+    } else {
+        surface_size.height - rect.max.y
+    };
+
+    gl.bind_framebuffer(gl::FRAMEBUFFER, info.framebuffer_object);
+    let mut pixels = gl.read_pixels(
+        rect.min.x,
+        y,
+        size.width,
+        size.height,
+        gl::RGBA,
+        gl::UNSIGNED_BYTE,
+    );
+
+    if !origin_is_top_left {
+        pixels = flip_rows(&pixels, size.width as usize * 4);
+    }
+
+    Ok(Some((size, pixels)))
+}
+
+// The part of RECT within a surface of SIZE, or None if there is none.
+fn clamp_rect(rect: DeviceIntRect, size: DeviceIntSize) -> Option<DeviceIntRect> {
+    rect.intersection(&DeviceIntRect::from_size(size))
+        .filter(|rect| !rect.is_empty())
+}
+
+// PIXELS with its rows of STRIDE bytes in reverse order.
+fn flip_rows(pixels: &[u8], stride: usize) -> Vec<u8> {
+    pixels
+        .chunks_exact(stride)
+        .rev()
+        .flatten()
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webrender::api::units::DeviceIntPoint;
+
+    #[test]
+    fn rect_is_clamped_to_the_surface() {
+        let size = DeviceIntSize::new(100, 50);
+        let rect = |x, y, width, height| {
+            DeviceIntRect::from_origin_and_size(
+                DeviceIntPoint::new(x, y),
+                DeviceIntSize::new(width, height),
+            )
+        };
+
+        assert_eq!(
+            clamp_rect(rect(-10, 40, 30, 30), size),
+            Some(rect(0, 40, 20, 10))
+        );
+        assert_eq!(clamp_rect(rect(100, 0, 10, 10), size), None);
+        assert_eq!(flip_rows(&[1, 2, 3, 4, 5, 6], 2), vec![5, 6, 3, 4, 1, 2]);
+    }
+}
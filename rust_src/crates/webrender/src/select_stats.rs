@@ -0,0 +1,114 @@
+//! Counters of how `wr_select1` calls went, to tell whether Emacs is
+//! busy-waiting or idle when it uses too much CPU or input lags.  They
+//! are relaxed atomics, which cost the calls next to nothing.
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// The counters since startup or the last reset.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SelectCounts {
+    /// Calls to `wr_select1`.
+    pub calls: u64,
+    /// Window events Emacs read from `EVENT_BUFFER`.
+    pub events: u64,
+    /// Calls that found no window input and only polled Emacs's
+    /// descriptors afterwards.
+    pub fallbacks: u64,
+    /// Calls that returned early to have Emacs read window input.
+    pub input_wakeups: u64,
+    /// Time spent inside `wr_select1`.
+    pub waited: Duration,
+}
+
+#[derive(Default)]
+pub struct SelectStats {
+    calls: AtomicU64,
+    events: AtomicU64,
+    fallbacks: AtomicU64,
+    input_wakeups: AtomicU64,
+    waited_micros: AtomicU64,
+}
+
+impl SelectStats {
+    pub const fn new() -> Self {
+        SelectStats {
+            calls: AtomicU64::new(0),
+            events: AtomicU64::new(0),
+            fallbacks: AtomicU64::new(0),
+            input_wakeups: AtomicU64::new(0),
+            waited_micros: AtomicU64::new(0),
+        }
+    }
+
+    /// Count a call that took WAITED, and whether it fell back to polling.
+    pub fn call(&self, waited: Duration, fallback: bool) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        if fallback {
+            self.fallbacks.fetch_add(1, Ordering::Relaxed);
+        }
+        self.waited_micros
+            .fetch_add(waited.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Count COUNT events read by Emacs.
+    pub fn events_read(&self, count: usize) {
+        self.events.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    /// Count a call returning early for Emacs to read input.
+    pub fn input_wakeup(&self) {
+        self.input_wakeups.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn counts(&self) -> SelectCounts {
+        SelectCounts {
+            calls: self.calls.load(Ordering::Relaxed),
+            events: self.events.load(Ordering::Relaxed),
+            fallbacks: self.fallbacks.load(Ordering::Relaxed),
+            input_wakeups: self.input_wakeups.load(Ordering::Relaxed),
+            waited: Duration::from_micros(self.waited_micros.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Return the counters, and start them over from zero.  Calls counted
+    /// meanwhile on other threads may end up on either side.
+    pub fn take(&self) -> SelectCounts {
+        SelectCounts {
+            calls: self.calls.swap(0, Ordering::Relaxed),
+            events: self.events.swap(0, Ordering::Relaxed),
+            fallbacks: self.fallbacks.swap(0, Ordering::Relaxed),
+            input_wakeups: self.input_wakeups.swap(0, Ordering::Relaxed),
+            waited: Duration::from_micros(self.waited_micros.swap(0, Ordering::Relaxed)),
+        }
+    }
+}
+
+pub static SELECT_STATS: SelectStats = SelectStats::new();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_until_taken() {
+        let stats = SelectStats::new();
+        stats.call(Duration::from_millis(3), false);
+        stats.call(Duration::from_millis(2), true);
+        stats.events_read(4);
+        stats.input_wakeup();
+
+        let counts = SelectCounts {
+            calls: 2,
+            events: 4,
+            fallbacks: 1,
+            input_wakeups: 1,
+            waited: Duration::from_millis(5),
+        };
+        assert_eq!(stats.counts(), counts);
+        assert_eq!(stats.take(), counts);
+        assert_eq!(stats.counts(), SelectCounts::default());
+    }
+}
@@ -0,0 +1,50 @@
+//! The swap interval of a window's surface: how many vertical blanks a
+//! present waits for, 1 to sync presents to the display and 0 not to.
+//!
+//! surfman has no call to set it, so it is set through the extension of
+//! the platform's GL binding, looked up like any GL function:
+//! `eglSwapInterval` with EGL, which surfman uses on Linux and with ANGLE
+//! on Windows, and `wglSwapIntervalEXT` with WGL.  On macOS surfman
+//! presents through Core Animation, which the swap interval of a CGL
+//! context doesn't affect, so it can't be set there.
+
+#[cfg(not(target_os = "macos"))]
+use std::{ffi::c_void, mem};
+
+use webrender_surfman::WebrenderSurfman;
+
+#[cfg(not(target_os = "macos"))]
+const EGL_TRUE: u32 = 1;
+
+/// Set the swap interval of the surface of _WEBRENDER_SURFMAN, whose GL
+/// context must be current, to _INTERVAL.  Return false if it can't be
+/// set on this platform or by the driver.
+pub fn set_swap_interval(_webrender_surfman: &WebrenderSurfman, _interval: i32) -> bool {
+    #[cfg(not(target_os = "macos"))]
+    {
+        let get_current_display = _webrender_surfman.get_proc_address("eglGetCurrentDisplay");
+        let swap_interval = _webrender_surfman.get_proc_address("eglSwapInterval");
+        if !get_current_display.is_null() && !swap_interval.is_null() {
+            let get_current_display: extern "system" fn() -> *mut c_void =
+                unsafe { mem::transmute(get_current_display) };
+            let swap_interval: extern "system" fn(*mut c_void, i32) -> u32 =
+                unsafe { mem::transmute(swap_interval) };
+
+            let display = get_current_display();
+            return !display.is_null() && swap_interval(display, _interval) == EGL_TRUE;
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        let swap_interval = _webrender_surfman.get_proc_address("wglSwapIntervalEXT");
+        if !swap_interval.is_null() {
+            let swap_interval: extern "system" fn(i32) -> i32 =
+                unsafe { mem::transmute(swap_interval) };
+
+            return swap_interval(_interval) != 0;
+        }
+    }
+
+    false
+}
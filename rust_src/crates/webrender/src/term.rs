@@ -1,4 +1,5 @@
 use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::{cmp::max, ffi::CString};
 
 use emacs::multibyte::LispStringRef;
@@ -6,24 +7,36 @@ use lazy_static::lazy_static;
 use winit::{
     dpi::PhysicalPosition,
     event::{ElementState, Event, KeyboardInput, WindowEvent},
+    window::UserAttentionType,
 };
 
 use webrender::api::units::LayoutPoint;
 use webrender::api::{units::LayoutRect, *};
 
-use crate::event_loop::EVENT_BUFFER;
+use crate::clipboard::{CLIPBOARD_CHANGED_EVENT, CLIPBOARD_READ_EVENT};
+use crate::event_loop::{
+    init_select_trace, init_wakeup_signal, is_suspended, GUIEvent, EVENT_BUFFER, EVENT_LOOP,
+};
 use crate::frame::LispFrameExt;
 use crate::fringe::get_or_create_fringe_bitmap;
 use crate::{
     color::{color_to_pixel, color_to_xcolor, lookup_color_by_name_or_hex, pixel_to_color},
     cursor::{draw_bar_cursor, draw_filled_cursor, draw_hollow_box_cursor},
-    display_info::{DisplayInfo, DisplayInfoRef},
+    devices,
+    display_info::{DisplayInfo, DisplayInfoInnerRef, DisplayInfoRef},
     event::create_emacs_event,
     image::WrPixmap,
     output::OutputRef,
+    select_stats::SELECT_STATS,
     util::HandyDandyRectBuilder,
+    wrterm::{
+        queue_clipboard_change_hook, queue_clipboard_read_callbacks, queue_display_reset_hook,
+        queue_theme_change_hook, visual_bell_duration,
+    },
 };
 
+#[cfg(feature = "wr-mouse")]
+use emacs::bindings::note_mouse_highlight;
 use emacs::{
     bindings::{
         block_input, display_and_set_cursor, do_pending_window_change, draw_window_fringes,
@@ -40,10 +53,9 @@ use emacs::{
     },
     bindings::{
         create_terminal, current_kboard, draw_fringe_bitmap_params, fontset_from_font,
-        frame_parm_handler, fullscreen_type, glyph_row, glyph_string, initial_kboard,
-        note_mouse_highlight, output_method, redisplay_interface, scroll_bar_part, terminal,
-        text_cursor_kinds, xlispstrdup, Emacs_Color, Emacs_Cursor, Emacs_Pixmap, Fcons,
-        Fredraw_frame,
+        frame_parm_handler, fullscreen_type, globals, glyph_row, glyph_string, initial_kboard,
+        output_method, redisplay_interface, scroll_bar_part, terminal, text_cursor_kinds,
+        xlispstrdup, Emacs_Color, Emacs_Cursor, Emacs_Pixmap, Fcons, Fredraw_frame,
     },
     font::LispFontRef,
     frame::{all_frames, LispFrameRef, Lisp_Frame},
@@ -194,13 +206,59 @@ extern "C" fn update_window_end(
     unsafe { unblock_input() };
 
     let frame: LispFrameRef = window.get_frame();
-    frame.wr_output().flush();
+    flush_frame(frame);
 }
 
 extern "C" fn flush_display(f: *mut Lisp_Frame) {
     let frame: LispFrameRef = f.into();
 
-    frame.wr_output().flush();
+    flush_frame(frame);
+}
+
+// Don't render frames the platform told us are fully hidden, or while
+// suspended. The frame is redrawn from scratch once it is no longer
+// occluded, or on resume.
+fn flush_frame(frame: LispFrameRef) {
+    let mut output = frame.wr_output();
+
+    if frame.is_occluded() || is_suspended() {
+        output.clear_display_list_builder();
+        return;
+    }
+
+    output.flush();
+
+    if output.context_lost() {
+        reset_lost_context(frame);
+    }
+}
+
+// Recreate the GL context of FRAME's window, which was lost, so what is
+// drawn shows again.  If that fails, it is tried again at the next flush.
+fn reset_lost_context(frame: LispFrameRef) {
+    let window_id = frame.wr_output().get_window().id();
+
+    log::error!(
+        "GL context of window {:?} was lost, recreating it",
+        window_id
+    );
+    reset_context(frame);
+}
+
+// Recreate the GL context of FRAME's window, and have Lisp redraw FRAME
+// and reload its images.
+fn reset_context(frame: LispFrameRef) {
+    let mut output = frame.wr_output();
+    let window_id = output.get_window().id();
+
+    match output.reset_context(&mut EVENT_LOOP.lock()) {
+        Ok(()) => queue_display_reset_hook(frame.into()),
+        Err(err) => log::error!(
+            "Failed to recreate GL context of window {:?}: {}",
+            window_id,
+            err
+        ),
+    }
 }
 
 #[allow(unused_variables)]
@@ -546,164 +604,258 @@ extern "C" fn define_frame_cursor(f: *mut Lisp_Frame, cursor: Emacs_Cursor) {
 extern "C" fn read_input_event(terminal: *mut terminal, hold_quit: *mut input_event) -> i32 {
     let terminal: TerminalRef = terminal.into();
     let dpyinfo = DisplayInfoRef::new(unsafe { terminal.display_info.wr } as *mut _);
+    let dpyinfo = dpyinfo.get_inner();
+
+    let mut events = EVENT_BUFFER.lock();
+    SELECT_STATS.events_read(events.len());
 
-    let mut dpyinfo = dpyinfo.get_inner();
+    events
+        .drain(..)
+        .map(|e| store_input_event(e, terminal, dpyinfo, hold_quit))
+        .sum()
+}
 
+/// Translate the buffered event E and store what it produces in Emacs's
+/// keyboard buffer, returning how many events were stored.
+pub fn store_input_event(
+    e: GUIEvent,
+    terminal: TerminalRef,
+    dpyinfo: DisplayInfoInnerRef,
+    hold_quit: *mut input_event,
+) -> i32 {
     let mut count = 0;
 
-    let mut events = EVENT_BUFFER.lock().unwrap();
+    translate_input_event(e, terminal, dpyinfo, &mut |mut iev| {
+        unsafe { kbd_buffer_store_event_hold(&mut iev, hold_quit) };
+        count += 1;
+    });
 
-    for e in events.iter() {
-        let e = e.clone();
+    count
+}
+
+/// Translate the buffered event E, handing each input event it produces
+/// to STORE.  Window events that aren't input are handled here.
+pub fn translate_input_event(
+    e: GUIEvent,
+    terminal: TerminalRef,
+    mut dpyinfo: DisplayInfoInnerRef,
+    store: &mut dyn FnMut(input_event),
+) {
+    match e {
+        Event::WindowEvent { window_id, event } => {
+            let output = dpyinfo.outputs.get_mut(&window_id);
 
-        match e {
-            Event::WindowEvent { window_id, event } => {
-                let output = dpyinfo.outputs.get_mut(&window_id);
+            if output.is_none() {
+                return;
+            }
 
-                if output.is_none() {
-                    continue;
-                }
+            let output = output.unwrap();
 
-                let output = output.unwrap();
+            let frame: LispObject = output.get_frame().into();
 
-                let frame: LispObject = output.get_frame().into();
+            match event {
+                WindowEvent::ReceivedCharacter(key_code) => {
+                    if let Some(iev) = dpyinfo.input_processor.receive_char(key_code, frame) {
+                        store(iev);
+                    }
+                }
+
+                WindowEvent::ModifiersChanged(state) => {
+                    dpyinfo.input_processor.change_modifiers(state);
+                }
 
-                match event {
-                    WindowEvent::ReceivedCharacter(key_code) => {
-                        if let Some(mut iev) = dpyinfo.input_processor.receive_char(key_code, frame)
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state,
+                            scancode,
+                            virtual_keycode,
+                            ..
+                        },
+                    ..
+                } => match state {
+                    ElementState::Pressed => {
+                        if let Some(iev) =
+                            dpyinfo
+                                .input_processor
+                                .key_pressed(virtual_keycode, scancode, frame)
                         {
-                            unsafe { kbd_buffer_store_event_hold(&mut iev, hold_quit) };
-                            count += 1;
+                            store(iev);
                         }
                     }
+                    ElementState::Released => dpyinfo.input_processor.key_released(),
+                },
+
+                #[cfg(feature = "wr-mouse")]
+                WindowEvent::MouseInput {
+                    device_id,
+                    state,
+                    button,
+                    ..
+                } => {
+                    if let Some(mut iev) =
+                        dpyinfo.input_processor.mouse_pressed(button, state, frame)
+                    {
+                        set_event_device(&mut iev, device_id);
+                        store(iev);
+                    }
+                }
 
-                    WindowEvent::ModifiersChanged(state) => {
-                        dpyinfo.input_processor.change_modifiers(state);
+                #[cfg(feature = "wr-mouse")]
+                WindowEvent::MouseWheel {
+                    device_id,
+                    delta,
+                    phase,
+                    ..
+                } => {
+                    if let Some(mut iev) = dpyinfo
+                        .input_processor
+                        .mouse_wheel_scrolled(delta, phase, frame)
+                    {
+                        set_event_device(&mut iev, device_id);
+                        store(iev);
                     }
 
-                    WindowEvent::KeyboardInput {
-                        input:
-                            KeyboardInput {
-                                state,
-                                virtual_keycode: Some(key_code),
-                                ..
-                            },
-                        ..
-                    } => match state {
-                        ElementState::Pressed => {
-                            if let Some(mut iev) =
-                                dpyinfo.input_processor.key_pressed(key_code, frame)
-                            {
-                                unsafe { kbd_buffer_store_event_hold(&mut iev, hold_quit) };
-                                count += 1;
-                            }
-                        }
-                        ElementState::Released => dpyinfo.input_processor.key_released(),
-                    },
+                    let mut frame: LispFrameRef = frame.into();
+                    frame.set_mouse_moved(false);
+                }
 
-                    WindowEvent::MouseInput { state, button, .. } => {
-                        if let Some(mut iev) =
-                            dpyinfo.input_processor.mouse_pressed(button, state, frame)
-                        {
-                            unsafe { kbd_buffer_store_event_hold(&mut iev, hold_quit) };
-                            count += 1;
-                        }
-                    }
+                #[cfg(feature = "wr-mouse")]
+                WindowEvent::CursorMoved { position, .. } => {
+                    let mut frame: LispFrameRef = frame.into();
 
-                    WindowEvent::MouseWheel { delta, phase, .. } => {
-                        if let Some(mut iev) = dpyinfo
-                            .input_processor
-                            .mouse_wheel_scrolled(delta, phase, frame)
-                        {
-                            unsafe { kbd_buffer_store_event_hold(&mut iev, hold_quit) };
-                            count += 1;
-                        }
+                    unsafe {
+                        note_mouse_highlight(frame.as_mut(), position.x as i32, position.y as i32)
+                    };
 
-                        let mut frame: LispFrameRef = frame.into();
-                        frame.set_mouse_moved(false);
-                    }
+                    dpyinfo.input_processor.cursor_move(position);
 
-                    WindowEvent::CursorMoved { position, .. } => {
-                        let mut frame: LispFrameRef = frame.into();
+                    frame.set_mouse_moved(true);
+                }
 
-                        unsafe {
-                            note_mouse_highlight(
-                                frame.as_mut(),
-                                position.x as i32,
-                                position.y as i32,
-                            )
-                        };
+                // Buffered only while devices are tracked.  The pointer
+                // moves by the `CursorMoved` that comes along with it.
+                WindowEvent::AxisMotion { device_id, .. } => {
+                    devices::note_device(device_id);
+                }
 
-                        dpyinfo.input_processor.cursor_move(position);
+                #[cfg(feature = "wr-mouse")]
+                WindowEvent::CursorEntered { .. } => {
+                    let mut frame: LispFrameRef = frame.into();
+                    frame.set_mouse_moved(true);
+                }
 
-                        frame.set_mouse_moved(true);
+                #[cfg(feature = "wr-mouse")]
+                WindowEvent::CursorLeft { .. } => {
+                    let mut frame: LispFrameRef = frame.into();
+
+                    // Clear any mouse face left under the pointer.
+                    unsafe { note_mouse_highlight(frame.as_mut(), -1, -1) };
+
+                    frame.set_mouse_moved(false);
+                }
+
+                WindowEvent::Focused(is_focused) => {
+                    if is_focused {
+                        output.clear_user_attention();
                     }
 
-                    WindowEvent::Focused(is_focused) => {
-                        let mut dpyinfo =
-                            DisplayInfoRef::new(unsafe { terminal.display_info.wr } as *mut _);
+                    let mut dpyinfo =
+                        DisplayInfoRef::new(unsafe { terminal.display_info.wr } as *mut _);
 
-                        let mut top_frame = frame.as_frame().unwrap();
+                    let mut top_frame = frame.as_frame().unwrap();
 
-                        let focus_frame = if !top_frame.focus_frame.eq(Qnil) {
-                            top_frame.focus_frame.as_frame().unwrap().as_mut()
-                        } else {
-                            top_frame.as_mut()
-                        };
+                    let focus_frame = if !top_frame.focus_frame.eq(Qnil) {
+                        top_frame.focus_frame.as_frame().unwrap().as_mut()
+                    } else {
+                        top_frame.as_mut()
+                    };
 
-                        dpyinfo.get_raw().highlight_frame = if is_focused {
-                            focus_frame
-                        } else {
-                            ptr::null_mut()
-                        };
+                    dpyinfo.get_raw().highlight_frame = if is_focused {
+                        focus_frame
+                    } else {
+                        ptr::null_mut()
+                    };
 
-                        let event_type = if is_focused {
-                            emacs::bindings::event_kind::FOCUS_IN_EVENT
-                        } else {
-                            emacs::bindings::event_kind::FOCUS_OUT_EVENT
-                        };
+                    let event_type = if is_focused {
+                        emacs::bindings::event_kind::FOCUS_IN_EVENT
+                    } else {
+                        emacs::bindings::event_kind::FOCUS_OUT_EVENT
+                    };
 
-                        let mut event = create_emacs_event(event_type, top_frame.into());
+                    let event = create_emacs_event(event_type, top_frame.into());
 
-                        unsafe { kbd_buffer_store_event_hold(&mut event, hold_quit) };
-                        count += 1;
-                    }
+                    store(event);
+                }
 
-                    WindowEvent::Resized(size) => {
-                        output.resize(&size);
+                WindowEvent::Resized(size) => {
+                    output.resize(&size);
 
-                        let frame: LispFrameRef = frame.into();
-                        frame.change_size(
-                            size.width as i32,
-                            size.height as i32 - frame.menu_bar_height,
-                            false,
-                            true,
-                            false,
-                        );
+                    let frame: LispFrameRef = frame.into();
+                    frame.change_size(
+                        size.width as i32,
+                        size.height as i32 - frame.menu_bar_height,
+                        false,
+                        true,
+                        false,
+                    );
 
-                        unsafe { do_pending_window_change(false) };
-                    }
+                    unsafe { do_pending_window_change(false) };
+                }
 
-                    WindowEvent::CloseRequested => {
-                        let mut event = create_emacs_event(
-                            emacs::bindings::event_kind::DELETE_WINDOW_EVENT,
-                            frame,
-                        );
+                // The theme itself is kept by the event loop.
+                WindowEvent::ThemeChanged(_) => queue_theme_change_hook(frame),
 
-                        unsafe { kbd_buffer_store_event_hold(&mut event, hold_quit) };
-                        count += 1;
-                    }
+                WindowEvent::CloseRequested => {
+                    let event =
+                        create_emacs_event(emacs::bindings::event_kind::DELETE_WINDOW_EVENT, frame);
 
-                    _ => {}
+                    store(event);
                 }
-            }
-            _ => {}
-        };
-    }
 
-    events.clear();
+                _ => {}
+            }
+        }
+        // Windows uncovered or exposed since the last redraw, each
+        // once.
+        Event::RedrawRequested(window_id) => {
+            if let Some(output) = dpyinfo.outputs.get(&window_id) {
+                unsafe { Fredraw_frame(output.get_frame().into()) };
+            }
+        }
+        // Emacs has no event for raw motion; buffering it is enough
+        // for the device to be listed by `wr-input-devices`.
+        Event::DeviceEvent { device_id, .. } => {
+            devices::note_device(device_id);
+        }
+        // Nothing is drawn while suspended, see `flush_frame`.
+        Event::Suspended => {}
+        // The surfaces of the windows may be gone after a suspend, and
+        // are made again along with their GL contexts.
+        Event::Resumed => {
+            let frames: Vec<LispFrameRef> = dpyinfo
+                .outputs
+                .values()
+                .map(|output| output.get_frame())
+                .collect();
+
+            for frame in frames {
+                reset_context(frame);
+            }
+        }
+        Event::UserEvent(CLIPBOARD_READ_EVENT) => queue_clipboard_read_callbacks(),
+        Event::UserEvent(CLIPBOARD_CHANGED_EVENT) => queue_clipboard_change_hook(),
+        _ => {}
+    };
+}
 
-    count
+// Let `last-event-device` tell which device produced IEV while input
+// devices are tracked.
+#[cfg(feature = "wr-mouse")]
+fn set_event_device(iev: &mut input_event, device_id: winit::event::DeviceId) {
+    if devices::is_tracking() {
+        iev.device = devices::note_device(device_id).as_str().into();
+    }
 }
 
 extern "C" fn fullscreen(f: *mut Lisp_Frame) {
@@ -773,11 +925,9 @@ extern "C" fn make_frame_visible_invisible(f: *mut Lisp_Frame, visible: bool) {
 }
 
 extern "C" fn iconify_frame(f: *mut Lisp_Frame) {
-    let mut frame: LispFrameRef = f.into();
-
-    frame.set_iconified(true);
+    let frame: LispFrameRef = f.into();
 
-    frame.wr_output().hide_window()
+    frame.set_minimized(true);
 }
 
 extern "C" fn mouse_position(
@@ -834,6 +984,22 @@ extern "C" fn free_pixmap(f: *mut Lisp_Frame, pixmap: Emacs_Pixmap) {
     frame.wr_output().delete_image(image_key);
 }
 
+// winit has no audible bell, so without `visible-bell' the window asks
+// for the user's attention instead, which only shows while it doesn't
+// have focus.
+extern "C" fn ring_bell(f: *mut Lisp_Frame) {
+    let frame: LispFrameRef = f.into();
+    let mut output = frame.wr_output();
+
+    if unsafe { globals.visible_bell } {
+        if !frame.is_occluded() {
+            output.flash(visual_bell_duration());
+        }
+    } else {
+        output.request_user_attention(UserAttentionType::Informational);
+    }
+}
+
 // cleanup frame resource after frame is deleted
 extern "C" fn destroy_frame(f: *mut Lisp_Frame) {
     let mut frame: LispFrameRef = f.into();
@@ -843,12 +1009,43 @@ extern "C" fn destroy_frame(f: *mut Lisp_Frame) {
     let window_id = output.get_window().id();
 
     display_info.get_inner().outputs.remove(&window_id);
+    EVENT_LOOP.lock().unregister_window(&window_id);
+
+    // As X does once the last frame of a display is gone.
+    if display_info.get_inner().outputs.is_empty() {
+        save_clipboard_to_manager();
+    }
 
     // Take back output ownership and destroy it
     let _ = unsafe { Box::from_raw(output.as_rust_ptr()).deinit() };
     frame.output_data.wr = ptr::null_mut();
 }
 
+// Have the clipboard manager take a copy of the clipboard Emacs owns,
+// unless `x-select-enable-clipboard-manager' is nil, so what was killed
+// last can still be yanked once Emacs is gone.
+fn save_clipboard_to_manager() {
+    if unsafe { globals.Vx_select_enable_clipboard_manager }.is_nil() {
+        return;
+    }
+
+    EVENT_LOOP.lock().save_clipboard_to_manager();
+}
+
+// Set once a webrender terminal is opened.  Before that Emacs can't own
+// the clipboard, and asking the event loop would start it.
+static TERM_INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Hand the clipboard to the clipboard manager before Emacs exits, as
+/// `x_clipboard_manager_save_all` does for X.  `kill-emacs` calls this
+/// once `kill-emacs-hook` has run.
+#[no_mangle]
+pub extern "C" fn wr_clipboard_manager_save_all() {
+    if TERM_INITIALIZED.load(Ordering::Relaxed) {
+        save_clipboard_to_manager();
+    }
+}
+
 fn wr_create_terminal(mut dpyinfo: DisplayInfoRef) -> TerminalRef {
     let terminal_ptr = unsafe {
         create_terminal(
@@ -881,6 +1078,7 @@ fn wr_create_terminal(mut dpyinfo: DisplayInfoRef) -> TerminalRef {
     terminal.update_end_hook = Some(update_end);
     terminal.free_pixmap = Some(free_pixmap);
     terminal.delete_frame_hook = Some(destroy_frame);
+    terminal.ring_bell_hook = Some(ring_bell);
 
     terminal
 }
@@ -888,6 +1086,11 @@ fn wr_create_terminal(mut dpyinfo: DisplayInfoRef) -> TerminalRef {
 pub fn wr_term_init(display_name: LispObject) -> DisplayInfoRef {
     log::info!("Emacs Webrender term init");
 
+    init_select_trace();
+    init_wakeup_signal();
+
+    TERM_INITIALIZED.store(true, Ordering::Relaxed);
+
     let dpyinfo = Box::new(DisplayInfo::new());
     let mut dpyinfo_ref = DisplayInfoRef::new(Box::into_raw(dpyinfo));
 
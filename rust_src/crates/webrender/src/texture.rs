@@ -3,6 +3,8 @@ use std::{cell::RefCell, collections::HashMap, rc::Rc};
 use gleam::gl::{self, Gl};
 use webrender::{self, api::units::*, api::*, RenderApi, Transaction};
 
+use crate::external_image::{latest_frame, source_id};
+
 type TextureTable = HashMap<gl::GLuint, (FramebufferIntSize, bool)>;
 
 pub struct TextureResourceManager {
@@ -170,21 +172,38 @@ struct ExternalHandler {
 
 impl ExternalImageHandler for ExternalHandler {
     fn lock(&mut self, key: ExternalImageId, _channel_index: u8) -> ExternalImage {
+        if let Some(id) = source_id(key) {
+            return match latest_frame(id) {
+                Some(frame) => ExternalImage {
+                    uv: texel_rect(frame.size.width, frame.size.height, frame.flip),
+                    source: ExternalImageSource::NativeTexture(frame.texture),
+                },
+                // The source was unregistered since the frame was built.
+                None => ExternalImage {
+                    uv: TexelRect::new(0.0, 0.0, 0.0, 0.0),
+                    source: ExternalImageSource::Invalid,
+                },
+            };
+        }
+
         let texture_id = key.0;
 
         let textures = self.texture.borrow();
         let (size, need_filp) = textures.get(&(texture_id as u32)).unwrap();
 
-        let uv = if *need_filp {
-            TexelRect::new(0.0, size.height as f32, size.width as f32, 0.0)
-        } else {
-            TexelRect::new(0.0, 0.0, size.width as f32, size.height as f32)
-        };
-
         ExternalImage {
-            uv,
+            uv: texel_rect(size.width, size.height, *need_filp),
             source: ExternalImageSource::NativeTexture(texture_id as u32),
         }
     }
     fn unlock(&mut self, _key: ExternalImageId, _channel_index: u8) {}
 }
+
+// The whole of a WIDTH by HEIGHT texture, upside down if FLIP.
+fn texel_rect(width: i32, height: i32, flip: bool) -> TexelRect {
+    if flip {
+        TexelRect::new(0.0, height as f32, width as f32, 0.0)
+    } else {
+        TexelRect::new(0.0, 0.0, width as f32, height as f32)
+    }
+}
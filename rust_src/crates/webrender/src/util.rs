@@ -1,3 +1,8 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex, MutexGuard,
+};
+
 use webrender::api::units::*;
 
 pub trait HandyDandyRectBuilder {
@@ -21,3 +26,31 @@ impl HandyDandyRectBuilder for (i32, i32) {
         )
     }
 }
+
+/// A mutex that hands out its data even after a panic poisoned it,
+/// instead of failing every later lock.  Only use it for data that is
+/// still consistent when a panic interrupts its owner mid-update.
+pub struct RecoverableMutex<T> {
+    name: &'static str,
+    mutex: Mutex<T>,
+    recovered: AtomicBool,
+}
+
+impl<T> RecoverableMutex<T> {
+    pub fn new(name: &'static str, value: T) -> Self {
+        RecoverableMutex {
+            name,
+            mutex: Mutex::new(value),
+            recovered: AtomicBool::new(false),
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.mutex.lock().unwrap_or_else(|poisoned| {
+            if !self.recovered.swap(true, Ordering::Relaxed) {
+                log::warn!("Recovered {} after a panic while it was locked", self.name);
+            }
+            poisoned.into_inner()
+        })
+    }
+}
@@ -0,0 +1,99 @@
+use winit::event::WindowEvent;
+
+// Each kind of window event that can be kept from waking Emacs, by the
+// name Lisp knows it by.  Keys and requests to close a window always
+// wake Emacs, so it can't be locked out of a frame.
+const KINDS: [&str; 7] = [
+    "resize",
+    "focus",
+    "occlusion",
+    "mouse-button",
+    "mouse-motion",
+    "mouse-crossing",
+    "mouse-wheel",
+];
+
+/// The kinds of window events that are buffered for Emacs, waking it
+/// up.  Events of other kinds are dropped as they arrive.  By default,
+/// every kind wakes Emacs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WakeupEvents(u32);
+
+impl Default for WakeupEvents {
+    fn default() -> Self {
+        WakeupEvents((1 << KINDS.len()) - 1)
+    }
+}
+
+impl WakeupEvents {
+    /// Only the events that can't be turned off.
+    pub fn none() -> Self {
+        WakeupEvents(0)
+    }
+
+    /// Let events of the kind NAME wake Emacs, returning false if there
+    /// is no such kind.
+    pub fn enable(&mut self, name: &str) -> bool {
+        match KINDS.iter().position(|kind| *kind == name) {
+            Some(index) => {
+                self.0 |= 1 << index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The names of the kinds of events that wake Emacs.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        KINDS
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.0 & (1 << index) != 0)
+            .map(|(_, kind)| *kind)
+    }
+
+    /// Whether EVENT should be buffered for Emacs.
+    pub fn wakes(&self, event: &WindowEvent) -> bool {
+        match kind(event) {
+            Some(kind) => self.names().any(|name| name == kind),
+            None => true,
+        }
+    }
+}
+
+// The kind of EVENT, or None if it can't be turned off.
+fn kind(event: &WindowEvent) -> Option<&'static str> {
+    let kind = match event {
+        WindowEvent::Resized(_) => "resize",
+        WindowEvent::Focused(_) => "focus",
+        WindowEvent::Occluded(_) => "occlusion",
+        WindowEvent::MouseInput { .. } => "mouse-button",
+        WindowEvent::CursorMoved { .. } | WindowEvent::AxisMotion { .. } => "mouse-motion",
+        WindowEvent::CursorEntered { .. } | WindowEvent::CursorLeft { .. } => "mouse-crossing",
+        WindowEvent::MouseWheel { .. } => "mouse-wheel",
+        _ => return None,
+    };
+
+    Some(kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::dpi::PhysicalSize;
+
+    #[test]
+    fn keys_and_close_always_wake() {
+        let mut events = WakeupEvents::none();
+        assert!(events.enable("focus"));
+        assert!(!events.enable("keyboard"));
+
+        assert!(events.wakes(&WindowEvent::Focused(true)));
+        assert!(!events.wakes(&WindowEvent::Resized(PhysicalSize::new(1, 1))));
+        assert!(events.wakes(&WindowEvent::CloseRequested));
+        assert!(events.wakes(&WindowEvent::ReceivedCharacter('a')));
+        assert_eq!(events.names().collect::<Vec<_>>(), vec!["focus"]);
+
+        assert_eq!(WakeupEvents::default().names().count(), KINDS.len());
+    }
+}
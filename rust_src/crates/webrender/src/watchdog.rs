@@ -0,0 +1,97 @@
+//! A watchdog for `run_return`, which should return by the deadline
+//! `wr_select1` gives it.  If a platform bug or a wedged compositor
+//! keeps it from returning, Emacs would stop reading input for good, so
+//! once the deadline is well past the watchdog sends `WATCHDOG_EVENT`
+//! through the event loop, which makes `run_return` exit.
+//!
+//! The watchdog is off by default, and only guards platforms where the
+//! event loop runs on Emacs's thread.
+
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::Instant,
+};
+
+/// User event sent through the event loop when `run_return` hasn't
+/// returned long after its deadline.
+pub const WATCHDOG_EVENT: i32 = 3;
+
+#[derive(Default)]
+struct State {
+    // When to fire, while armed.
+    deadline: Option<Instant>,
+}
+
+/// A thread that calls its callback when it has been armed past a
+/// deadline without being disarmed.
+pub struct Watchdog {
+    shared: Arc<(Mutex<State>, Condvar)>,
+}
+
+impl Watchdog {
+    /// Start the watchdog, disarmed.  FIRE is called on the watchdog's
+    /// thread, each time it fires.
+    pub fn spawn(fire: impl Fn() + Send + 'static) -> Self {
+        let shared = Arc::new((Mutex::new(State::default()), Condvar::new()));
+        let thread_shared = shared.clone();
+
+        thread::Builder::new()
+            .name("webrender-watchdog".to_owned())
+            .spawn(move || {
+                let (state, changed) = &*thread_shared;
+                let mut state = state.lock().unwrap();
+
+                loop {
+                    state = match state.deadline {
+                        None => changed.wait(state).unwrap(),
+                        Some(deadline) => {
+                            let now = Instant::now();
+                            if now < deadline {
+                                changed.wait_timeout(state, deadline - now).unwrap().0
+                            } else {
+                                state.deadline = None;
+                                fire();
+                                state
+                            }
+                        }
+                    };
+                }
+            })
+            .expect("Failed to spawn webrender watchdog thread");
+
+        Watchdog { shared }
+    }
+
+    /// Fire at DEADLINE unless disarmed before.
+    pub fn arm(&self, deadline: Instant) {
+        let (state, changed) = &*self.shared;
+        state.lock().unwrap().deadline = Some(deadline);
+        changed.notify_one();
+    }
+
+    pub fn disarm(&self) {
+        let (state, changed) = &*self.shared;
+        state.lock().unwrap().deadline = None;
+        changed.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::mpsc, time::Duration};
+
+    #[test]
+    fn fires_unless_disarmed() {
+        let (fired, fires) = mpsc::channel();
+        let watchdog = Watchdog::spawn(move || fired.send(()).unwrap());
+
+        watchdog.arm(Instant::now() + Duration::from_millis(50));
+        watchdog.disarm();
+        assert!(fires.recv_timeout(Duration::from_millis(100)).is_err());
+
+        watchdog.arm(Instant::now());
+        assert!(fires.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+}
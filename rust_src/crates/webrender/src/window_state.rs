@@ -0,0 +1,307 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use winit::{
+    dpi::{LogicalPosition, PhysicalPosition, PhysicalSize},
+    window::{Theme, WindowId},
+};
+
+/// How long scale factor changes must stop before the last one is
+/// applied.  Dragging a window across monitors can produce a storm of
+/// them, and each applied change resizes the surface.
+pub const SCALE_FACTOR_DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// A scale factor change waiting for `SCALE_FACTOR_DEBOUNCE` to pass.
+#[derive(Clone, Copy, Debug)]
+pub struct PendingScale {
+    pub scale_factor: f64,
+    pub size: PhysicalSize<u32>,
+    pub changed_at: Instant,
+}
+
+/// Window state that winit can't reliably read back from a `Window`.
+/// It is kept up to date from winit events and from our own API calls.
+#[derive(Clone, Debug)]
+pub struct WindowState {
+    pub minimized: bool,
+    pub maximized: bool,
+    pub focused: bool,
+    // Only set from an explicit `Occluded(true)`; never inferred.
+    pub occluded: bool,
+    pub scale_factor: f64,
+    pub pending_scale: Option<PendingScale>,
+    // Set when the window is exposed or uncovered, until Emacs is told
+    // to redraw it.
+    pub needs_redraw: bool,
+    // The last theme reported for the window, None until one is.
+    pub theme: Option<Theme>,
+    // Where the pointer last moved to in the window, None while it is
+    // outside.
+    pub pointer: Option<PhysicalPosition<f64>>,
+}
+
+impl WindowState {
+    pub fn new(scale_factor: f64) -> Self {
+        WindowState {
+            minimized: false,
+            maximized: false,
+            focused: false,
+            occluded: false,
+            scale_factor,
+            pending_scale: None,
+            needs_redraw: false,
+            theme: None,
+            pointer: None,
+        }
+    }
+}
+
+/// Registry of the state of every live webrender window.
+#[derive(Default)]
+pub struct WindowRegistry {
+    windows: HashMap<WindowId, WindowState>,
+    pointer_window: Option<WindowId>,
+}
+
+impl WindowRegistry {
+    pub fn register(&mut self, window_id: WindowId, scale_factor: f64) {
+        self.windows
+            .insert(window_id, WindowState::new(scale_factor));
+    }
+
+    pub fn unregister(&mut self, window_id: &WindowId) {
+        self.windows.remove(window_id);
+        self.pointer_left(*window_id);
+    }
+
+    pub fn get(&self, window_id: &WindowId) -> Option<&WindowState> {
+        self.windows.get(window_id)
+    }
+
+    pub fn get_mut(&mut self, window_id: &WindowId) -> Option<&mut WindowState> {
+        self.windows.get_mut(window_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&WindowId, &WindowState)> {
+        self.windows.iter()
+    }
+
+    /// Whether there are windows and every one of them is minimized or
+    /// occluded, so nothing needs to be redrawn.
+    pub fn all_hidden(&self) -> bool {
+        !self.windows.is_empty()
+            && self
+                .windows
+                .values()
+                .all(|state| state.minimized || state.occluded)
+    }
+
+    pub fn request_redraw(&mut self, window_id: WindowId) {
+        if let Some(state) = self.windows.get_mut(&window_id) {
+            state.needs_redraw = true;
+        }
+    }
+
+    /// Take the windows that need to be redrawn, each once however many
+    /// times it asked.  Requests of windows that are minimized or
+    /// occluded are dropped: they are redrawn once they are uncovered.
+    pub fn take_redraws(&mut self) -> Vec<WindowId> {
+        let mut redraws = Vec::new();
+
+        for (window_id, state) in self.windows.iter_mut() {
+            if state.needs_redraw && !state.minimized && !state.occluded {
+                redraws.push(*window_id);
+            }
+            state.needs_redraw = false;
+        }
+
+        redraws
+    }
+
+    /// The window the pointer is over, if any.
+    pub fn pointer_window(&self) -> Option<WindowId> {
+        self.pointer_window
+    }
+
+    pub fn pointer_entered(&mut self, window_id: WindowId) {
+        self.pointer_window = Some(window_id);
+    }
+
+    /// Record that the pointer left WINDOW_ID.  When moving between
+    /// windows, the `CursorLeft` of the old window may arrive after the
+    /// `CursorEntered` of the new one, so only the window the pointer
+    /// last entered is cleared.
+    pub fn pointer_left(&mut self, window_id: WindowId) {
+        if self.pointer_window == Some(window_id) {
+            self.pointer_window = None;
+        }
+    }
+
+    /// Where the pointer is in WINDOW_ID, in logical pixels, or None if
+    /// it isn't over the window.
+    pub fn pointer_position(&self, window_id: &WindowId) -> Option<LogicalPosition<f64>> {
+        let pointer = self.windows.get(window_id)?.pointer?;
+        Some(self.physical_to_logical(window_id, pointer))
+    }
+
+    /// Scale factor of WINDOW_ID as last reported by `ScaleFactorChanged`.
+    /// Unknown windows are treated as having a scale factor of 1.0.
+    pub fn scale_factor(&self, window_id: &WindowId) -> f64 {
+        self.windows
+            .get(window_id)
+            .map_or(1.0, |state| state.scale_factor)
+    }
+
+    /// Record that WINDOW_ID changed to SCALE_FACTOR with SIZE as its new
+    /// inner size.  The change replaces any pending one for the window.
+    pub fn scale_factor_changed(
+        &mut self,
+        window_id: WindowId,
+        scale_factor: f64,
+        size: PhysicalSize<u32>,
+        now: Instant,
+    ) {
+        if let Some(state) = self.windows.get_mut(&window_id) {
+            state.pending_scale = Some(PendingScale {
+                scale_factor,
+                size,
+                changed_at: now,
+            });
+        }
+    }
+
+    /// When the next pending scale factor change settles, if any.
+    pub fn next_scale_deadline(&self) -> Option<Instant> {
+        self.windows
+            .values()
+            .filter_map(|state| state.pending_scale)
+            .map(|pending| pending.changed_at + SCALE_FACTOR_DEBOUNCE)
+            .min()
+    }
+
+    /// Apply the scale factor changes that have settled at NOW, returning
+    /// each window with the inner size it should be resized to.
+    pub fn take_settled_scales(&mut self, now: Instant) -> Vec<(WindowId, PhysicalSize<u32>)> {
+        let mut settled = Vec::new();
+
+        for (window_id, state) in self.windows.iter_mut() {
+            if let Some(pending) = state.pending_scale {
+                if pending.changed_at + SCALE_FACTOR_DEBOUNCE <= now {
+                    state.scale_factor = pending.scale_factor;
+                    state.pending_scale = None;
+                    settled.push((*window_id, pending.size));
+                }
+            }
+        }
+
+        settled
+    }
+
+    pub fn logical_to_physical(
+        &self,
+        window_id: &WindowId,
+        position: LogicalPosition<f64>,
+    ) -> PhysicalPosition<f64> {
+        position.to_physical(self.scale_factor(window_id))
+    }
+
+    pub fn physical_to_logical(
+        &self,
+        window_id: &WindowId,
+        position: PhysicalPosition<f64>,
+    ) -> LogicalPosition<f64> {
+        position.to_logical(self.scale_factor(window_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_with_scale_factor() {
+        let window_id = unsafe { WindowId::dummy() };
+        let mut registry = WindowRegistry::default();
+        registry.register(window_id, 2.0);
+
+        let physical = PhysicalPosition::new(300.0, 150.0);
+        let logical = registry.physical_to_logical(&window_id, physical);
+        assert_eq!(logical, LogicalPosition::new(150.0, 75.0));
+        assert_eq!(registry.logical_to_physical(&window_id, logical), physical);
+    }
+
+    #[test]
+    fn pointer_position_is_logical() {
+        let window_id = unsafe { WindowId::dummy() };
+        let mut registry = WindowRegistry::default();
+        registry.register(window_id, 2.0);
+        assert_eq!(registry.pointer_position(&window_id), None);
+
+        registry.get_mut(&window_id).unwrap().pointer = Some(PhysicalPosition::new(40.0, 10.0));
+        assert_eq!(
+            registry.pointer_position(&window_id),
+            Some(LogicalPosition::new(20.0, 5.0))
+        );
+    }
+
+    #[test]
+    fn hidden_until_a_window_is_visible() {
+        let window_id = unsafe { WindowId::dummy() };
+        let mut registry = WindowRegistry::default();
+        assert!(!registry.all_hidden());
+
+        registry.register(window_id, 1.0);
+        assert!(!registry.all_hidden());
+
+        registry.get_mut(&window_id).unwrap().occluded = true;
+        assert!(registry.all_hidden());
+
+        registry.get_mut(&window_id).unwrap().occluded = false;
+        assert!(!registry.all_hidden());
+    }
+
+    #[test]
+    fn redraws_are_coalesced() {
+        let window_id = unsafe { WindowId::dummy() };
+        let mut registry = WindowRegistry::default();
+        registry.register(window_id, 1.0);
+        assert!(registry.take_redraws().is_empty());
+
+        registry.request_redraw(window_id);
+        registry.request_redraw(window_id);
+        assert_eq!(registry.take_redraws(), vec![window_id]);
+        assert!(registry.take_redraws().is_empty());
+
+        registry.request_redraw(window_id);
+        registry.get_mut(&window_id).unwrap().occluded = true;
+        assert!(registry.take_redraws().is_empty());
+    }
+
+    #[test]
+    fn scale_factor_changes_are_debounced() {
+        let window_id = unsafe { WindowId::dummy() };
+        let mut registry = WindowRegistry::default();
+        registry.register(window_id, 1.0);
+
+        let start = Instant::now();
+        let step = SCALE_FACTOR_DEBOUNCE / 2;
+        registry.scale_factor_changed(window_id, 2.0, PhysicalSize::new(200, 100), start);
+        registry.scale_factor_changed(window_id, 1.5, PhysicalSize::new(150, 75), start + step);
+
+        // The first change would have settled, but the second restarted
+        // the wait.
+        let now = start + SCALE_FACTOR_DEBOUNCE;
+        assert!(registry.take_settled_scales(now).is_empty());
+        assert_eq!(registry.scale_factor(&window_id), 1.0);
+
+        let now = start + step + SCALE_FACTOR_DEBOUNCE;
+        assert_eq!(
+            registry.take_settled_scales(now),
+            vec![(window_id, PhysicalSize::new(150, 75))]
+        );
+        assert_eq!(registry.scale_factor(&window_id), 1.5);
+        assert_eq!(registry.next_scale_deadline(), None);
+    }
+}
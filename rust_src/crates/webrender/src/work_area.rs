@@ -0,0 +1,213 @@
+//! The work area of a monitor: the part of it not covered by panels,
+//! docks and taskbars.
+//!
+//! winit only knows the full bounds of a monitor, so the work area is
+//! asked of the window system where it can be: `_NET_WORKAREA` of the
+//! window manager on X11, the monitor info on Windows and the visible
+//! frame of the screen on macOS.  Wayland has no such notion, and there,
+//! as well as when the window system can't be asked, the work area is
+//! the whole monitor.
+
+#[cfg(all(unix, not(target_os = "macos")))]
+use once_cell::sync::Lazy;
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    monitor::MonitorHandle,
+};
+#[cfg(all(unix, not(target_os = "macos")))]
+use xcb::{x, Xid};
+
+use crate::event_loop::Platform;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkArea {
+    pub position: PhysicalPosition<i32>,
+    pub size: PhysicalSize<u32>,
+}
+
+impl WorkArea {
+    /// The whole of MONITOR.
+    pub fn bounds(monitor: &MonitorHandle) -> Self {
+        WorkArea {
+            position: monitor.position(),
+            size: monitor.size(),
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl WorkArea {
+    // The part of self that is also in OTHER, or None if they don't
+    // overlap.
+    fn intersection(&self, other: &WorkArea) -> Option<WorkArea> {
+        let left = self.position.x.max(other.position.x);
+        let top = self.position.y.max(other.position.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+
+        if right <= left || bottom <= top {
+            return None;
+        }
+
+        Some(WorkArea {
+            position: PhysicalPosition::new(left, top),
+            size: PhysicalSize::new((right - left) as u32, (bottom - top) as u32),
+        })
+    }
+
+    fn right(&self) -> i32 {
+        self.position.x + self.size.width as i32
+    }
+
+    fn bottom(&self) -> i32 {
+        self.position.y + self.size.height as i32
+    }
+}
+
+/// The work area of MONITOR on _PLATFORM, in physical pixels.
+pub fn work_area(monitor: &MonitorHandle, _platform: Platform) -> WorkArea {
+    let bounds = WorkArea::bounds(monitor);
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let work_area = match _platform {
+        // The work area of the window manager spans all monitors.
+        Platform::X11 => x11_work_area().and_then(|work_area| work_area.intersection(&bounds)),
+        _ => None,
+    };
+    #[cfg(target_os = "macos")]
+    let work_area = macos_work_area(monitor, &bounds);
+    #[cfg(windows)]
+    let work_area = windows_work_area(monitor);
+
+    work_area.unwrap_or(bounds)
+}
+
+// The connection work areas are asked on, opened the first time one is
+// asked for.  Monitors are listed with `EVENT_LOOP` held, and connecting
+// for each of them would hold it up.
+#[cfg(all(unix, not(target_os = "macos")))]
+static X11_CONNECTION: Lazy<Option<(xcb::Connection, i32)>> = Lazy::new(|| {
+    xcb::Connection::connect(None)
+        .map_err(|err| log::warn!("Failed to connect to X11 for the work area: {err:?}"))
+        .ok()
+});
+
+// `_NET_WORKAREA` of the current desktop, if the window manager sets it.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn x11_work_area() -> Option<WorkArea> {
+    let (connection, screen) = X11_CONNECTION.as_ref()?;
+    let root = connection.get_setup().roots().nth(*screen as usize)?.root();
+
+    let cardinals = |name: &[u8]| -> Option<Vec<u32>> {
+        let cookie = connection.send_request(&x::InternAtom {
+            only_if_exists: true,
+            name,
+        });
+        let atom = connection.wait_for_reply(cookie).ok()?.atom();
+        if atom.is_none() {
+            return None;
+        }
+
+        let cookie = connection.send_request(&x::GetProperty {
+            delete: false,
+            window: root,
+            property: atom,
+            r#type: x::ATOM_CARDINAL,
+            long_offset: 0,
+            long_length: 1024,
+        });
+        let reply = connection.wait_for_reply(cookie).ok()?;
+        Some(reply.value::<u32>().to_vec())
+    };
+
+    let desktop = cardinals(b"_NET_CURRENT_DESKTOP")
+        .and_then(|desktop| desktop.first().copied())
+        .unwrap_or(0) as usize;
+
+    // Four cardinals, x, y, width and height, for each desktop.
+    let work_areas = cardinals(b"_NET_WORKAREA")?;
+    match work_areas.chunks_exact(4).nth(desktop) {
+        Some([x, y, width, height]) => Some(WorkArea {
+            position: PhysicalPosition::new(*x as i32, *y as i32),
+            size: PhysicalSize::new(*width, *height),
+        }),
+        _ => None,
+    }
+}
+
+// The visible frame of MONITOR's screen, which leaves out the menu bar
+// and the Dock.  Cocoa measures screens in points from the bottom left
+// corner of the main screen, so the insets of the visible frame are
+// taken off BOUNDS instead of converting its origin.
+#[cfg(target_os = "macos")]
+fn macos_work_area(monitor: &MonitorHandle, bounds: &WorkArea) -> Option<WorkArea> {
+    use cocoa::{appkit::NSScreen, base::id};
+    use winit::platform::macos::MonitorHandleExtMacOS;
+
+    let screen = monitor.ns_screen()? as id;
+    let (frame, visible) = unsafe { (NSScreen::frame(screen), NSScreen::visibleFrame(screen)) };
+
+    let scale_factor = monitor.scale_factor();
+    let left = (visible.origin.x - frame.origin.x) * scale_factor;
+    let top = ((frame.origin.y + frame.size.height) - (visible.origin.y + visible.size.height))
+        * scale_factor;
+
+    Some(WorkArea {
+        position: PhysicalPosition::new(
+            bounds.position.x + left.round() as i32,
+            bounds.position.y + top.round() as i32,
+        ),
+        size: PhysicalSize::new(
+            (visible.size.width * scale_factor).round() as u32,
+            (visible.size.height * scale_factor).round() as u32,
+        ),
+    })
+}
+
+// The work rectangle Windows keeps for MONITOR, which leaves out the
+// taskbar and docked application bars.
+#[cfg(windows)]
+fn windows_work_area(monitor: &MonitorHandle) -> Option<WorkArea> {
+    use windows_sys::Win32::Graphics::Gdi::{GetMonitorInfoW, MONITORINFO};
+    use winit::platform::windows::MonitorHandleExtWindows;
+
+    let mut info: MONITORINFO = unsafe { std::mem::zeroed() };
+    info.cbSize = std::mem::size_of::<MONITORINFO>() as u32;
+
+    if unsafe { GetMonitorInfoW(monitor.hmonitor(), &mut info) } == 0 {
+        return None;
+    }
+
+    let work = info.rcWork;
+    Some(WorkArea {
+        position: PhysicalPosition::new(work.left, work.top),
+        size: PhysicalSize::new(
+            (work.right - work.left) as u32,
+            (work.bottom - work.top) as u32,
+        ),
+    })
+}
+
+#[cfg(all(test, unix, not(target_os = "macos")))]
+mod tests {
+    use super::*;
+
+    fn area(x: i32, y: i32, width: u32, height: u32) -> WorkArea {
+        WorkArea {
+            position: PhysicalPosition::new(x, y),
+            size: PhysicalSize::new(width, height),
+        }
+    }
+
+    #[test]
+    fn desktop_work_area_is_clipped_to_the_monitor() {
+        // A panel along the top of a desktop spanning two monitors.
+        let desktop = area(0, 32, 3840, 1048);
+
+        assert_eq!(
+            desktop.intersection(&area(1920, 0, 1920, 1080)),
+            Some(area(1920, 32, 1920, 1048))
+        );
+        assert_eq!(desktop.intersection(&area(3840, 0, 1920, 1080)), None);
+    }
+}
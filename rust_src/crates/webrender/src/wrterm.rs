@@ -3,21 +3,49 @@
 use emacs::multibyte::LispStringRef;
 use std::ffi::CString;
 use std::ptr;
+use std::time::{Duration, Instant};
 
 use emacs::bindings::output_method;
-use winit::{event::VirtualKeyCode, monitor::MonitorHandle};
+use winit::{
+    dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize},
+    event::{Event, ModifiersState, VirtualKeyCode, WindowEvent},
+    monitor::{MonitorHandle, VideoMode},
+    window::{CursorGrabMode, Fullscreen, Icon, Theme, UserAttentionType},
+};
 
+use image::{DynamicImage, ImageOutputFormat};
 use lisp_macros::lisp_fn;
+use webrender::api::{
+    units::{DeviceIntPoint, DeviceIntRect, DeviceIntSize, LayoutRect, WorldPoint},
+    ColorF, CommonItemProperties, DebugFlags, ItemTag, PrimitiveFlags,
+};
 
-use crate::event_loop::EVENT_LOOP;
+use crate::blur::{set_blur, DEFAULT_BLUR_RADIUS};
+use crate::clipboard::{next_read_id, take_finished_reads, TEXT_TARGET};
+use crate::custom_cursor::CustomCursor;
+use crate::debug_flags::{debug_flag, debug_flag_names};
+use crate::devices;
+use crate::event_loop::{
+    wait_for_events, wait_for_window_resize, window_system_inhibited, Platform, EVENT_BUFFER,
+    EVENT_LOOP, EVENT_LOOP_SUPPORTED,
+};
+use crate::external_image::unregister_external_image;
 use crate::frame::frame_edges;
 use crate::frame::LispFrameExt;
+use crate::modifiers::ModifierRemap;
+use crate::monitor_id::monitor_ids;
+use crate::overlay::Overlay;
+use crate::select_stats::SELECT_STATS;
+use crate::term::translate_input_event;
+use crate::util::HandyDandyRectBuilder;
+use crate::wakeup_events::WakeupEvents;
+use crate::work_area::{work_area, WorkArea};
 use crate::{
     color::lookup_color_by_name_or_hex,
     font::{FontRef, FONT_DRIVER},
     frame::create_frame,
     input::winit_keycode_emacs_key_name,
-    output::OutputRef,
+    output::{size_limits_fit, OutputRef, WindowOptions},
     term::wr_term_init,
 };
 
@@ -25,20 +53,24 @@ use emacs::{
     bindings::globals,
     bindings::resource_types::{RES_TYPE_NUMBER, RES_TYPE_STRING, RES_TYPE_SYMBOL},
     bindings::{
-        block_input, build_string, gui_display_get_arg, hashtest_eql, image as Emacs_Image, list3i,
-        make_fixnum, make_hash_table, make_monitor_attribute_list, register_font_driver,
-        unblock_input, Display, Emacs_Pixmap, Emacs_Rectangle, Fcons, Fcopy_alist, Fmake_vector,
-        Fprovide, MonitorInfo, Vframe_list, Window, CHECK_STRING, DEFAULT_REHASH_SIZE,
-        DEFAULT_REHASH_THRESHOLD,
+        block_input, build_string, gui_display_get_arg, hashtest_eql, image as Emacs_Image, intern,
+        kbd_buffer_store_event_hold, list3i, make_fixnum, make_float, make_hash_table,
+        make_lispy_event, make_monitor_attribute_list, make_unibyte_string, pending_funcalls,
+        register_font_driver, unblock_input, Display, Emacs_Pixmap, Emacs_Rectangle, Fassq, Fcons,
+        Fcopy_alist, Fdelete_frame, Fdelq, Fmake_vector, Fprovide, MonitorInfo, Vframe_list,
+        Window, CHECK_STRING, DEFAULT_REHASH_SIZE, DEFAULT_REHASH_THRESHOLD, FLOATP, XFLOAT_DATA,
     },
-    definitions::EmacsInt,
+    definitions::{EmacsInt, EmacsUint},
     frame::{all_frames, window_frame_live_or_selected, LispFrameRef},
     globals::{
-        Qbackground_color, Qfont, Qfont_backend, Qforeground_color, Qleft_fringe, Qminibuffer,
-        Qname, Qnil, Qparent_id, Qright_fringe, Qt, Qterminal, Qunbound, Qwr, Qx_create_frame_1,
+        Qbackground_color, Qfont, Qfont_backend, Qforeground_color, Qframep, Qicon, Qleft,
+        Qleft_fringe, Qminibuffer, Qname, Qnil, Qnumberp, Qparent_frame, Qparent_id, Qright_fringe,
+        Qt, Qterminal, Qtop, Qunbound, Qwr, Qwr_clipboard_change_hook, Qwr_display_reset_functions,
+        Qwr_max_size, Qwr_min_size, Qwr_monitor, Qwr_theme_change_functions, Qx_create_frame_1,
         Qx_create_frame_2,
     },
     lisp::{ExternalPtr, LispObject},
+    list::{LispConsCircularChecks, LispConsEndChecks},
 };
 
 pub use crate::display_info::{DisplayInfo, DisplayInfoRef};
@@ -230,6 +262,16 @@ pub fn x_hide_tip() -> LispObject {
 /// `default-minibuffer-frame' must be a frame whose minibuffer can be
 /// shared by the new frame.
 ///
+/// The `wr-monitor' parameter, an index into
+/// `display-monitor-attributes-list' or an identifier returned by
+/// `wr-monitor-id', opens the frame at the top left of that monitor's
+/// work area.  An index or identifier without a monitor stands for the
+/// primary monitor.
+///
+/// A frame with a `parent-frame' opens as an undecorated overlay that
+/// doesn't take the focus, at its `left' and `top' in pixels from the
+/// parent's inner edges.  It is deleted along with its parent.
+///
 /// This function is an internal primitive--use `make-frame' instead.
 #[lisp_fn]
 pub fn wr_create_frame(parms: LispObject) -> LispFrameRef {
@@ -314,7 +356,104 @@ pub fn wr_create_frame(parms: LispObject) -> LispFrameRef {
         )
     };
 
-    let mut frame = create_frame(display, dpyinfo, tem, kb.into());
+    let monitor = unsafe {
+        gui_display_get_arg(
+            dpyinfo.get_raw().as_mut(),
+            parms,
+            Qwr_monitor,
+            ptr::null(),
+            ptr::null(),
+            RES_TYPE_NUMBER,
+        )
+    };
+
+    let mut size_limit = |param| {
+        let size = unsafe {
+            gui_display_get_arg(
+                dpyinfo.get_raw().as_mut(),
+                parms,
+                param,
+                ptr::null(),
+                ptr::null(),
+                RES_TYPE_SYMBOL,
+            )
+        };
+        if size.eq(Qunbound) {
+            None
+        } else {
+            logical_size_from_lisp(size)
+        }
+    };
+    let min_size = size_limit(Qwr_min_size);
+    let max_size = size_limit(Qwr_max_size);
+    if !size_limits_fit(min_size, max_size) {
+        error!("`wr-min-size' is larger than `wr-max-size'");
+    }
+
+    let parent_frame = unsafe {
+        gui_display_get_arg(
+            dpyinfo.get_raw().as_mut(),
+            parms,
+            Qparent_frame,
+            ptr::null(),
+            ptr::null(),
+            RES_TYPE_SYMBOL,
+        )
+    };
+    let parent_frame = if parent_frame.eq(Qunbound) || parent_frame.is_nil() {
+        None
+    } else {
+        match live_wr_frame(parent_frame) {
+            Some(parent_frame) => Some(parent_frame),
+            None => error!("Invalid specification of `parent-frame'"),
+        }
+    };
+
+    // A child frame opens as an overlay over its parent, at its `left'
+    // and `top' in pixels from the parent's inner edges.
+    let overlay = match parent_frame {
+        Some(parent_frame) => {
+            let mut offset = |param| {
+                let value = unsafe {
+                    gui_display_get_arg(
+                        dpyinfo.get_raw().as_mut(),
+                        parms,
+                        param,
+                        ptr::null(),
+                        ptr::null(),
+                        RES_TYPE_NUMBER,
+                    )
+                };
+                value.as_fixnum().unwrap_or(0) as i32
+            };
+            let offset = PhysicalPosition::new(offset(Qleft), offset(Qtop));
+
+            Some(Overlay::over(parent_frame.wr_output().get_window(), offset))
+        }
+        None => None,
+    };
+
+    let options = WindowOptions {
+        overlay,
+        monitor: if monitor.eq(Qunbound) || monitor.is_nil() {
+            None
+        } else if let Some(id) = monitor.as_string() {
+            Some(monitor_by_id(&id.to_utf8()))
+        } else {
+            let index = monitor.as_natnum_or_error() as usize;
+            Some(EVENT_LOOP.lock().get_monitor(index))
+        },
+        min_size,
+        max_size,
+    };
+
+    let mut frame = create_frame(display, dpyinfo, tem, kb.into(), options);
+
+    // Deleting the parent deletes its child frames, and with them their
+    // overlays.
+    if let Some(parent_frame) = parent_frame {
+        frame.parent_frame = parent_frame.into();
+    }
 
     unsafe {
         register_font_driver(&FONT_DRIVER.0 as *const _, frame.as_mut());
@@ -406,13 +545,31 @@ pub fn wr_create_frame(parms: LispObject) -> LispFrameRef {
     /* Now consider the frame official.  */
     unsafe { Vframe_list = Fcons(frame.into(), Vframe_list) };
 
-    let mut dpyinfo = output.display_info();
+    // Overlays don't take the focus from their parents.
+    if parent_frame.is_none() {
+        let mut dpyinfo = output.display_info();
 
-    dpyinfo.get_raw().highlight_frame = frame.as_mut();
+        dpyinfo.get_raw().highlight_frame = frame.as_mut();
+    }
 
     frame
 }
 
+// The monitor with the identifier ID, as `wr-monitor-id' returns it, or
+// the primary monitor if there is none.
+fn monitor_by_id(id: &str) -> MonitorHandle {
+    let event_loop = EVENT_LOOP.lock();
+    let monitors: Vec<_> = event_loop.get_available_monitors().collect();
+
+    match monitor_ids(&monitors).iter().position(|other| other == id) {
+        Some(index) => monitors[index].clone(),
+        None => {
+            log::warn!("No monitor {id}, using the primary monitor");
+            event_loop.get_primary_monitor()
+        }
+    }
+}
+
 /// Open a connection to a display server.
 /// DISPLAY is the name of the display to connect to.
 /// Optional second arg XRM-STRING is a string of resources in xrdb format.
@@ -425,6 +582,7 @@ pub fn x_open_connection(
     _xrm_string: LispObject,
     _must_succeed: LispObject,
 ) -> LispObject {
+    check_window_system();
     let display = if display.is_nil() {
         unsafe { build_string("".as_ptr() as *const ::libc::c_char) }
     } else {
@@ -433,9 +591,9 @@ pub fn x_open_connection(
 
     unsafe { CHECK_STRING(display) };
 
-    let mut event_loop = EVENT_LOOP.lock().unwrap();
-    let _native_display = event_loop.open_native_display();
-
+    // The surfman connection is left for the first frame to open, so a
+    // display without a usable GPU still answers clipboard and monitor
+    // queries.
     let mut display_info = wr_term_init(display);
 
     // Put this display on the chain.
@@ -581,7 +739,10 @@ pub fn x_display_visual_class(_terminal: LispObject) -> LispObject {
     new_unibyte_string!("true-color")
 }
 
-pub fn webrender_monitor_to_emacs_monitor(m: MonitorHandle) -> (MonitorInfo, Option<CString>) {
+pub fn webrender_monitor_to_emacs_monitor(
+    m: MonitorHandle,
+    platform: Platform,
+) -> (MonitorInfo, Option<CString>) {
     let dpi_factor = m.scale_factor();
 
     let physical_pos = m.position();
@@ -597,6 +758,17 @@ pub fn webrender_monitor_to_emacs_monitor(m: MonitorHandle) -> (MonitorInfo, Opt
         height: logical_size.height,
     };
 
+    let work_area = work_area(&m, platform);
+    let work_pos = work_area.position.to_logical::<i32>(dpi_factor);
+    let work_size = work_area.size.to_logical::<u32>(dpi_factor);
+
+    let work = Emacs_Rectangle {
+        x: work_pos.x,
+        y: work_pos.y,
+        width: work_size.width,
+        height: work_size.height,
+    };
+
     let physical_size: (u32, u32) = physical_size.into();
 
     let name = m.name().and_then(|s| CString::new(s).ok());
@@ -608,7 +780,7 @@ pub fn webrender_monitor_to_emacs_monitor(m: MonitorHandle) -> (MonitorInfo, Opt
 
     let monitor_info = MonitorInfo {
         geom,
-        work: geom,
+        work,
         mm_width: physical_size.0 as i32,
         mm_height: physical_size.1 as i32,
         name: name_c_ptr as *mut i8,
@@ -634,7 +806,8 @@ pub fn webrender_monitor_to_emacs_monitor(m: MonitorHandle) -> (MonitorInfo, Opt
 /// Internal use only, use `display-monitor-attributes-list' instead.
 #[lisp_fn(min = "0")]
 pub fn x_display_monitor_attributes_list(_terminal: LispObject) -> LispObject {
-    let event_loop = EVENT_LOOP.lock().unwrap();
+    check_window_system();
+    let event_loop = EVENT_LOOP.lock();
 
     let monitors: Vec<_> = event_loop.get_available_monitors().collect();
     let primary_monitor = event_loop.get_primary_monitor();
@@ -650,7 +823,7 @@ pub fn x_display_monitor_attributes_list(_terminal: LispObject) -> LispObject {
 
     let emacs_monitor_infos: Vec<_> = monitors
         .iter()
-        .map(|m| webrender_monitor_to_emacs_monitor(m.clone()))
+        .map(|m| webrender_monitor_to_emacs_monitor(m.clone(), event_loop.platform()))
         .collect();
 
     let mut emacs_monitors: Vec<_> = emacs_monitor_infos.iter().map(|(m, _)| m.clone()).collect();
@@ -703,7 +876,8 @@ pub fn x_display_monitor_attributes_list(_terminal: LispObject) -> LispObject {
 /// each physical monitor, use `display-monitor-attributes-list'.
 #[lisp_fn(min = "0")]
 pub fn x_display_pixel_width(_terminal: LispObject) -> LispObject {
-    let event_loop = EVENT_LOOP.lock().unwrap();
+    check_window_system();
+    let event_loop = EVENT_LOOP.lock();
 
     let primary_monitor = event_loop.get_primary_monitor();
 
@@ -726,7 +900,8 @@ pub fn x_display_pixel_width(_terminal: LispObject) -> LispObject {
 /// each physical monitor, use `display-monitor-attributes-list'.
 #[lisp_fn(min = "0")]
 pub fn x_display_pixel_height(_terminal: LispObject) -> LispObject {
-    let event_loop = EVENT_LOOP.lock().unwrap();
+    check_window_system();
+    let event_loop = EVENT_LOOP.lock();
 
     let primary_monitor = event_loop.get_primary_monitor();
 
@@ -738,6 +913,20 @@ pub fn x_display_pixel_height(_terminal: LispObject) -> LispObject {
     unsafe { make_fixnum(logical_size.height as i64) }
 }
 
+// Name of SELECTION, where nil stands for `PRIMARY' and t for `SECONDARY'.
+fn selection_name(selection: LispObject) -> String {
+    if selection.is_nil() {
+        return "PRIMARY".to_owned();
+    }
+
+    if selection == Qt {
+        return "SECONDARY".to_owned();
+    }
+
+    let name: LispStringRef = selection.force_symbol().symbol_name().into();
+    name.to_utf8()
+}
+
 /// Assert an X selection of type SELECTION and value VALUE.
 /// SELECTION is a symbol, typically `PRIMARY', `SECONDARY', or `CLIPBOARD'.
 /// \(Those are literal upper-case symbol names, since that's what X expects.)
@@ -750,21 +939,33 @@ pub fn x_display_pixel_height(_terminal: LispObject) -> LispObject {
 /// On Nextstep, FRAME is unused.
 #[lisp_fn(min = "2")]
 pub fn x_own_selection_internal(
-    _selection: LispObject,
+    selection: LispObject,
     value: LispObject,
     _frame: LispObject,
 ) -> LispObject {
-    let mut event_loop = EVENT_LOOP.lock().unwrap();
-
-    let clipboard = event_loop.get_clipboard();
+    check_window_system();
+    let mut event_loop = EVENT_LOOP.lock();
 
     let content = value.force_string().to_utf8();
 
-    clipboard.set_contents(content).unwrap();
+    event_loop.own_selection(&selection_name(selection), content);
 
     value
 }
 
+/// Have the clipboard manager take a copy of the clipboard Emacs owns,
+/// so that it can still be pasted once Emacs has exited.  This is done
+/// on its own when Emacs exits or its last frame is deleted, unless
+/// `x-select-enable-clipboard-manager' is nil.
+/// Return t if the clipboard manager took a copy, nil if Emacs doesn't
+/// own the clipboard or no clipboard manager is running.  Only X has
+/// clipboard managers; elsewhere the clipboard outlives its owner anyway.
+#[lisp_fn]
+pub fn wr_clipboard_persist() -> bool {
+    check_window_system();
+    EVENT_LOOP.lock().save_clipboard_to_manager()
+}
+
 /// Return text selected from some X window.
 /// SELECTION-SYMBOL is typically `PRIMARY', `SECONDARY', or `CLIPBOARD'.
 /// \(Those are literal upper-case symbol names, since that's what X expects.)
@@ -785,7 +986,8 @@ pub fn x_get_selection_internal(
     _time_stamp: LispObject,
     _terminal: LispObject,
 ) -> LispObject {
-    let mut event_loop = EVENT_LOOP.lock().unwrap();
+    check_window_system();
+    let mut event_loop = EVENT_LOOP.lock();
 
     let clipboard = event_loop.get_clipboard();
 
@@ -798,6 +1000,190 @@ pub fn x_get_selection_internal(
     contents.into()
 }
 
+// Have CALLBACK called with CONTENTS the next time Emacs runs timers.
+fn queue_clipboard_callback(callback: LispObject, contents: Option<String>) {
+    let contents = match contents {
+        Some(contents) => contents.as_str().into(),
+        None => Qnil,
+    };
+
+    unsafe { pending_funcalls = LispObject::cons(list!(callback, contents), pending_funcalls) };
+}
+
+/// Queue the callbacks of the background clipboard reads that have
+/// finished.  Called when `CLIPBOARD_READ_EVENT` is read.
+pub fn queue_clipboard_read_callbacks() {
+    for (id, contents) in take_finished_reads() {
+        let reads = unsafe { globals.Vwr_clipboard_pending_reads };
+        let read = unsafe { Fassq((id as EmacsInt).into(), reads) };
+
+        if let Some(cons) = read.as_cons() {
+            unsafe { globals.Vwr_clipboard_pending_reads = Fdelq(read, reads) };
+            queue_clipboard_callback(cons.cdr(), contents);
+        }
+    }
+}
+
+/// Have `wr-clipboard-change-hook' run the next time Emacs runs timers.
+/// Called when `CLIPBOARD_CHANGED_EVENT` is read.
+pub fn queue_clipboard_change_hook() {
+    unsafe {
+        pending_funcalls = LispObject::cons(
+            list!(globals.Vrun_hooks, Qwr_clipboard_change_hook),
+            pending_funcalls,
+        )
+    };
+}
+
+/// Have `wr-theme-change-functions' called with FRAME the next time
+/// Emacs runs timers.  Called when the theme of FRAME's window changes.
+pub fn queue_theme_change_hook(frame: LispObject) {
+    let run_hook_with_args = CString::new("run-hook-with-args").unwrap();
+
+    unsafe {
+        pending_funcalls = LispObject::cons(
+            list!(
+                intern(run_hook_with_args.as_ptr()),
+                Qwr_theme_change_functions,
+                frame
+            ),
+            pending_funcalls,
+        )
+    };
+}
+
+/// Have FRAME redrawn from scratch with its images reloaded, and then
+/// `wr-display-reset-functions' called with it, the next time Emacs runs
+/// timers.  Called once the GL context of FRAME's window was recreated
+/// after being lost or a suspend, which dropped everything the renderer
+/// held.
+pub fn queue_display_reset_hook(frame: LispObject) {
+    let run_hook_with_args = CString::new("run-hook-with-args").unwrap();
+    let redraw_frame = CString::new("redraw-frame").unwrap();
+    let clear_image_cache = CString::new("clear-image-cache").unwrap();
+
+    // Pending funcalls run most recently queued first.
+    unsafe {
+        pending_funcalls = LispObject::cons(
+            list!(
+                intern(run_hook_with_args.as_ptr()),
+                Qwr_display_reset_functions,
+                frame
+            ),
+            pending_funcalls,
+        );
+        pending_funcalls = LispObject::cons(
+            list!(intern(redraw_frame.as_ptr()), frame),
+            pending_funcalls,
+        );
+        pending_funcalls = LispObject::cons(
+            list!(intern(clear_image_cache.as_ptr()), frame),
+            pending_funcalls,
+        );
+    };
+}
+
+/// Read the clipboard as text and call CALLBACK with it.
+/// CALLBACK is called with one argument, the text, or nil if the
+/// clipboard couldn't be read.
+/// Where the clipboard can be read in the background, as on X, it is,
+/// whatever its size, so a large selection is transferred without holding
+/// up input, and CALLBACK is called once the transfer finishes.  The size
+/// of a selection isn't known until it has been transferred, so reads
+/// that are small enough to do at once should use `gui-get-selection'.
+/// When Emacs owns the clipboard, or elsewhere, the clipboard is read
+/// right away and CALLBACK is called the next time timers run.
+#[lisp_fn]
+pub fn wr_read_clipboard_async(callback: LispObject) {
+    check_window_system();
+    let mut event_loop = EVENT_LOOP.lock();
+
+    // Ownership is known without reading the clipboard wherever it can be
+    // read in the background.
+    if event_loop.owns_selection("CLIPBOARD") == Some(false) {
+        let id = next_read_id();
+
+        if event_loop.read_clipboard_in_background(id) {
+            unsafe {
+                globals.Vwr_clipboard_pending_reads = LispObject::cons(
+                    LispObject::cons(id as EmacsInt, callback),
+                    globals.Vwr_clipboard_pending_reads,
+                );
+            }
+            return;
+        }
+    }
+
+    let contents = event_loop.get_clipboard().get_contents().ok();
+    queue_clipboard_callback(callback, contents);
+}
+
+/// Watch the clipboard for changes, checking it every INTERVAL
+/// milliseconds, and run `wr-clipboard-change-hook' after each change.
+/// Changes made by Emacs are reported as well.
+/// If INTERVAL is nil, stop watching the clipboard.
+/// Return t if the clipboard is watched, or nil if it can't be watched
+/// on this window system, as on Wayland, or INTERVAL is nil.
+#[lisp_fn]
+pub fn wr_set_clipboard_watch_interval(interval: LispObject) -> bool {
+    check_window_system();
+    let interval = if interval.is_nil() {
+        None
+    } else {
+        let interval = interval.as_natnum_or_error() as u64;
+
+        if interval == 0 {
+            error!("Clipboard watch interval must be positive");
+        }
+
+        Some(Duration::from_millis(interval))
+    };
+
+    let watching = EVENT_LOOP.lock().set_clipboard_watch_interval(interval);
+    watching && interval.is_some()
+}
+
+/// Return the interval set by `wr-set-clipboard-watch-interval', in
+/// milliseconds, or nil if the clipboard isn't watched.
+#[lisp_fn]
+pub fn wr_clipboard_watch_interval() -> LispObject {
+    check_window_system();
+    match EVENT_LOOP.lock().clipboard_watch_interval() {
+        Some(interval) => (interval.as_millis() as EmacsInt).into(),
+        None => Qnil,
+    }
+}
+
+/// Keep the SIZE most recent texts Emacs put on the clipboard, for
+/// `wr-clipboard-history'.  On X11 they are also put in the cut buffers,
+/// where other X clients and later sessions can read them, and at most 8
+/// are kept.  If SIZE is nil or 0, keep none, as by default.
+#[lisp_fn]
+pub fn wr_set_clipboard_history(size: LispObject) {
+    check_window_system();
+    let size = if size.is_nil() {
+        0
+    } else {
+        size.as_natnum_or_error() as usize
+    };
+
+    EVENT_LOOP.lock().set_clipboard_history_size(size);
+}
+
+/// Return the texts recently put on the clipboard, most recent first, as
+/// kept by `wr-set-clipboard-history'.  On X11 this is the contents of
+/// the cut buffers, which other clients may have changed.
+#[lisp_fn]
+pub fn wr_clipboard_history() -> LispObject {
+    check_window_system();
+    let history = EVENT_LOOP.lock().clipboard_history();
+
+    history
+        .iter()
+        .rev()
+        .fold(Qnil, |list, text| LispObject::cons(text.as_str(), list))
+}
+
 /// Whether the current Emacs process owns the given X Selection.
 /// The arg should be the name of the selection in question, typically one of
 /// the symbols `PRIMARY', `SECONDARY', or `CLIPBOARD'.
@@ -811,8 +1197,13 @@ pub fn x_get_selection_internal(
 ///
 /// On Nextstep, TERMINAL is unused.
 #[lisp_fn(min = "0")]
-pub fn x_selection_owner_p(_selection: LispObject, _terminal: LispObject) -> LispObject {
-    Qnil
+pub fn x_selection_owner_p(selection: LispObject, _terminal: LispObject) -> bool {
+    check_window_system();
+    let mut event_loop = EVENT_LOOP.lock();
+
+    event_loop
+        .selection_owned_since(&selection_name(selection))
+        .is_some()
 }
 
 /// Whether there is an owner for the given X selection.
@@ -827,8 +1218,129 @@ pub fn x_selection_owner_p(_selection: LispObject, _terminal: LispObject) -> Lis
 ///
 /// On Nextstep, TERMINAL is unused.
 #[lisp_fn(min = "0")]
-pub fn x_selection_exists_p(_selection: LispObject, _terminal: LispObject) -> LispObject {
-    Qnil
+pub fn x_selection_exists_p(selection: LispObject, _terminal: LispObject) -> bool {
+    check_window_system();
+    let mut event_loop = EVENT_LOOP.lock();
+
+    event_loop
+        .selection_owned_since(&selection_name(selection))
+        .is_some()
+        || event_loop.selection_exists()
+}
+
+/// Return the time Emacs took ownership of SELECTION, if it owns it.
+/// The time is in milliseconds since the Unix epoch.  Emacs loses
+/// ownership once another client changes the clipboard.
+/// SELECTION is a symbol, typically `PRIMARY', `SECONDARY', or `CLIPBOARD';
+/// nil is the same as `PRIMARY' and t the same as `SECONDARY'.
+/// Return nil if Emacs doesn't own SELECTION.
+#[lisp_fn(min = "0")]
+pub fn wr_selection_owner_p(selection: LispObject) -> LispObject {
+    check_window_system();
+    let mut event_loop = EVENT_LOOP.lock();
+
+    match event_loop.selection_owned_since(&selection_name(selection)) {
+        Some(timestamp) => (timestamp as EmacsInt).into(),
+        None => Qnil,
+    }
+}
+
+/// Return the targets the clipboard contents can be read as.
+/// The value is a list of strings such as "text/html" or "UTF8_STRING".
+/// Where the window system can't list targets, the list only contains
+/// "text/plain;charset=utf-8".
+#[lisp_fn]
+pub fn wr_clipboard_targets() -> LispObject {
+    check_window_system();
+    let event_loop = EVENT_LOOP.lock();
+
+    event_loop
+        .clipboard_targets()
+        .iter()
+        .rev()
+        .fold(Qnil, |list, target| LispObject::cons(target.as_str(), list))
+}
+
+/// Return the clipboard contents converted to TARGET, a string.
+/// The data is returned undecoded, as a unibyte string.
+/// Return nil if the clipboard can't be read as TARGET.
+#[lisp_fn]
+pub fn wr_clipboard_get_target(target: LispStringRef) -> LispObject {
+    check_window_system();
+    let mut event_loop = EVENT_LOOP.lock();
+
+    match event_loop.clipboard_get_target(&target.to_utf8()) {
+        Some(data) => unsafe {
+            make_unibyte_string(data.as_ptr() as *const libc::c_char, data.len() as isize)
+        },
+        None => Qnil,
+    }
+}
+
+/// Connect to the clipboard of the window system again, for when copy
+/// and paste stopped working, as when the clipboard's own connection to
+/// the X server broke or couldn't be made when the display was opened.
+/// On Wayland, the clipboard uses the display's connection, so this
+/// can't recover from the compositor being restarted, which Emacs'
+/// frames don't survive either.  Return t if the clipboard could be
+/// connected to.  Otherwise return nil, and until the clipboard can be
+/// connected to, copy and paste only work within Emacs.
+#[lisp_fn]
+pub fn wr_reinit_clipboard() -> bool {
+    check_window_system();
+    EVENT_LOOP.lock().reconnect_clipboard()
+}
+
+/// Return the clipboard contents as TARGET, a string, undecoded in a
+/// unibyte string.  TARGET defaults to UTF-8 text, which is read without
+/// checking it is valid where the window system allows, so it can be
+/// decoded with `selection-coding-system'.
+/// Signal an error if the clipboard can't be read.
+#[lisp_fn(min = "0")]
+pub fn wr_clipboard_get_bytes(target: LispObject) -> LispObject {
+    check_window_system();
+    let target = clipboard_target(target);
+
+    // The event loop must be unlocked before signaling an error.
+    let data = EVENT_LOOP.lock().clipboard_bytes(&target);
+    match data {
+        Ok(data) => unsafe {
+            make_unibyte_string(data.as_ptr() as *const libc::c_char, data.len() as isize)
+        },
+        Err(err) => error!("Failed to read the clipboard: {}", err),
+    }
+}
+
+/// Set the clipboard to BYTES, a unibyte string, offered as TARGET.
+/// TARGET defaults to UTF-8 text.  Encode text with the coding system
+/// its readers expect first.  On window systems other than X, the
+/// clipboard only holds text, so TARGET must be omitted and BYTES valid
+/// UTF-8.
+/// Signal an error if the clipboard can't be set.
+#[lisp_fn(min = "1")]
+pub fn wr_clipboard_set_bytes(bytes: LispStringRef, target: LispObject) {
+    check_window_system();
+    if bytes.is_multibyte() {
+        error!("Clipboard contents must be a unibyte string");
+    }
+    let target = clipboard_target(target);
+
+    let set = EVENT_LOOP
+        .lock()
+        .set_clipboard_bytes(&target, bytes.as_slice().to_vec());
+    if let Err(err) = set {
+        error!("Failed to set the clipboard: {}", err);
+    }
+}
+
+// The clipboard target named by the string TARGET, or UTF-8 text if
+// TARGET is nil.
+fn clipboard_target(target: LispObject) -> String {
+    if target.is_nil() {
+        TEXT_TARGET.to_owned()
+    } else {
+        LispStringRef::from(target).to_utf8()
+    }
 }
 
 /// Return edge coordinates of FRAME.
@@ -924,80 +1436,1653 @@ pub fn wr_api_stop_capture_sequence() {
     }
 }
 
-fn syms_of_wrfont() {
-    unsafe {
-        register_font_driver(&FONT_DRIVER.0, ptr::null_mut());
-    }
+/// Iconify (minimize) FRAME.
+/// If FRAME is omitted or nil, use the selected frame.
+#[lisp_fn(min = "0")]
+pub fn wr_iconify_frame(frame: LispObject) {
+    let frame = window_frame_live_or_selected(frame);
+    frame.set_minimized(true);
 }
 
-#[no_mangle]
-#[allow(unused_doc_comments)]
-pub extern "C" fn syms_of_wrterm() {
-    // pretend webrender as a X gui backend, so we can reuse the x-win.el logic
-    def_lisp_sym!(Qwr, "wr");
-    unsafe {
-        Fprovide(Qwr, Qnil);
+/// Maximize FRAME.
+/// If FRAME is omitted or nil, use the selected frame.
+#[lisp_fn(min = "0")]
+pub fn wr_maximize_frame(frame: LispObject) {
+    let frame = window_frame_live_or_selected(frame);
+    frame.set_maximized();
+}
+
+// (WIDTH HEIGHT BIT-DEPTH REFRESH-RATE) describing MODE.
+fn video_mode_to_lisp(mode: &VideoMode) -> LispObject {
+    let size = mode.size();
+
+    list!(
+        size.width as EmacsInt,
+        size.height as EmacsInt,
+        mode.bit_depth() as EmacsInt,
+        mode.refresh_rate_millihertz() as EmacsInt
+    )
+}
+
+/// Return the video modes of every monitor.
+/// The value is an alist of (NAME . MODES), where NAME is the monitor's
+/// name, or nil if it has none, and MODES is a list of
+/// (WIDTH HEIGHT BIT-DEPTH REFRESH-RATE).  The refresh rate is in
+/// millihertz.  Monitors without video modes, such as virtual displays,
+/// have an empty MODES list.
+#[lisp_fn]
+pub fn wr_monitor_video_modes() -> LispObject {
+    check_window_system();
+    let event_loop = EVENT_LOOP.lock();
+
+    let monitors: Vec<_> = event_loop.get_available_monitors().collect();
+
+    monitors.iter().rev().fold(Qnil, |list, monitor| {
+        let name = match monitor.name() {
+            Some(name) => name.as_str().into(),
+            None => Qnil,
+        };
+
+        let modes: Vec<_> = monitor.video_modes().collect();
+        let modes = modes.iter().rev().fold(Qnil, |list, mode| {
+            LispObject::cons(video_mode_to_lisp(mode), list)
+        });
+
+        LispObject::cons(LispObject::cons(name, modes), list)
+    })
+}
+
+/// Make FRAME fullscreen if FULLSCREEN is non-nil, else leave fullscreen.
+/// If MODE is nil, FRAME covers its monitor without changing its video
+/// mode.  Otherwise MODE is one of the (WIDTH HEIGHT BIT-DEPTH REFRESH-RATE)
+/// lists `wr-monitor-video-modes' returns for FRAME's monitor, and the
+/// monitor switches to that mode while FRAME is fullscreen.
+/// If FRAME is nil, use the selected frame.
+#[lisp_fn(min = "2")]
+pub fn wr_set_frame_fullscreen(frame: LispObject, fullscreen: LispObject, mode: LispObject) {
+    let frame = window_frame_live_or_selected(frame);
+    let output = frame.wr_output();
+
+    if fullscreen.is_nil() {
+        output.set_fullscreen(None);
+        return;
     }
 
-    #[cfg(feature = "capture")]
-    {
-        let wr_capture_sym =
-            CString::new("wr-capture").expect("Failed to create string for intern function call");
-        def_lisp_sym!(Qwr_capture, "wr-capture");
-        unsafe {
-            Fprovide(
-                emacs::bindings::intern_c_string(wr_capture_sym.as_ptr()),
-                Qnil,
-            );
-        }
+    if mode.is_nil() {
+        output.set_fullscreen(Some(Fullscreen::Borderless(None)));
+        return;
     }
 
-    let wr_keysym_table = unsafe {
-        make_hash_table(
-            hashtest_eql.clone(),
-            900,
-            DEFAULT_REHASH_SIZE,
-            DEFAULT_REHASH_THRESHOLD,
-            Qnil,
-            false,
-        )
+    let mode = mode
+        .iter_cars(LispConsEndChecks::on, LispConsCircularChecks::on)
+        .map(|value| value.as_natnum_or_error())
+        .collect::<Vec<_>>();
+
+    let monitor = match output.get_window().current_monitor() {
+        Some(monitor) => monitor,
+        None => error!("Frame is not on any monitor"),
     };
 
-    // Hash table of character codes indexed by X keysym codes.
-    #[rustfmt::skip]
-    defvar_lisp!(Vwr_keysym_table, "wr-keysym-table", wr_keysym_table);
+    let video_modes: Vec<_> = monitor.video_modes().collect();
 
-    // Which toolkit scroll bars Emacs uses, if any.
-    // A value of nil means Emacs doesn't use toolkit scroll bars.
-    // With the X Window system, the value is a symbol describing the
-    // X toolkit.  Possible values are: gtk, motif, xaw, or xaw3d.
-    // With MS Windows or Nextstep, the value is t.
-    #[rustfmt::skip]
-    defvar_lisp!(Vx_toolkit_scroll_bars, "x-toolkit-scroll-bars", Qnil);
+    if video_modes.is_empty() {
+        error!("Monitor has no video modes; exclusive fullscreen is unavailable");
+    }
 
-    // Non-nil means make use of UNDERLINE_POSITION font properties.
-    // A value of nil means ignore them.  If you encounter fonts with bogus
-    // UNDERLINE_POSITION font properties, set this to nil.  You can also use
-    // `underline-minimum-offset' to override the font's UNDERLINE_POSITION for
-    // small font display sizes.
-    #[rustfmt::skip]
-    defvar_bool!(Vx_use_underline_position_properties, "x-use-underline-position-properties", true);
+    let video_mode = video_modes.into_iter().find(|video_mode| {
+        let size = video_mode.size();
 
-    // Non-nil means to draw the underline at the same place as the descent line.
-    // (If `line-spacing' is in effect, that moves the underline lower by
-    // that many pixels.)
-    // A value of nil means to draw the underline according to the value of the
-    // variable `x-use-underline-position-properties', which is usually at the
-    // baseline level.  The default value is nil.
-    #[rustfmt::skip]
-    defvar_bool!(Vx_underline_at_descent_line, "x-underline-at-descent-line", false);
+        mode == [
+            size.width as EmacsUint,
+            size.height as EmacsUint,
+            video_mode.bit_depth() as EmacsUint,
+            video_mode.refresh_rate_millihertz() as EmacsUint,
+        ]
+    });
 
-    // Whether to enable X clipboard manager support.
-    // If non-nil, then whenever Emacs is killed or an Emacs frame is deleted
-    // while owning the X clipboard, the clipboard contents are saved to the
-    // clipboard manager if one is present.
-    #[rustfmt::skip]
-    defvar_lisp!(Vx_select_enable_clipboard_manager, "x-select-enable-clipboard-manager", Qt);
+    match video_mode {
+        Some(video_mode) => output.set_fullscreen(Some(Fullscreen::Exclusive(video_mode))),
+        None => error!("Monitor has no such video mode"),
+    }
+}
+
+/// Restore FRAME from an iconified or maximized state.
+/// If FRAME is omitted or nil, use the selected frame.
+#[lisp_fn(min = "0")]
+pub fn wr_restore_frame(frame: LispObject) {
+    let frame = window_frame_live_or_selected(frame);
+    frame.restore();
+}
+
+/// Return t if FRAME is visible, the symbol `icon' if it is iconified,
+/// and nil if it was made invisible.
+/// If FRAME is omitted or nil, use the selected frame.
+#[lisp_fn(min = "0")]
+pub fn wr_frame_visible_p(frame: LispObject) -> LispObject {
+    let frame = window_frame_live_or_selected(frame);
+
+    if frame.is_minimized() {
+        Qicon
+    } else if frame.is_visible() {
+        Qt
+    } else {
+        Qnil
+    }
+}
+
+/// Set the window icon of FRAME to ICON.
+/// ICON is either the file name of an image, such as a PNG file, or a
+/// unibyte string of RGBA pixels.  In the latter case WIDTH and HEIGHT
+/// give the size of the icon in pixels, and ICON must hold exactly
+/// WIDTH * HEIGHT * 4 bytes.
+///
+/// If the image file can't be decoded, a warning is logged and the
+/// current icon is kept.
+///
+/// If FRAME is nil, use the selected frame.
+#[lisp_fn(min = "2")]
+pub fn wr_set_frame_icon(
+    frame: LispObject,
+    icon: LispStringRef,
+    width: LispObject,
+    height: LispObject,
+) {
+    let frame = window_frame_live_or_selected(frame);
+
+    let (rgba, width, height) = if width.is_nil() && height.is_nil() {
+        let filename = icon.to_utf8();
+
+        match image::open(&filename) {
+            Ok(image) => {
+                let image = image.into_rgba8();
+                let (width, height) = image.dimensions();
+                (image.into_raw(), width, height)
+            }
+            Err(err) => {
+                log::warn!("Failed to decode frame icon {}: {}", filename, err);
+                return;
+            }
+        }
+    } else {
+        let width = width.as_natnum_or_error() as u32;
+        let height = height.as_natnum_or_error() as u32;
+        let rgba = icon.as_slice();
+
+        if rgba.len() != (width as usize) * (height as usize) * 4 {
+            error!(
+                "Icon data is {} bytes, expected {} for a {}x{} RGBA image",
+                rgba.len(),
+                (width as usize) * (height as usize) * 4,
+                width,
+                height
+            );
+        }
+
+        (rgba.to_vec(), width, height)
+    };
+
+    match Icon::from_rgba(rgba, width, height) {
+        Ok(icon) => frame.wr_output().set_icon(icon),
+        Err(err) => error!("Invalid frame icon: {}", err),
+    }
+}
+
+/// Return t if FRAME is known to be fully hidden behind other windows.
+/// Frames are only considered occluded when the window system says so;
+/// platforms that don't report occlusion always yield nil.
+/// If FRAME is omitted or nil, use the selected frame.
+#[lisp_fn(min = "0")]
+pub fn wr_frame_occluded_p(frame: LispObject) -> bool {
+    let frame = window_frame_live_or_selected(frame);
+    frame.is_occluded()
+}
+
+// Signal an error if the window system is inhibited or its events can't
+// be read on this platform, so nothing starts `EVENT_LOOP` then.
+// Functions on a frame needn't check: there are no webrender frames
+// without a window system, and they already do nothing for other frames.
+fn check_window_system() {
+    if window_system_inhibited() {
+        error!("No window system");
+    }
+    if !EVENT_LOOP_SUPPORTED {
+        error!("No window system event loop on this platform");
+    }
+}
+
+// FRAME, or the selected frame if FRAME is nil, unless FRAME has been
+// deleted or isn't a WebRender frame.
+fn live_wr_frame(frame: LispObject) -> Option<LispFrameRef> {
+    let frame = if frame.is_nil() {
+        window_frame_live_or_selected(frame)
+    } else {
+        frame
+            .as_frame()
+            .unwrap_or_else(|| wrong_type!(Qframep, frame))
+    };
+
+    if frame.is_live() && frame.output_method() == output_method::output_wr {
+        Some(frame)
+    } else {
+        None
+    }
+}
+
+// SIZE of FRAME's window as (WIDTH . HEIGHT), in logical pixels if
+// LOGICAL is true.
+fn window_size_to_lisp(frame: LispFrameRef, size: PhysicalSize<u32>, logical: bool) -> LispObject {
+    let (width, height) = if logical {
+        let window_id = frame.wr_output().get_window().id();
+        let scale_factor = EVENT_LOOP.lock().scale_factor(&window_id);
+        let size: LogicalSize<u32> = size.to_logical(scale_factor);
+        (size.width, size.height)
+    } else {
+        (size.width, size.height)
+    };
+
+    LispObject::cons(width as EmacsInt, height as EmacsInt)
+}
+
+// Parse SIZE, (WIDTH . HEIGHT) in logical pixels, or nil for no size.
+fn logical_size_from_lisp(size: LispObject) -> Option<LogicalSize<u32>> {
+    if size.is_nil() {
+        return None;
+    }
+
+    let size = size.force_cons();
+    Some(LogicalSize::new(
+        size.car().as_natnum_or_error() as u32,
+        size.cdr().as_natnum_or_error() as u32,
+    ))
+}
+
+/// Return the size of FRAME's window without its decorations.
+/// The value is (WIDTH . HEIGHT) in physical pixels, or in logical
+/// pixels if LOGICAL is non-nil.  Logical pixels use the scale factor
+/// last applied to the window.
+/// Return nil if FRAME has been deleted.
+/// If FRAME is omitted or nil, use the selected frame.
+#[lisp_fn(min = "0")]
+pub fn wr_frame_inner_size(frame: LispObject, logical: bool) -> LispObject {
+    match live_wr_frame(frame) {
+        Some(frame) => window_size_to_lisp(frame, frame.wr_output().get_inner_size(), logical),
+        None => Qnil,
+    }
+}
+
+/// Return the size of FRAME's window including its decorations.
+/// The value is (WIDTH . HEIGHT) in physical pixels, or in logical
+/// pixels if LOGICAL is non-nil.  Where decorations aren't known, this
+/// is the same as `wr-frame-inner-size'.
+/// Return nil if FRAME has been deleted.
+/// If FRAME is omitted or nil, use the selected frame.
+#[lisp_fn(min = "0")]
+pub fn wr_frame_outer_size(frame: LispObject, logical: bool) -> LispObject {
+    match live_wr_frame(frame) {
+        Some(frame) => window_size_to_lisp(frame, frame.wr_output().get_outer_size(), logical),
+        None => Qnil,
+    }
+}
+
+/// Resize FRAME's window to WIDTH by HEIGHT logical pixels, not
+/// counting its decorations, waiting briefly for the window system to
+/// apply the new size.  Window managers may adjust or ignore the request,
+/// as tiling ones do, so return the size the window has afterwards as
+/// (WIDTH . HEIGHT) in logical pixels.
+/// It is an error for WIDTH or HEIGHT to be 0, or more physical pixels
+/// than the GPU can draw a window at.
+/// Return nil if FRAME has been deleted.
+/// If FRAME is nil, use the selected frame.
+#[lisp_fn]
+pub fn wr_set_frame_size(frame: LispObject, width: LispObject, height: LispObject) -> LispObject {
+    let width = width.as_natnum_or_error();
+    let height = height.as_natnum_or_error();
+    if width == 0 || height == 0 {
+        error!("Invalid frame size: {}x{}", width, height);
+    }
+
+    let frame = match live_wr_frame(frame) {
+        Some(frame) => frame,
+        None => return Qnil,
+    };
+    let window_id = frame.wr_output().get_window().id();
+    let max_size = frame.wr_output().max_inner_size();
+
+    let scale_factor = EVENT_LOOP.lock().scale_factor(&window_id);
+    let size: PhysicalSize<f64> =
+        LogicalSize::new(width as f64, height as f64).to_physical(scale_factor);
+    if size.width > max_size as f64 || size.height > max_size as f64 {
+        error!(
+            "Frame size {}x{} is larger than {} physical pixels",
+            width, height, max_size
+        );
+    }
+    let size: PhysicalSize<u32> = size.cast();
+
+    let mut output = frame.wr_output();
+
+    if size != output.get_inner_size() {
+        output.set_inner_size(size);
+        wait_for_window_resize(window_id);
+    }
+
+    // Go by the size the window ended up with, resizing its surface and
+    // document now rather than when `Resized` is read, so they match the
+    // window once this returns.
+    let size = output.get_inner_size();
+    output.resize(&size);
+    frame.change_size(
+        size.width as i32,
+        size.height as i32 - frame.menu_bar_height,
+        false,
+        true,
+        false,
+    );
+
+    window_size_to_lisp(frame, size, true)
+}
+
+/// Keep FRAME's window from being resized below SIZE, not counting its
+/// decorations.  SIZE is (WIDTH . HEIGHT) in logical pixels, or nil to
+/// lift the limit.  The frame parameter `wr-min-size' sets the limit
+/// when a frame is created.  The limit is kept in logical pixels, so it
+/// follows the window to monitors of other scale factors.  It is an
+/// error for SIZE to be larger than FRAME's maximum size.
+/// Return SIZE, or nil if FRAME has been deleted.
+/// If FRAME is nil, use the selected frame.
+#[lisp_fn]
+pub fn wr_set_frame_min_size(frame: LispObject, size: LispObject) -> LispObject {
+    let min_size = logical_size_from_lisp(size);
+
+    match live_wr_frame(frame) {
+        Some(frame) => {
+            let fits = frame.wr_output().set_min_inner_size(min_size);
+            if !fits {
+                error!("Minimum size is larger than the frame's maximum size");
+            }
+            size
+        }
+        None => Qnil,
+    }
+}
+
+/// Keep FRAME's window from being resized beyond SIZE, not counting its
+/// decorations.  SIZE is (WIDTH . HEIGHT) in logical pixels, or nil to
+/// lift the limit.  The frame parameter `wr-max-size' sets the limit
+/// when a frame is created.  The limit is kept in logical pixels, so it
+/// follows the window to monitors of other scale factors.  It is an
+/// error for SIZE to be smaller than FRAME's minimum size.
+/// Return SIZE, or nil if FRAME has been deleted.
+/// If FRAME is nil, use the selected frame.
+#[lisp_fn]
+pub fn wr_set_frame_max_size(frame: LispObject, size: LispObject) -> LispObject {
+    let max_size = logical_size_from_lisp(size);
+
+    match live_wr_frame(frame) {
+        Some(frame) => {
+            let fits = frame.wr_output().set_max_inner_size(max_size);
+            if !fits {
+                error!("Maximum size is smaller than the frame's minimum size");
+            }
+            size
+        }
+        None => Qnil,
+    }
+}
+
+/// Draw the latest frame of the external image source ID in FRAME's
+/// window, in the rectangle at X, Y of WIDTH by HEIGHT, in physical
+/// pixels from the top left corner of the window.  External image
+/// sources are GL textures pushed by Rust code, such as a video decoder,
+/// which are drawn without copying them.  The image stays until that
+/// part of the window is redrawn, so a moving image is drawn again for
+/// each new frame.
+/// Return t, or nil if the source has no frame yet, doesn't exist or
+/// FRAME has been deleted.
+/// If FRAME is nil, use the selected frame.
+#[lisp_fn]
+pub fn wr_draw_external_image(
+    frame: LispObject,
+    id: LispObject,
+    x: LispObject,
+    y: LispObject,
+    width: LispObject,
+    height: LispObject,
+) -> bool {
+    let id = id.as_natnum_or_error();
+    let x = x.as_fixnum_or_error();
+    let y = y.as_fixnum_or_error();
+    let width = width.as_natnum_or_error();
+    let height = height.as_natnum_or_error();
+
+    let frame = match live_wr_frame(frame) {
+        Some(frame) => frame,
+        None => return false,
+    };
+    if frame.is_occluded() {
+        return false;
+    }
+
+    let mut output = frame.wr_output();
+    let rect = (x as i32, y as i32).by(width as i32, height as i32);
+    if !output.draw_external_image(id as u32, rect) {
+        return false;
+    }
+
+    output.flush();
+    true
+}
+
+/// Forget the external image source ID, as when its producer stops.
+/// Windows showing it draw nothing in its place until redrawn.
+/// Return t, or nil if there was no such source.
+#[lisp_fn]
+pub fn wr_unregister_external_image(id: LispObject) -> bool {
+    unregister_external_image(id.as_natnum_or_error() as u32)
+}
+
+/// Return the tags of the display items at X, Y in FRAME's window, in
+/// physical pixels from the top left corner of the window, as pointer
+/// positions are reported.  Each tag is a cons (TAG . HINT) of integers,
+/// as `wr-set-layer-items' tagged the items of a layer; the topmost item
+/// comes first.  What the frame itself draws is never tagged, as
+/// redisplay only draws what changed over the last frame: use
+/// `posn-at-x-y' for what of the frame is at X, Y.
+/// Return nil if FRAME has been deleted, nothing has been drawn in it
+/// yet or no tagged item is at X, Y.
+/// If FRAME is nil, use the selected frame.
+#[lisp_fn]
+pub fn wr_hit_test(frame: LispObject, x: LispObject, y: LispObject) -> LispObject {
+    let x = x.as_fixnum_or_error();
+    let y = y.as_fixnum_or_error();
+
+    let tags = match live_wr_frame(frame) {
+        Some(frame) => frame
+            .wr_output()
+            .hit_test(WorldPoint::new(x as f32, y as f32)),
+        None => None,
+    };
+
+    tags.unwrap_or_default()
+        .into_iter()
+        .rev()
+        .fold(Qnil, |list, (tag, hint)| {
+            LispObject::cons(LispObject::cons(tag as EmacsInt, hint as EmacsInt), list)
+        })
+}
+
+/// Add a layer over FRAME's window, which is drawn over the frame and
+/// redrawn on its own, as for tooltips that come and go without a
+/// redisplay of the frame.  Layers of a higher Z-ORDER, an integer
+/// defaulting to 0, are drawn over those of a lower one; the new layer
+/// goes over the others of its Z-ORDER.  The layer is empty until
+/// `wr-set-layer-items' fills it.
+/// Recreating the window's GL context empties its layers, keeping their
+/// ids, so `wr-display-reset-functions' can fill them again.
+/// Return the id of the layer, or nil if FRAME has been deleted.
+/// If FRAME is nil, use the selected frame.
+#[lisp_fn(min = "1")]
+pub fn wr_add_layer(frame: LispObject, z_order: LispObject) -> LispObject {
+    let z_order = if z_order.is_nil() {
+        0
+    } else {
+        z_order.as_fixnum_or_error() as i32
+    };
+
+    match live_wr_frame(frame) {
+        Some(frame) => (frame.wr_output().add_layer(z_order) as EmacsInt).into(),
+        None => Qnil,
+    }
+}
+
+/// Replace what LAYER of FRAME's window shows with ITEMS, and show it
+/// without redrawing the frame under it.  Each item is a list
+/// (X Y WIDTH HEIGHT COLOR TAG), a rectangle filled with COLOR at X, Y
+/// in physical pixels from the top left corner of the window.  COLOR is
+/// as for `wr-set-frame-clear-color', so it may be translucent.  TAG is
+/// a cons (TAG . HINT) of integers `wr-hit-test' returns for points in
+/// the rectangle, or nil or left out for none.
+/// Return t, or nil if FRAME has no LAYER or has been deleted.
+/// If FRAME is nil, use the selected frame.
+#[lisp_fn]
+pub fn wr_set_layer_items(frame: LispObject, layer: LispObject, items: LispObject) -> bool {
+    let layer = layer.as_natnum_or_error() as u32;
+    let items: Vec<(LayoutRect, ColorF, Option<ItemTag>)> = items
+        .iter_cars(LispConsEndChecks::on, LispConsCircularChecks::on)
+        .map(layer_item_from_lisp)
+        .collect();
+
+    match live_wr_frame(frame) {
+        Some(frame) => {
+            frame
+                .wr_output()
+                .set_layer_display_list(layer, |builder, space_and_clip| {
+                    for (rect, color, tag) in &items {
+                        builder.push_rect(
+                            &CommonItemProperties::new(*rect, space_and_clip),
+                            *rect,
+                            *color,
+                        );
+                        if let Some(tag) = tag {
+                            builder.push_hit_test(
+                                *rect,
+                                space_and_clip.clip_chain_id,
+                                space_and_clip.spatial_id,
+                                PrimitiveFlags::default(),
+                                *tag,
+                            );
+                        }
+                    }
+                })
+        }
+        None => false,
+    }
+}
+
+// ITEM of a layer, (X Y WIDTH HEIGHT COLOR TAG) with TAG optional.
+fn layer_item_from_lisp(item: LispObject) -> (LayoutRect, ColorF, Option<ItemTag>) {
+    let values: Vec<LispObject> = item
+        .iter_cars(LispConsEndChecks::on, LispConsCircularChecks::on)
+        .collect();
+
+    let (x, y, width, height, color, tag) = match values[..] {
+        [x, y, width, height, color] => (x, y, width, height, color, Qnil),
+        [x, y, width, height, color, tag] => (x, y, width, height, color, tag),
+        _ => error!("Layer item must be a list (X Y WIDTH HEIGHT COLOR TAG)"),
+    };
+
+    let rect = (x.as_fixnum_or_error() as i32, y.as_fixnum_or_error() as i32).by(
+        width.as_natnum_or_error() as i32,
+        height.as_natnum_or_error() as i32,
+    );
+    let tag = if tag.is_nil() {
+        None
+    } else {
+        let tag = tag.force_cons();
+        Some((
+            tag.car().as_natnum_or_error() as u64,
+            tag.cdr().as_natnum_or_error() as u16,
+        ))
+    };
+
+    (rect, color_from_lisp(color), tag)
+}
+
+/// Remove LAYER from FRAME's window, showing what is under it again.
+/// Return t, or nil if FRAME has no LAYER or has been deleted.
+/// If FRAME is nil, use the selected frame.
+#[lisp_fn]
+pub fn wr_remove_layer(frame: LispObject, layer: LispObject) -> bool {
+    let layer = layer.as_natnum_or_error() as u32;
+
+    match live_wr_frame(frame) {
+        Some(frame) => frame.wr_output().remove_layer(layer),
+        None => false,
+    }
+}
+
+/// Return the theme of FRAME's window, as the window system's light or
+/// dark appearance: the symbol `light' or `dark'.
+/// Return nil if FRAME has been deleted, or if the window system hasn't
+/// reported a theme.  Only Windows reports it before it first changes;
+/// `wr-theme-change-functions' run when it does.
+/// If FRAME is nil, use the selected frame.
+#[lisp_fn(min = "0")]
+pub fn wr_frame_theme(frame: LispObject) -> LispObject {
+    let theme = live_wr_frame(frame).and_then(|frame| {
+        let window_id = frame.wr_output().get_window().id();
+        EVENT_LOOP.lock().window_state(&window_id)?.theme
+    });
+
+    let name = match theme {
+        Some(Theme::Light) => "light",
+        Some(Theme::Dark) => "dark",
+        None => return Qnil,
+    };
+    let name = CString::new(name).unwrap();
+    unsafe { intern(name.as_ptr()) }
+}
+
+/// Grab the mouse pointer for FRAME's window in MODE, as for a drag
+/// that goes on past the window's edge.  MODE is one of the symbols:
+///  `confined': keep the pointer inside the window;
+///  `locked': keep the pointer where it is, while motion is still
+///   reported;
+///  `none' or nil: release the pointer.
+/// Not every window system supports every mode: X and Windows can
+/// confine the pointer but not lock it, macOS can lock it but not
+/// confine it, and Wayland can do both.
+/// Return t if the pointer was grabbed in MODE or released, nil if the
+/// window system can't grab it so or FRAME has been deleted.
+/// If FRAME is nil, use the selected frame.
+#[lisp_fn]
+pub fn wr_set_cursor_grab(frame: LispObject, mode: LispObject) -> bool {
+    let mode = if mode.is_nil() {
+        CursorGrabMode::None
+    } else {
+        let name: LispStringRef = mode.force_symbol().symbol_name().into();
+
+        match name.to_utf8().as_str() {
+            "none" => CursorGrabMode::None,
+            "confined" => CursorGrabMode::Confined,
+            "locked" => CursorGrabMode::Locked,
+            name => error!("Unknown cursor grab mode: {}", name),
+        }
+    };
+
+    match live_wr_frame(frame) {
+        Some(frame) => frame.wr_output().set_cursor_grab(mode),
+        None => false,
+    }
+}
+
+/// Show the image CURSOR as the mouse pointer of FRAME's window.
+/// CURSOR is a unibyte string of RGBA pixels, exactly WIDTH * HEIGHT * 4
+/// bytes.  HOTSPOT is (X . Y), the point of the image a click is at, in
+/// logical pixels from its top left corner: it is scaled by the scale
+/// factor of the window, like the rest of Emacs' positions, while the
+/// image isn't.
+/// The pointer stays until Emacs next sets FRAME's pointer shape, as when
+/// the mouse moves over text.  Where the window system can't show images
+/// as pointers, the standard shape nearest CURSOR is shown instead, and a
+/// warning is logged.
+/// Return t if CURSOR is shown, nil if a standard shape is or FRAME has
+/// been deleted.
+/// If FRAME is nil, use the selected frame.
+#[lisp_fn]
+pub fn wr_set_custom_cursor(
+    frame: LispObject,
+    cursor: LispStringRef,
+    width: LispObject,
+    height: LispObject,
+    hotspot: LispObject,
+) -> bool {
+    let width = width.as_natnum_or_error();
+    let height = height.as_natnum_or_error();
+    if width > u16::MAX as EmacsUint || height > u16::MAX as EmacsUint {
+        error!("Cursor is too large: {}x{}", width, height);
+    }
+
+    let hotspot = hotspot.force_cons();
+    let hotspot = LogicalPosition::new(
+        hotspot.car().as_natnum_or_error() as f64,
+        hotspot.cdr().as_natnum_or_error() as f64,
+    );
+    let cursor = match CustomCursor::new(
+        cursor.as_slice().to_vec(),
+        width as u16,
+        height as u16,
+        hotspot,
+    ) {
+        Ok(cursor) => cursor,
+        Err(msg) => error!("{}", msg),
+    };
+
+    match live_wr_frame(frame) {
+        Some(frame) => {
+            let window_id = frame.wr_output().get_window().id();
+            let scale_factor = EVENT_LOOP.lock().scale_factor(&window_id);
+            frame.wr_output().set_custom_cursor(&cursor, scale_factor)
+        }
+        None => false,
+    }
+}
+
+/// Have the window system blur what is behind FRAME's window, so text
+/// stays readable over the parts of it left transparent.  BLUR is nil to
+/// stop blurring, t to blur, or the radius to blur by in points, which
+/// only macOS honors.  A frame drawn opaque shows no blur.
+/// The blur is supported by macOS, by Windows 11 as an acrylic backdrop,
+/// and on X by KWin; other X compositors and Wayland ignore it.
+/// Return t if the blur was set or removed, nil if the window system
+/// can't blur behind windows or FRAME has been deleted.
+/// If FRAME is nil, use the selected frame.
+#[lisp_fn]
+pub fn wr_set_frame_blur(frame: LispObject, blur: LispObject) -> bool {
+    let radius = if blur.is_nil() {
+        None
+    } else if blur.is_t() {
+        Some(DEFAULT_BLUR_RADIUS)
+    } else {
+        Some(blur.as_natnum_or_error() as u32)
+    };
+
+    match live_wr_frame(frame) {
+        Some(frame) => {
+            let platform = EVENT_LOOP.lock().platform();
+            set_blur(frame.wr_output().get_window(), platform, radius)
+        }
+        None => false,
+    }
+}
+
+/// Set the color FRAME's window is cleared to before anything is drawn in
+/// it, as while a frame is created or resized, to COLOR.  COLOR is a
+/// color name or "#RRGGBB" string, or a list (RED GREEN BLUE ALPHA) of
+/// numbers from 0 to 1, where ALPHA may be left out for an opaque color.
+/// Return COLOR, or nil if FRAME has been deleted.
+/// If FRAME is nil, use the selected frame.
+#[lisp_fn]
+pub fn wr_set_frame_clear_color(frame: LispObject, color: LispObject) -> LispObject {
+    let clear_color = color_from_lisp(color);
+
+    match live_wr_frame(frame) {
+        Some(frame) => {
+            frame.wr_output().set_clear_color(clear_color);
+            color
+        }
+        None => Qnil,
+    }
+}
+
+// COLOR, a color name or "#RRGGBB" string, or a list (RED GREEN BLUE
+// ALPHA) of color components, where ALPHA may be left out.
+fn color_from_lisp(color: LispObject) -> ColorF {
+    if let Some(name) = color.as_string() {
+        match lookup_color_by_name_or_hex(&name.to_utf8()) {
+            Some(color) => color,
+            None => error!("Undefined color: {}", name.to_utf8()),
+        }
+    } else {
+        let components: Vec<f32> = color
+            .iter_cars(LispConsEndChecks::on, LispConsCircularChecks::on)
+            .map(color_component)
+            .collect();
+
+        match components[..] {
+            [r, g, b] => ColorF::new(r, g, b, 1.0),
+            [r, g, b, a] => ColorF::new(r, g, b, a),
+            _ => error!("Color must be a color name or a list (RED GREEN BLUE ALPHA)"),
+        }
+    }
+}
+
+// VALUE as a color component, a number from 0 to 1.
+fn color_component(value: LispObject) -> f32 {
+    let component = if let Some(value) = value.as_fixnum() {
+        value as f64
+    } else if unsafe { FLOATP(value) } {
+        unsafe { XFLOAT_DATA(value) }
+    } else {
+        wrong_type!(Qnumberp, value)
+    };
+
+    if !(0.0..=1.0).contains(&component) {
+        error!("Color component must be from 0 to 1: {}", component);
+    }
+
+    component as f32
+}
+
+/// Set the title of FRAME's window to TITLE, a string.
+/// The title lasts until Emacs next sets it from the frame's name.
+/// Return TITLE, or nil if FRAME has been deleted.
+/// If FRAME is nil, use the selected frame.
+#[lisp_fn]
+pub fn wr_set_frame_title(frame: LispObject, title: LispObject) -> LispObject {
+    let title_text = title.force_string().to_utf8();
+
+    match live_wr_frame(frame) {
+        Some(frame) => {
+            frame.wr_output().set_title(&title_text);
+            title
+        }
+        None => Qnil,
+    }
+}
+
+/// Return the title last set for FRAME's window.
+/// Return nil if no title has been set or FRAME has been deleted.
+/// If FRAME is omitted or nil, use the selected frame.
+#[lisp_fn(min = "0")]
+pub fn wr_frame_title(frame: LispObject) -> LispObject {
+    live_wr_frame(frame)
+        .and_then(|frame| frame.wr_output().title().map(|title| title.into()))
+        .unwrap_or(Qnil)
+}
+
+/// Return the live webrender frames, with their windows, as a list of
+/// (FRAME . ATTRIBUTES).  ATTRIBUTES is an alist of:
+///  `window-id': the window system's id of the window, an integer;
+///  `title': the title last set for the window, or nil;
+///  `size': the size of the window as (WIDTH . HEIGHT), in physical
+///   pixels and not counting its decorations;
+///  `focused': whether the window has keyboard focus.
+/// Frames come in the order of `frame-list', and other `wr-' functions
+/// accept FRAME.
+#[lisp_fn]
+pub fn wr_frame_list() -> LispObject {
+    check_window_system();
+    let attribute = |name: &str, value: LispObject| {
+        let name = CString::new(name).unwrap();
+        LispObject::cons(unsafe { intern(name.as_ptr()) }, value)
+    };
+
+    let event_loop = EVENT_LOOP.lock();
+    let frames: Vec<_> = all_frames()
+        .filter(|frame| frame.is_live() && frame.output_method() == output_method::output_wr)
+        .map(|frame| {
+            let output = frame.wr_output();
+            let window = output.get_window();
+            let size = window.inner_size();
+            let focused = event_loop
+                .window_state(&window.id())
+                .map_or(false, |state| state.focused);
+
+            let attributes = list!(
+                attribute("window-id", (u64::from(window.id()) as EmacsInt).into()),
+                attribute("title", output.title().map_or(Qnil, |title| title.into())),
+                attribute(
+                    "size",
+                    LispObject::cons(size.width as EmacsInt, size.height as EmacsInt)
+                ),
+                attribute("focused", focused.into())
+            );
+            LispObject::cons(frame, attributes)
+        })
+        .collect();
+
+    frames
+        .into_iter()
+        .rev()
+        .fold(Qnil, |list, entry| LispObject::cons(entry, list))
+}
+
+/// Close FRAME's window as if its close button had been clicked, which
+/// queues a `delete-frame' event for `handle-delete-frame' to answer.
+/// With FORCE non-nil, delete FRAME right away instead, even if no other
+/// frame is visible; that destroys its window and GL surface.
+/// The display stays open after its last frame is closed, so that a
+/// daemon can make frames on it again.
+/// Return t if FRAME was closed or its event queued, nil if FRAME has
+/// already been deleted.
+/// If FRAME is nil, use the selected frame.
+#[lisp_fn(min = "1")]
+pub fn wr_close_frame(frame: LispObject, force: LispObject) -> bool {
+    let frame = match live_wr_frame(frame) {
+        Some(frame) => frame,
+        None => return false,
+    };
+
+    if force.is_not_nil() {
+        unsafe { Fdelete_frame(frame.into(), Qt) };
+    } else {
+        let window_id = frame.wr_output().get_window().id();
+        EVENT_BUFFER.lock().push(Event::WindowEvent {
+            window_id,
+            event: WindowEvent::CloseRequested,
+        });
+    }
+    true
+}
+
+/// Return the frame the mouse pointer is over, or nil if it isn't over
+/// any frame on the display of FRAME.
+/// If FRAME is omitted or nil, use the selected frame.
+#[lisp_fn(min = "0")]
+pub fn wr_pointer_frame(frame: LispObject) -> LispObject {
+    check_window_system();
+    let frame = window_frame_live_or_selected(frame);
+    let window_id = match EVENT_LOOP.lock().pointer_window() {
+        Some(window_id) => window_id,
+        None => return Qnil,
+    };
+
+    let dpyinfo = frame.wr_display_info();
+    let dpyinfo = dpyinfo.get_inner();
+
+    match dpyinfo.outputs.get(&window_id) {
+        Some(output) => output.get_frame().into(),
+        None => Qnil,
+    }
+}
+
+/// Return where the mouse pointer is in FRAME's window, as (X . Y) in
+/// logical pixels from the top left corner of the window's inside.
+/// This is where the pointer last moved, whether or not Emacs has read
+/// that motion yet.
+/// Return nil if the pointer isn't over the window, or FRAME has been
+/// deleted.
+/// If FRAME is omitted or nil, use the selected frame.
+#[lisp_fn(min = "0")]
+pub fn wr_pointer_position(frame: LispObject) -> LispObject {
+    let position = live_wr_frame(frame).and_then(|frame| {
+        let window_id = frame.wr_output().get_window().id();
+        EVENT_LOOP.lock().pointer_position(&window_id)
+    });
+
+    match position {
+        Some(position) => LispObject::cons(
+            position.x.round() as EmacsInt,
+            position.y.round() as EmacsInt,
+        ),
+        None => Qnil,
+    }
+}
+
+/// Return the work area of the monitor FRAME is on: the part of it not
+/// covered by panels, docks or taskbars.  The value is a list
+/// (X Y WIDTH HEIGHT) in physical pixels, or in logical pixels if
+/// LOGICAL is non-nil.  Where the window system doesn't tell, as on
+/// Wayland, this is the whole monitor.  If FRAME's monitor isn't known,
+/// use the primary monitor.
+/// Return nil if FRAME has been deleted.
+/// If FRAME is omitted or nil, use the selected frame.
+#[lisp_fn(min = "0")]
+pub fn wr_monitor_work_area(frame: LispObject, logical: bool) -> LispObject {
+    let frame = match live_wr_frame(frame) {
+        Some(frame) => frame,
+        None => return Qnil,
+    };
+
+    let monitor = frame.wr_output().get_window().current_monitor();
+
+    let event_loop = EVENT_LOOP.lock();
+    let monitor = monitor.unwrap_or_else(|| event_loop.get_primary_monitor());
+    let work_area = work_area(&monitor, event_loop.platform());
+    drop(event_loop);
+
+    let (x, y, width, height) = if logical {
+        let scale_factor = monitor.scale_factor();
+        let position = work_area.position.to_logical::<i32>(scale_factor);
+        let size = work_area.size.to_logical::<u32>(scale_factor);
+        (position.x, position.y, size.width, size.height)
+    } else {
+        let WorkArea { position, size } = work_area;
+        (position.x, position.y, size.width, size.height)
+    };
+
+    list!(
+        x as EmacsInt,
+        y as EmacsInt,
+        width as EmacsInt,
+        height as EmacsInt
+    )
+}
+
+/// Return an identifier of the monitor FRAME is on, a string, that
+/// Lisp can save to find the same monitor in a later session, say
+/// through the `wr-monitor' frame parameter.  It is made from the
+/// monitor's name, position and size, so it changes when monitors are
+/// rearranged or change resolution, and the window system may not keep
+/// those the same across sessions either.  Monitors alike in all three,
+/// as cloned displays, are told apart by their order.  If FRAME's
+/// monitor isn't known, use the primary monitor.
+/// Return nil if FRAME has been deleted.
+/// If FRAME is omitted or nil, use the selected frame.
+#[lisp_fn(min = "0")]
+pub fn wr_monitor_id(frame: LispObject) -> LispObject {
+    check_window_system();
+    let frame = match live_wr_frame(frame) {
+        Some(frame) => frame,
+        None => return Qnil,
+    };
+
+    let monitor = frame.wr_output().get_window().current_monitor();
+
+    let event_loop = EVENT_LOOP.lock();
+    let monitor = monitor.unwrap_or_else(|| event_loop.get_primary_monitor());
+    let monitors: Vec<_> = event_loop.get_available_monitors().collect();
+    drop(event_loop);
+
+    let ids = monitor_ids(&monitors);
+    match monitors.iter().position(|other| *other == monitor) {
+        Some(index) => ids[index].as_str().into(),
+        None => Qnil,
+    }
+}
+
+/// Return the name the window system gives the monitor FRAME is on, or
+/// nil if it gives none.  Names need not be unique; see `wr-monitor-id'.
+/// If FRAME's monitor isn't known, use the primary monitor.
+/// Return nil if FRAME has been deleted.
+/// If FRAME is omitted or nil, use the selected frame.
+#[lisp_fn(min = "0")]
+pub fn wr_monitor_name(frame: LispObject) -> LispObject {
+    check_window_system();
+    let frame = match live_wr_frame(frame) {
+        Some(frame) => frame,
+        None => return Qnil,
+    };
+
+    let monitor = frame.wr_output().get_window().current_monitor();
+    let monitor = monitor.unwrap_or_else(|| EVENT_LOOP.lock().get_primary_monitor());
+
+    match monitor.name() {
+        Some(name) => name.as_str().into(),
+        None => Qnil,
+    }
+}
+
+/// Flash FRAME as the visible bell does, for `wr-visual-bell-duration'
+/// milliseconds.  If ATTENTION is non-nil, also ask the window system to
+/// draw the user's attention to FRAME, e.g. by bouncing its dock or
+/// taskbar icon, until it next gets focus.
+/// Do nothing if FRAME has been deleted.
+/// If FRAME is omitted or nil, use the selected frame.
+#[lisp_fn(min = "0")]
+pub fn wr_visual_bell(frame: LispObject, attention: bool) {
+    let frame = match live_wr_frame(frame) {
+        Some(frame) => frame,
+        None => return,
+    };
+    let mut output = frame.wr_output();
+
+    if attention {
+        output.request_user_attention(UserAttentionType::Critical);
+    }
+
+    if !frame.is_occluded() {
+        output.flash(visual_bell_duration());
+    }
+}
+
+/// How long the visible bell flashes a frame.
+pub fn visual_bell_duration() -> Duration {
+    let millis = unsafe { globals.Vwr_visual_bell_duration }
+        .as_natnum()
+        .unwrap_or(DEFAULT_VISUAL_BELL_DURATION);
+
+    Duration::from_millis(millis as u64)
+}
+
+const DEFAULT_VISUAL_BELL_DURATION: EmacsUint = 150;
+
+/// Return an image of what FRAME displays, as PNG data in a unibyte
+/// string.  X, Y, WIDTH and HEIGHT select a rectangle of FRAME in
+/// physical pixels to capture instead of the whole frame; the rectangle
+/// is clipped to FRAME.  X and Y default to 0, WIDTH and HEIGHT to the
+/// rest of FRAME.
+/// Return nil if FRAME has been deleted, hasn't been displayed yet, or
+/// the rectangle lies outside of it.
+/// If FRAME is omitted or nil, use the selected frame.
+#[lisp_fn(min = "0")]
+pub fn wr_capture_frame(
+    frame: LispObject,
+    x: LispObject,
+    y: LispObject,
+    width: LispObject,
+    height: LispObject,
+) -> LispObject {
+    let frame = match live_wr_frame(frame) {
+        Some(frame) => frame,
+        None => return Qnil,
+    };
+    let mut output = frame.wr_output();
+
+    let fixnum_or = |value: LispObject, default: EmacsInt| {
+        if value.is_nil() {
+            default
+        } else {
+            value.as_fixnum_or_error()
+        }
+    };
+
+    let size = output.get_window().inner_size();
+    let x = fixnum_or(x, 0);
+    let y = fixnum_or(y, 0);
+    let width = fixnum_or(width, size.width as EmacsInt - x);
+    let height = fixnum_or(height, size.height as EmacsInt - y);
+
+    let rect = DeviceIntRect::from_origin_and_size(
+        DeviceIntPoint::new(x as i32, y as i32),
+        DeviceIntSize::new(width as i32, height as i32),
+    );
+
+    let image = match output.read_pixels(rect) {
+        Ok(Some(image)) => image,
+        Ok(None) => return Qnil,
+        Err(err) => error!("Failed to read back frame: {:?}", err),
+    };
+
+    let mut png = Vec::new();
+    if let Err(err) = DynamicImage::ImageRgba8(image).write_to(&mut png, ImageOutputFormat::Png) {
+        error!("Failed to encode frame capture: {}", err);
+    }
+
+    unsafe { make_unibyte_string(png.as_ptr() as *const libc::c_char, png.len() as isize) }
+}
+
+/// Wait for the next input event from the window system and return it,
+/// as `read-event' does.  Events that arrived along with it are left for
+/// Emacs to read later, and window events that aren't input, such as
+/// resizes, are handled as they are read.
+/// TIMEOUT is the longest time to wait, in seconds; nil means to wait
+/// as long as it takes.  Return nil if no input arrives in time, or if
+/// Emacs is already waiting for input on this thread.
+#[lisp_fn(min = "0")]
+pub fn wr_read_event(timeout: LispObject) -> LispObject {
+    check_window_system();
+    let timeout = if timeout.is_nil() {
+        None
+    } else if let Some(seconds) = timeout.as_fixnum() {
+        Some(Duration::from_secs(seconds.max(0) as u64))
+    } else if unsafe { FLOATP(timeout) } {
+        // NaN becomes 0 and overlong waits a day, which is as good as
+        // forever here.
+        let seconds = unsafe { XFLOAT_DATA(timeout) }.max(0.0).min(86400.0);
+        Some(Duration::from_secs_f64(seconds))
+    } else {
+        wrong_type!(Qnumberp, timeout)
+    };
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+    let dpyinfo = check_x_display_info(Qnil).get_inner();
+
+    loop {
+        let timeout = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        if !wait_for_events(timeout) {
+            return Qnil;
+        }
+
+        let e = EVENT_BUFFER.lock().remove(0);
+        SELECT_STATS.events_read(1);
+
+        let mut events = Vec::new();
+        translate_input_event(e, dpyinfo.terminal, dpyinfo, &mut |iev| events.push(iev));
+
+        // Return the first event made from E itself, rather than reading
+        // the keyboard buffer, which may hold older input.
+        let mut events = events.into_iter();
+        if let Some(mut iev) = events.next() {
+            for mut rest in events {
+                unsafe { kbd_buffer_store_event_hold(&mut rest, ptr::null_mut()) };
+            }
+
+            return unsafe { make_lispy_event(&mut iev) };
+        }
+    }
+}
+
+/// Return the window system webrender frames are displayed on, one of
+/// the symbols `x11', `wayland', `macos' or `windows'.
+#[lisp_fn]
+pub fn wr_window_system_type() -> LispObject {
+    check_window_system();
+    let name = CString::new(EVENT_LOOP.lock().platform().name()).unwrap();
+
+    unsafe { intern(name.as_ptr()) }
+}
+
+/// Return the hardware scancode of the last key pressed on FRAME's display.
+/// Unlike the key symbol, the scancode identifies the physical key, so
+/// keys that produce the same symbol can be told apart.
+/// Return nil if no key has been pressed yet.
+/// If FRAME is omitted or nil, use the selected frame.
+#[lisp_fn(min = "0")]
+pub fn wr_last_key_scancode(frame: LispObject) -> LispObject {
+    let frame = window_frame_live_or_selected(frame);
+    let dpyinfo = frame.wr_display_info();
+
+    match dpyinfo.get_inner().input_processor.last_scancode() {
+        Some(scancode) => (scancode as EmacsInt).into(),
+        None => Qnil,
+    }
+}
+
+/// Set the key repeat DELAY and INTERVAL, both in milliseconds.
+/// While a key is held, it repeats after DELAY and then every INTERVAL,
+/// regardless of the platform's own repeat settings.
+/// If both are nil, follow the platform's key repeat again.
+#[lisp_fn(min = "0")]
+pub fn wr_set_key_repeat(delay: LispObject, interval: LispObject) {
+    check_window_system();
+    let rate = if delay.is_nil() && interval.is_nil() {
+        None
+    } else {
+        let delay = delay.as_natnum_or_error() as u64;
+        let interval = interval.as_natnum_or_error() as u64;
+
+        if interval == 0 {
+            error!("Key repeat interval must be positive");
+        }
+
+        Some((
+            Duration::from_millis(delay),
+            Duration::from_millis(interval),
+        ))
+    };
+
+    EVENT_LOOP.lock().set_key_repeat(rate);
+}
+
+// The modifier named by SYMBOL: `shift', `control', `alt' (Alt or
+// Option) or `super' (Super, Command or the Windows key).
+fn modifier_from_symbol(symbol: LispObject) -> ModifiersState {
+    let name: LispStringRef = symbol.force_symbol().symbol_name().into();
+
+    match name.to_utf8().as_str() {
+        "shift" => ModifiersState::SHIFT,
+        "control" => ModifiersState::CTRL,
+        "alt" => ModifiersState::ALT,
+        "super" => ModifiersState::LOGO,
+        name => error!("Unknown modifier: {}", name),
+    }
+}
+
+/// Remap modifier keys before Emacs decodes key events.
+/// REMAP is an alist of (FROM . TO) pairs, where FROM and TO are one of
+/// the symbols `shift', `control', `alt' (the Alt or Option key) and
+/// `super' (the Super, Command or Windows key).  Modifiers not in REMAP
+/// stand for themselves.  For instance, '((alt . super) (super . alt))
+/// swaps Alt and Super.
+/// If REMAP is nil, every modifier stands for itself again.
+#[lisp_fn]
+pub fn wr_set_modifier_remap(remap: LispObject) {
+    check_window_system();
+    let mut modifier_remap = ModifierRemap::default();
+
+    for pair in remap.iter_cars(LispConsEndChecks::on, LispConsCircularChecks::on) {
+        let (from, to): (LispObject, LispObject) = pair.into();
+        modifier_remap.set(modifier_from_symbol(from), modifier_from_symbol(to));
+    }
+
+    EVENT_LOOP.lock().set_modifier_remap(modifier_remap);
+}
+
+/// Return the key repeat as (DELAY . INTERVAL) in milliseconds.
+/// Return nil if key repeat follows the platform.
+#[lisp_fn]
+pub fn wr_key_repeat() -> LispObject {
+    check_window_system();
+    match EVENT_LOOP.lock().key_repeat() {
+        Some((delay, interval)) => (
+            delay.as_millis() as EmacsInt,
+            interval.as_millis() as EmacsInt,
+        )
+            .into(),
+        None => Qnil,
+    }
+}
+
+/// Turn tracking of input devices on if TRACK is non-nil, off otherwise.
+/// While tracking, raw device events are passed to Emacs, so devices
+/// appear in `wr-input-devices' once used, and mouse clicks and wheel
+/// events record their device in `last-event-device'.  Raw events arrive
+/// at a high rate, so tracking is off by default.  Turning it off
+/// forgets the devices seen so far.
+#[lisp_fn]
+pub fn wr_set_track_input_devices(track: bool) {
+    devices::set_tracking(track);
+}
+
+/// Return the names of the input devices seen since tracking was turned
+/// on with `wr-set-track-input-devices', in the order they were first
+/// used.  Each physical device, like a mouse or a pen, has a name of its
+/// own, as used in `last-event-device'.
+#[lisp_fn]
+pub fn wr_input_devices() -> LispObject {
+    devices::device_names()
+        .iter()
+        .rev()
+        .fold(Qnil, |list, name| LispObject::cons(name.as_str(), list))
+}
+
+/// Limit how often pointer motion, scrolling and resizing are reported
+/// to Emacs to RATE times a second, which limits how often they cause
+/// redisplay.  Input arriving sooner is reported once the limit allows.
+/// Keys and mouse clicks are always reported right away.
+/// If RATE is nil, input is reported as soon as it arrives.
+#[lisp_fn]
+pub fn wr_set_redraw_rate_limit(rate: LispObject) {
+    check_window_system();
+    let interval = if rate.is_nil() {
+        None
+    } else {
+        let rate = rate.as_natnum_or_error();
+
+        if rate == 0 {
+            error!("Redraw rate limit must be positive");
+        }
+
+        Some(Duration::from_secs_f64(1.0 / rate as f64))
+    };
+
+    EVENT_LOOP.lock().set_report_interval(interval);
+}
+
+/// Return the limit set by `wr-set-redraw-rate-limit', in reports a
+/// second, or nil if there is no limit.
+#[lisp_fn]
+pub fn wr_redraw_rate_limit() -> LispObject {
+    check_window_system();
+    match EVENT_LOOP.lock().report_interval() {
+        Some(interval) => ((1.0 / interval.as_secs_f64()).round() as EmacsInt).into(),
+        None => Qnil,
+    }
+}
+
+/// Hold input back for MILLISECONDS after the first of it arrives, so
+/// that Emacs reads a burst of input in one go rather than waking up and
+/// redisplaying for each event.  This trades a little latency for fewer
+/// wakeups.  Keys are reported right away, unless KEYS is non-nil.
+/// If MILLISECONDS is nil or 0, input is reported as soon as it arrives,
+/// as by default.
+#[lisp_fn(min = "1")]
+pub fn wr_set_input_batch_window(milliseconds: LispObject, keys: LispObject) {
+    check_window_system();
+    let batch = if milliseconds.is_nil() {
+        None
+    } else {
+        match milliseconds.as_natnum_or_error() {
+            0 => None,
+            milliseconds => Some(Duration::from_millis(milliseconds as u64)),
+        }
+    };
+
+    EVENT_LOOP.lock().set_input_batch(batch, keys.is_not_nil());
+}
+
+/// Return the window set by `wr-set-input-batch-window', in
+/// milliseconds, or 0 if input isn't batched.
+#[lisp_fn]
+pub fn wr_input_batch_window() -> LispObject {
+    check_window_system();
+    let (batch, _) = EVENT_LOOP.lock().input_batch();
+    (batch.map_or(0, |batch| batch.as_millis()) as EmacsInt).into()
+}
+
+/// Guard against the window system event loop getting stuck.  If it
+/// keeps running SECONDS past the time Emacs asked it to return by, it
+/// is forced to return, and the error is logged.
+/// If SECONDS is nil, the event loop is never forced to return, as by
+/// default.  This only matters on macOS and Windows, where the event
+/// loop runs on Emacs's thread.
+#[lisp_fn]
+pub fn wr_set_run_loop_watchdog(seconds: LispObject) {
+    check_window_system();
+    let interval = if seconds.is_nil() {
+        None
+    } else {
+        let seconds = seconds.as_natnum_or_error();
+
+        if seconds == 0 {
+            error!("Watchdog interval must be positive");
+        }
+
+        Some(Duration::from_secs(seconds as u64))
+    };
+
+    EVENT_LOOP.lock().set_watchdog_interval(interval);
+}
+
+/// Return the interval set by `wr-set-run-loop-watchdog', in seconds, or
+/// nil if there is no watchdog.
+#[lisp_fn]
+pub fn wr_run_loop_watchdog() -> LispObject {
+    check_window_system();
+    match EVENT_LOOP.lock().watchdog_interval() {
+        Some(interval) => (interval.as_secs() as EmacsInt).into(),
+        None => Qnil,
+    }
+}
+
+/// Return counters of how Emacs waited for input, as an alist:
+///  `calls': times Emacs waited for input or descriptors;
+///  `events': window events Emacs read;
+///  `fallbacks': waits that found no window input and only polled
+///   Emacs's descriptors afterwards, left out where window events are
+///   read on a thread of their own, as on X and Wayland, since Emacs's
+///   descriptors are then waited on together with it;
+///  `input-wakeups': waits cut short because window input arrived;
+///  `waited': seconds spent waiting, a float.
+/// Many calls and fallbacks with little time waited mean Emacs is busy
+/// waiting rather than idle.  The counters go back to startup, or to the
+/// last call with RESET non-nil, which starts them over from zero.
+#[lisp_fn(min = "0")]
+pub fn wr_event_loop_stats(reset: bool) -> LispObject {
+    let counts = if reset {
+        SELECT_STATS.take()
+    } else {
+        SELECT_STATS.counts()
+    };
+
+    let waited = unsafe { make_float(counts.waited.as_secs_f64()) };
+    let mut counters = vec![
+        ("waited", waited),
+        ("input-wakeups", (counts.input_wakeups as EmacsInt).into()),
+    ];
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    counters.push(("fallbacks", (counts.fallbacks as EmacsInt).into()));
+    counters.push(("events", (counts.events as EmacsInt).into()));
+    counters.push(("calls", (counts.calls as EmacsInt).into()));
+
+    counters.iter().fold(Qnil, |alist, &(name, value)| {
+        let name = CString::new(name).unwrap();
+        let key = unsafe { intern(name.as_ptr()) };
+        LispObject::cons(LispObject::cons(key, value), alist)
+    })
+}
+
+/// Choose which kinds of window events wake Emacs.  EVENTS is a list of
+/// the symbols `resize', `focus', `occlusion', `mouse-button',
+/// `mouse-motion', `mouse-crossing' and `mouse-wheel'; events of kinds
+/// left out are dropped as they arrive.  Keys and requests to close a
+/// frame always wake Emacs.  For instance, '(resize focus occlusion)
+/// leaves out every mouse event.
+/// If EVENTS is t, every kind of event wakes Emacs, as by default.
+#[lisp_fn]
+pub fn wr_set_wakeup_events(events: LispObject) {
+    check_window_system();
+    let wakeup_events = if events.is_t() {
+        WakeupEvents::default()
+    } else {
+        let mut wakeup_events = WakeupEvents::none();
+
+        for event in events.iter_cars(LispConsEndChecks::on, LispConsCircularChecks::on) {
+            let name: LispStringRef = event.force_symbol().symbol_name().into();
+            let name = name.to_utf8();
+
+            if !wakeup_events.enable(&name) {
+                error!("Unknown kind of event: {}", name);
+            }
+        }
+
+        wakeup_events
+    };
+
+    EVENT_LOOP.lock().set_wakeup_events(wakeup_events);
+}
+
+/// Return the list of kinds of window events that wake Emacs, as set by
+/// `wr-set-wakeup-events'.
+#[lisp_fn]
+pub fn wr_wakeup_events() -> LispObject {
+    check_window_system();
+    let wakeup_events = EVENT_LOOP.lock().wakeup_events();
+
+    let names: Vec<_> = wakeup_events.names().collect();
+    names.into_iter().rev().fold(Qnil, |list, name| {
+        let name = CString::new(name).unwrap();
+        LispObject::cons(unsafe { intern(name.as_ptr()) }, list)
+    })
+}
+
+/// Set the WebRender debug flags of every frame to FLAGS, a list of the
+/// symbols `profiler', `render-targets', `texture-cache',
+/// `gpu-time-queries', `gpu-sample-queries', `disable-batching',
+/// `echo-driver-messages', `gpu-cache', `picture-caching' and
+/// `show-overdraw'.  Flags left out are cleared, so nil clears them all.
+/// Unknown symbols are reported and ignored.  Frames created later get
+/// the same flags.
+#[lisp_fn]
+pub fn wr_set_debug_flags(flags: LispObject) {
+    check_window_system();
+    let mut debug_flags = DebugFlags::empty();
+
+    for flag in flags.iter_cars(LispConsEndChecks::on, LispConsCircularChecks::on) {
+        let name: LispStringRef = flag.force_symbol().symbol_name().into();
+        let name = name.to_utf8();
+
+        match debug_flag(&name) {
+            Some(flag) => debug_flags |= flag,
+            None => message!("Ignoring unknown WebRender debug flag: {}", name),
+        }
+    }
+
+    EVENT_LOOP.lock().set_debug_flags(debug_flags);
+
+    for frame in all_frames() {
+        if frame.is_live() && frame.output_method() == output_method::output_wr {
+            frame.wr_output().set_debug_flags(debug_flags);
+        }
+    }
+}
+
+/// Make the presents of every frame wait for the vertical blank of the
+/// display if VSYNC is non-nil, so that redisplay doesn't tear, or have
+/// them present at once if it is nil.  Frames created later are set the
+/// same way.  Until this is called, it is up to the graphics driver.
+/// Return nil if the swap interval of some frame's surface can't be set,
+/// as on macOS, so it may still tear; otherwise return t.
+#[lisp_fn]
+pub fn wr_set_vsync(vsync: bool) -> bool {
+    check_window_system();
+    EVENT_LOOP.lock().set_vsync(vsync);
+
+    let mut all_set = true;
+    for frame in all_frames() {
+        if frame.is_live() && frame.output_method() == output_method::output_wr {
+            all_set &= frame.wr_output().set_vsync(vsync);
+        }
+    }
+
+    all_set
+}
+
+/// Return non-nil if presents wait for the vertical blank, as set by
+/// `wr-set-vsync'.
+#[lisp_fn]
+pub fn wr_vsync() -> bool {
+    check_window_system();
+    EVENT_LOOP.lock().vsync()
+}
+
+/// Return the list of WebRender debug flags set by `wr-set-debug-flags'.
+#[lisp_fn]
+pub fn wr_debug_flags() -> LispObject {
+    check_window_system();
+    let debug_flags = EVENT_LOOP.lock().debug_flags();
+
+    let names: Vec<_> = debug_flag_names(debug_flags).collect();
+    names.into_iter().rev().fold(Qnil, |list, name| {
+        let name = CString::new(name).unwrap();
+        LispObject::cons(unsafe { intern(name.as_ptr()) }, list)
+    })
+}
+
+fn syms_of_wrfont() {
+    unsafe {
+        register_font_driver(&FONT_DRIVER.0, ptr::null_mut());
+    }
+}
+
+#[no_mangle]
+#[allow(unused_doc_comments)]
+pub extern "C" fn syms_of_wrterm() {
+    // pretend webrender as a X gui backend, so we can reuse the x-win.el logic
+    def_lisp_sym!(Qwr, "wr");
+    unsafe {
+        Fprovide(Qwr, Qnil);
+    }
+
+    // Frame parameter naming the monitor a new frame opens on, as an
+    // index into `display-monitor-attributes-list' or an identifier
+    // returned by `wr-monitor-id'.
+    def_lisp_sym!(Qwr_monitor, "wr-monitor");
+    // Frame parameters limiting the size of a new frame's window, as
+    // (WIDTH . HEIGHT) in logical pixels.
+    def_lisp_sym!(Qwr_min_size, "wr-min-size");
+    def_lisp_sym!(Qwr_max_size, "wr-max-size");
+
+    #[cfg(feature = "capture")]
+    {
+        let wr_capture_sym =
+            CString::new("wr-capture").expect("Failed to create string for intern function call");
+        def_lisp_sym!(Qwr_capture, "wr-capture");
+        unsafe {
+            Fprovide(
+                emacs::bindings::intern_c_string(wr_capture_sym.as_ptr()),
+                Qnil,
+            );
+        }
+    }
+
+    let wr_keysym_table = unsafe {
+        make_hash_table(
+            hashtest_eql.clone(),
+            900,
+            DEFAULT_REHASH_SIZE,
+            DEFAULT_REHASH_THRESHOLD,
+            Qnil,
+            false,
+        )
+    };
+
+    // Hash table of character codes indexed by X keysym codes.
+    #[rustfmt::skip]
+    defvar_lisp!(Vwr_keysym_table, "wr-keysym-table", wr_keysym_table);
+
+    // Which toolkit scroll bars Emacs uses, if any.
+    // A value of nil means Emacs doesn't use toolkit scroll bars.
+    // With the X Window system, the value is a symbol describing the
+    // X toolkit.  Possible values are: gtk, motif, xaw, or xaw3d.
+    // With MS Windows or Nextstep, the value is t.
+    #[rustfmt::skip]
+    defvar_lisp!(Vx_toolkit_scroll_bars, "x-toolkit-scroll-bars", Qnil);
+
+    // Non-nil means make use of UNDERLINE_POSITION font properties.
+    // A value of nil means ignore them.  If you encounter fonts with bogus
+    // UNDERLINE_POSITION font properties, set this to nil.  You can also use
+    // `underline-minimum-offset' to override the font's UNDERLINE_POSITION for
+    // small font display sizes.
+    #[rustfmt::skip]
+    defvar_bool!(Vx_use_underline_position_properties, "x-use-underline-position-properties", true);
+
+    // Non-nil means to draw the underline at the same place as the descent line.
+    // (If `line-spacing' is in effect, that moves the underline lower by
+    // that many pixels.)
+    // A value of nil means to draw the underline according to the value of the
+    // variable `x-use-underline-position-properties', which is usually at the
+    // baseline level.  The default value is nil.
+    #[rustfmt::skip]
+    defvar_bool!(Vx_underline_at_descent_line, "x-underline-at-descent-line", false);
+
+    // Whether to enable X clipboard manager support.
+    // If non-nil, then whenever Emacs is killed or an Emacs frame is deleted
+    // while owning the X clipboard, the clipboard contents are saved to the
+    // clipboard manager if one is present.
+    #[rustfmt::skip]
+    defvar_lisp!(Vx_select_enable_clipboard_manager, "x-select-enable-clipboard-manager", Qt);
+
+    // Alist of (ID . CALLBACK) for the clipboard reads started by
+    // `wr-read-clipboard-async' that haven't finished yet.
+    #[rustfmt::skip]
+    defvar_lisp!(Vwr_clipboard_pending_reads, "wr--clipboard-pending-reads", Qnil);
+
+    // Normal hook run after the clipboard changed while it is watched.
+    // See `wr-set-clipboard-watch-interval'.
+    def_lisp_sym!(Qwr_clipboard_change_hook, "wr-clipboard-change-hook");
+    #[rustfmt::skip]
+    defvar_lisp!(Vwr_clipboard_change_hook, "wr-clipboard-change-hook", Qnil);
+
+    // Abnormal hook run with a frame after the theme of its window
+    // changed.  See `wr-frame-theme'.
+    def_lisp_sym!(Qwr_theme_change_functions, "wr-theme-change-functions");
+    #[rustfmt::skip]
+    defvar_lisp!(Vwr_theme_change_functions, "wr-theme-change-functions", Qnil);
+
+    // Abnormal hook run with a frame after the GL context of its window
+    // was lost, as on a GPU reset, or the system resumed from a suspend,
+    // and the context was recreated.  The frame is redrawn and its images
+    // reloaded before, but its layers are left empty; see `wr-add-layer'.
+    def_lisp_sym!(Qwr_display_reset_functions, "wr-display-reset-functions");
+    #[rustfmt::skip]
+    defvar_lisp!(Vwr_display_reset_functions, "wr-display-reset-functions", Qnil);
+
+    // Milliseconds the visible bell flashes a frame for.
+    #[rustfmt::skip]
+    defvar_lisp!(Vwr_visual_bell_duration, "wr-visual-bell-duration", (DEFAULT_VISUAL_BELL_DURATION as EmacsInt).into());
 
     syms_of_wrfont();
 }
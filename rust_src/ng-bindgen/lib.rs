@@ -1,21 +1,20 @@
 #[macro_use]
 extern crate lazy_static;
 
+use std::collections::HashSet;
 use std::env;
 use std::ffi::OsStr;
+use std::fmt;
 use std::fs;
 use std::fs::File;
-use std::fs::OpenOptions;
 use std::io;
 use std::io::{BufRead, BufReader, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process;
 
 use cargo_toml::Manifest;
 use regex::Regex;
 
-static C_NAME: &str = "c_name = \"";
-
 /// Exit with error $code after printing the $fmtstr to stderr
 macro_rules! fail_with_msg {
     ($code:expr, $modname:expr, $lineno:expr, $($arg:expr),*) => {{
@@ -25,6 +24,10 @@ macro_rules! fail_with_msg {
     }};
 }
 
+// The most arguments a subr that isn't MANY or UNEVALLED can take.
+const MAX_FIXED_ARGS: usize = 8;
+
+#[cfg_attr(test, derive(Debug))]
 pub struct LintMsg {
     modname: String,
     lineno: u32,
@@ -45,6 +48,17 @@ impl LintMsg {
     }
 }
 
+impl fmt::Display for LintMsg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "In {} on line {}\n{}",
+            self.modname, self.lineno, self.msg
+        )
+    }
+}
+
+#[cfg_attr(test, derive(Debug))]
 pub enum BuildError {
     IOError(io::Error),
     Lint(LintMsg),
@@ -63,12 +77,22 @@ impl From<LintMsg> for BuildError {
 }
 
 #[derive(Clone)]
+#[cfg_attr(test, derive(Debug))]
 struct ModuleInfo {
     pub name: String,
     pub path: PathBuf,
 }
 
 impl ModuleInfo {
+    /// A module named NAME whose source isn't read from a file.
+    #[cfg(test)]
+    fn synthetic(name: &str) -> ModuleInfo {
+        ModuleInfo {
+            name: name.to_string(),
+            path: PathBuf::from(format!("{}.rs", name)),
+        }
+    }
+
     pub fn from_path(mod_path: &PathBuf) -> Option<ModuleInfo> {
         // in order to parse correctly, determine where the code lives.
         // For submodules that will be in a mod.rs file.
@@ -94,11 +118,23 @@ impl ModuleInfo {
     }
 }
 
+#[cfg_attr(test, derive(Debug))]
 struct ModuleData {
     pub info: ModuleInfo,
     pub c_exports: Vec<(Option<String>, String)>,
-    pub lisp_fns: Vec<(Option<String>, String)>,
+    /// The cfg, C name, Lisp symbol name and line of each lisp_fn.
+    pub lisp_fns: Vec<(Option<String>, String, String, u32)>,
     pub protected_statics: Vec<String>,
+    /// Lines of `#[no_mangle]` and `#[lisp_fn]` attributes skipped for
+    /// being indented, with the attribute.
+    pub skipped_exports: Vec<(u32, &'static str)>,
+    /// Names of the module's top-level async functions.
+    pub async_fns: HashSet<String>,
+    /// The function and line of each `#[async_stream]`, whose `call_`
+    /// lisp_fn calls that function.
+    pub async_streams: Vec<(String, u32)>,
+    /// The lint problems of the module, if it was parsed in lint mode.
+    pub lints: Vec<LintMsg>,
 }
 
 impl ModuleData {
@@ -108,6 +144,10 @@ impl ModuleData {
             c_exports: Vec::new(),
             lisp_fns: Vec::new(),
             protected_statics: Vec::new(),
+            skipped_exports: Vec::new(),
+            async_fns: HashSet::new(),
+            async_streams: Vec::new(),
+            lints: Vec::new(),
         }
     }
 }
@@ -115,6 +155,9 @@ impl ModuleData {
 struct ModuleParser<'a> {
     info: &'a ModuleInfo,
     lineno: u32,
+    // In lint mode, the lint problems found so far, which are collected
+    // instead of failing on the first.
+    lints: Option<Vec<LintMsg>>,
 }
 
 impl<'a> ModuleParser<'a> {
@@ -122,124 +165,194 @@ impl<'a> ModuleParser<'a> {
         ModuleParser {
             info: mod_info,
             lineno: 0,
+            lints: None,
+        }
+    }
+
+    /// A parser in lint mode, which collects every lint problem of the
+    /// module into its ModuleData.
+    pub fn linting(mod_info: &'a ModuleInfo) -> Self {
+        ModuleParser {
+            lints: Some(Vec::new()),
+            ..ModuleParser::new(mod_info)
         }
     }
 
     pub fn run(&mut self, in_file: impl BufRead) -> Result<ModuleData, BuildError> {
         let mut mod_data = ModuleData::new(self.info.clone());
         let mut reader = in_file.lines();
-        let mut has_include = false;
+        let mut includes: Vec<String> = Vec::new();
         let mut preceding_cfg: Option<String> = None;
 
         while let Some(next) = reader.next() {
-            let line = next?;
+            let mut line = next?;
             self.lineno += 1;
 
             if line.starts_with(' ') {
+                // Nested functions can't be exported, but an indented
+                // export would only show up as a link error.  The
+                // lisp_fn macro defines statics next to the function, so
+                // lisp_fns can't be methods in an impl block either.
+                let cfg_attr = parse_cfg_attr(line.trim_start());
+                let attr = cfg_attr
+                    .as_ref()
+                    .map_or(line.trim_start(), |(_, attr)| attr.as_str());
+                if is_attribute(attr, "no_mangle") {
+                    mod_data.skipped_exports.push((self.lineno, "#[no_mangle]"));
+                } else if is_attribute(attr, "lisp_fn") {
+                    mod_data.skipped_exports.push((self.lineno, "#[lisp_fn]"));
+                }
                 continue;
             }
 
+            // `#[cfg_attr(COND, lisp_fn)]` exports like `#[lisp_fn]` under
+            // `#[cfg(COND)]`, and so for no_mangle.  Other cfg_attrs are
+            // just attributes, whatever they apply.
+            if is_attribute(&line, "cfg_attr") {
+                let (attr, signature) = self.read_attribute(line, &mut reader)?;
+                let (cond, export) = match parse_cfg_attr(&attr) {
+                    Some(cfg_attr) => cfg_attr,
+                    None => continue,
+                };
+
+                preceding_cfg = Some(add_cfg(preceding_cfg, &cond));
+                line = match signature {
+                    Some(signature) => format!("{} {}", export, signature),
+                    None => export,
+                };
+            }
+
             if line.starts_with("declare_GC_protected_static!") {
                 let var = self.parse_gc_protected_static(&line)?;
                 mod_data.protected_statics.push(var);
-            } else if line.starts_with("#[no_mangle]") {
-                if let Some(next) = reader.next() {
-                    let line = next?;
-
-                    if let Some(func) = self.parse_c_export(&line, None)? {
-                        self.lint_nomangle(&line)?;
-                        mod_data.c_exports.push((preceding_cfg, func));
-                    }
+            } else if is_attribute(&line, "no_mangle") {
+                // Statics are exported too, so unlike for lisp_fns there
+                // may be no signature to read up to.
+                let line = match self.read_attribute(line, &mut reader)?.1 {
+                    Some(signature) => signature,
+                    None => self.read_line(&mut reader)?,
+                };
 
-                    preceding_cfg = None;
-                } else {
-                    self.fail(1, "unexpected end of file");
+                let export = self.parse_c_export(&line, None);
+                if let Some(func) = self.collect(export)?.flatten() {
+                    let lint = self.lint_nomangle(&line);
+                    self.collect(lint)?;
+                    mod_data.c_exports.push((preceding_cfg, func));
                 }
+
+                preceding_cfg = None;
             } else if line.starts_with("#[cfg") {
                 preceding_cfg = Some(line);
-            } else if line.starts_with("#[lisp_fn") {
-                let line = if line.ends_with("]") {
-                    line.clone()
-                } else {
-                    let mut line = line.clone();
-                    loop {
-                        if let Some(next) = reader.next() {
-                            let l = next?;
-                            if !l.ends_with(")]") {
-                                line += &l;
-                            } else {
-                                line += &l;
-                                break;
-                            }
-                        } else {
-                            break;
-                        }
-                    }
+            } else if is_attribute(&line, "lisp_fn") {
+                let lisp_fn_lineno = self.lineno;
+                let (attr, signature) = self.read_attribute(line, &mut reader)?;
 
-                    line
+                let (name, lisp_name) = match self.parse_names(&attr)? {
+                    Some(names) => names,
+                    None => continue,
                 };
 
-                let name = if let Some(begin) = line.find(C_NAME) {
-                    let start = begin + C_NAME.len();
-                    let end = line[start..].find('"').unwrap() + start;
-                    let name = line[start..end].to_string();
-                    if name.starts_with('$') {
-                        // Ignore macros, nothing we can do with them
-                        continue;
-                    }
+                let line = self.read_signature(signature, &mut reader)?;
+
+                let export = self.parse_c_export(&line, name);
+                if let Some(func) = self.collect(export)?.flatten() {
+                    // Named like the macro does by default.
+                    let lisp_name = lisp_name
+                        .or_else(|| get_function_name(&line).map(|name| name.replace('_', "-")))
+                        .unwrap_or_else(|| func.replace('_', "-"));
+                    let limits = self.validate_lisp_fn(lisp_fn_lineno, &attr, &line, &lisp_name);
+                    self.collect(limits)?;
+                    mod_data
+                        .lisp_fns
+                        .push((preceding_cfg, func, lisp_name, lisp_fn_lineno));
+                }
 
-                    Some(name)
-                } else {
-                    None
+                preceding_cfg = None;
+            } else if is_attribute(&line, "async_stream") {
+                let lisp_fn_lineno = self.lineno;
+                let (line, signature) = self.read_attribute(line, &mut reader)?;
+
+                let (name, lisp_name) = match self.parse_names(&line)? {
+                    Some(names) => names,
+                    None => continue,
                 };
 
-                if let Some(next) = reader.next() {
-                    let line = next?;
+                let line = self.read_signature(signature, &mut reader)?;
 
-                    if let Some(func) = self.parse_c_export(&line, name)? {
-                        mod_data.lisp_fns.push((preceding_cfg, func));
-                    }
-                } else {
-                    self.fail(1, "unexpected end of file");
+                if let Some(name) = parse_async_fn_name(&line) {
+                    mod_data.async_fns.insert(name);
                 }
 
-                preceding_cfg = None;
-            } else if line.starts_with("#[async_stream") {
-                if let Some(next) = reader.next() {
-                    let line = next?;
-
-                    if let Some(func) = self.parse_c_export(&line, None)? {
-                        let mut prefix = String::from("call_");
-                        prefix.push_str(&func);
-                        mod_data.lisp_fns.push((preceding_cfg, prefix));
-                    }
-                } else {
-                    self.fail(1, "Unexpected end of file");
+                let export = self.parse_c_export(&line, None);
+                if let Some(func) = self.collect(export)?.flatten() {
+                    mod_data.async_streams.push((func.clone(), lisp_fn_lineno));
+                    // The macro wraps the stream in a lisp_fn `call_<fn>`.
+                    let wrapper = format!("call_{}", func);
+                    let lisp_name = lisp_name.unwrap_or_else(|| wrapper.replace('_', "-"));
+                    mod_data.lisp_fns.push((
+                        preceding_cfg,
+                        name.unwrap_or(wrapper),
+                        lisp_name,
+                        lisp_fn_lineno,
+                    ));
                 }
 
                 preceding_cfg = None;
             } else if line.starts_with("include!(concat!(") {
-                has_include = true;
+                let mut line = line.clone();
+                while !line.ends_with(';') {
+                    if let Some(next) = reader.next() {
+                        line += next?.trim();
+                        self.lineno += 1;
+                    } else {
+                        break;
+                    }
+                }
+
+                includes.push(self.parse_include_path(&line)?);
             } else if line.starts_with("/*") && !line.ends_with("*/") {
                 while let Some(next) = reader.next() {
                     let line = next?;
+                    self.lineno += 1;
                     if line.ends_with("*/") {
                         break;
                     }
                 }
             } else {
+                if let Some(name) = parse_async_fn_name(&line) {
+                    mod_data.async_fns.insert(name);
+                }
+
                 preceding_cfg = None;
             }
         }
 
-        if !has_include && !(mod_data.lisp_fns.is_empty() && mod_data.protected_statics.is_empty())
-        {
-            let msg = format!(
-                "{} is missing the required include for protected statics or lisp_fn exports.",
-                path_as_str(self.info.path.file_name()).to_string()
-            );
+        if !(mod_data.lisp_fns.is_empty() && mod_data.protected_statics.is_empty()) {
+            let file_name = path_as_str(self.info.path.file_name()).to_string();
+
+            // write_lisp_fns generates exactly this file for the module.
+            let expected = format!("/out/{}_exports.rs", self.info.name);
+            if includes.is_empty() {
+                let msg = format!(
+                    "{} is missing the required include for protected statics or lisp_fn exports.",
+                    file_name
+                );
+
+                self.lint(2, msg);
+            } else if !includes.contains(&expected) {
+                let msg = format!(
+                    "{} includes the wrong file for its lisp_fn exports.\nExpected: {}\nFound: {}",
+                    file_name,
+                    expected,
+                    includes.join(", ")
+                );
+
+                self.lint(2, msg);
+            }
+        }
 
-            self.fail(2, &msg);
+        if let Some(lints) = self.lints.as_mut() {
+            mod_data.lints.append(lints);
         }
 
         Ok(mod_data)
@@ -249,6 +362,143 @@ impl<'a> ModuleParser<'a> {
         fail_with_msg!(code, &self.info.name, self.lineno, "{}", msg);
     }
 
+    // Fail with CODE for the lint problem MSG, or collect it in lint
+    // mode.
+    fn lint(&mut self, code: i32, msg: String) {
+        match self.lints.as_mut() {
+            Some(lints) => lints.push(LintMsg::new(&self.info.name, self.lineno, msg)),
+            None => self.fail(code, &msg),
+        }
+    }
+
+    // The value of RESULT, or in lint mode None once its lint problem is
+    // collected.
+    fn collect<T>(&mut self, result: Result<T, LintMsg>) -> Result<Option<T>, LintMsg> {
+        match (result, self.lints.as_mut()) {
+            (Ok(value), _) => Ok(Some(value)),
+            (Err(lint), Some(lints)) => {
+                lints.push(lint);
+                Ok(None)
+            }
+            (Err(lint), None) => Err(lint),
+        }
+    }
+
+    // The error for source that can't be parsed, saying MSG.  It isn't
+    // a lint problem, so in lint mode it is an IO error: what follows it
+    // can't be checked.
+    fn unparseable(&self, msg: &str) -> BuildError {
+        let lint = LintMsg::new(&self.info.name, self.lineno, msg.to_string());
+        match self.lints {
+            Some(_) => io::Error::new(io::ErrorKind::InvalidData, lint.to_string()).into(),
+            None => lint.into(),
+        }
+    }
+
+    // Read the rest of the attribute starting on LINE, which may go on
+    // over several lines.  Return the attribute, and the signature
+    // following it if it is on the same line.
+    fn read_attribute(
+        &mut self,
+        line: String,
+        reader: &mut impl Iterator<Item = io::Result<String>>,
+    ) -> Result<(String, Option<String>), BuildError> {
+        let (mut line, signature) = match line.find(']') {
+            Some(end) if !line[end + 1..].trim().is_empty() => (
+                line[..=end].to_string(),
+                Some(line[end + 1..].trim().to_string()),
+            ),
+            _ => (line, None),
+        };
+
+        if !line.ends_with(']') {
+            while let Some(next) = reader.next() {
+                let l = next?;
+                self.lineno += 1;
+                // Keep arguments on separate lines apart.
+                line.push(' ');
+                line += &l;
+                if l.ends_with(")]") {
+                    break;
+                }
+            }
+        }
+
+        Ok((line, signature))
+    }
+
+    // The signature following an attribute, starting with SIGNATURE if
+    // it was on the attribute's line, or else on the next line, and going
+    // on up to the `{` opening the body.  A signature spread over several
+    // lines, as by a where clause, is joined into one line.  Other
+    // attributes and comments before it, as `#[allow(...)]`, are skipped.
+    fn read_signature(
+        &mut self,
+        mut signature: Option<String>,
+        reader: &mut impl Iterator<Item = io::Result<String>>,
+    ) -> Result<String, BuildError> {
+        let mut line = loop {
+            let line = match signature.take() {
+                Some(signature) => signature,
+                None => self.read_line(reader)?,
+            };
+            let line = line.trim_start();
+
+            if line.starts_with("#[") {
+                signature = self.read_attribute(line.to_string(), reader)?.1;
+            } else if !(line.is_empty() || line.starts_with("//")) {
+                break line.to_string();
+            }
+        };
+
+        // The body can only start once the parameters have.
+        while !(line.contains('(') && (line.contains('{') || line.ends_with(';'))) {
+            let next = self.read_line(reader)?;
+            line.push(' ');
+            line += next.trim();
+        }
+
+        Ok(line)
+    }
+
+    fn read_line(
+        &mut self,
+        reader: &mut impl Iterator<Item = io::Result<String>>,
+    ) -> Result<String, BuildError> {
+        match reader.next() {
+            Some(next) => {
+                self.lineno += 1;
+                Ok(next?)
+            }
+            None => Err(self.unparseable("unexpected end of file")),
+        }
+    }
+
+    // The `c_name` and `name` arguments of the attribute LINE, or None if
+    // either is a macro variable, which can't be resolved here.
+    fn parse_names(
+        &mut self,
+        line: &str,
+    ) -> Result<Option<(Option<String>, Option<String>)>, BuildError> {
+        let name = match parse_argument(line, "c_name") {
+            Some(value) => match parse_string_literal(value) {
+                Some(name) => Some(name),
+                None => return Err(self.unparseable("malformed c_name")),
+            },
+            None => None,
+        };
+        let lisp_name = parse_lisp_name(line);
+
+        if [&name, &lisp_name]
+            .iter()
+            .any(|name| name.as_ref().map_or(false, |name| name.starts_with('$')))
+        {
+            return Ok(None);
+        }
+
+        Ok(Some((name, lisp_name)))
+    }
+
     /// Handle both no_mangle and lisp_fn functions
     fn parse_c_export(
         &mut self,
@@ -263,7 +513,19 @@ impl<'a> ModuleParser<'a> {
         }
     }
 
-    fn parse_gc_protected_static(&mut self, line: &str) -> Result<String, LintMsg> {
+    // The path an include!(concat!(...)) appends to its directory.
+    fn parse_include_path(&mut self, line: &str) -> Result<String, BuildError> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(r#"\),\s*"([^"]+)"\s*\)\);"#).unwrap();
+        }
+
+        match RE.captures(line) {
+            Some(caps) => Ok(caps[1].to_string()),
+            None => Err(self.unparseable("could not parse include path")),
+        }
+    }
+
+    fn parse_gc_protected_static(&mut self, line: &str) -> Result<String, BuildError> {
         lazy_static! {
             static ref RE: Regex = Regex::new(r#"GC_protected_static!\((.+), .+\);"#).unwrap();
         }
@@ -273,11 +535,7 @@ impl<'a> ModuleParser<'a> {
                 let name = caps[1].to_string();
                 Ok(name)
             }
-            None => Err(LintMsg::new(
-                &self.info.name,
-                self.lineno,
-                "could not parse protected static".to_string(),
-            )),
+            None => Err(self.unparseable("could not parse protected static")),
         }
     }
 
@@ -299,17 +557,78 @@ impl<'a> ModuleParser<'a> {
                         format!("\n`{}` is not public.\n{}", name, msg),
                     ))
                 } else {
-                    eprintln!(
-                        "Unhandled code in the {} module at line {}",
-                        self.info.name, self.lineno
-                    );
-                    unreachable!();
+                    Err(LintMsg::new(
+                        &self.info.name,
+                        self.lineno,
+                        format!("\n`{}` can't be exported from `{}`.\n{}", name, line, msg),
+                    ))
                 }
             }
             None => Ok(None),
         }
     }
 
+    // Check the lisp_fn with the attribute ATTR and the signature LINE,
+    // defining LISP_NAME, against what Emacs can define: a name that reads
+    // back as that symbol, and no more arguments than a subr can take.
+    fn validate_lisp_fn(
+        &self,
+        lineno: u32,
+        attr: &str,
+        line: &str,
+        lisp_name: &str,
+    ) -> Result<(), LintMsg> {
+        lazy_static! {
+            static ref NUMBER: Regex =
+                Regex::new(r"^[-+]?([0-9]+\.?|[0-9]*\.[0-9]+(e[-+]?[0-9]+)?)$").unwrap();
+        }
+
+        let error = |msg: String| Err(LintMsg::new(&self.info.name, lineno, msg));
+
+        let unreadable = |c: char| c.is_whitespace() || c.is_control() || "()[]\"';`,".contains(c);
+        if lisp_name.is_empty()
+            || lisp_name.starts_with('#')
+            || lisp_name.contains(unreadable)
+            || NUMBER.is_match(lisp_name)
+        {
+            return error(format!(
+                "`{}` doesn't read back as a Lisp symbol.\nlisp_fn names must be symbols.",
+                lisp_name
+            ));
+        }
+
+        let params = match parse_parameters(line) {
+            Some(params) => params,
+            None => return Ok(()),
+        };
+        let many = params.len() == 1 && is_lisp_object_slice(params[0]);
+        if many || parse_argument(attr, "unevalled").is_some() {
+            return Ok(());
+        }
+
+        if params.len() > MAX_FIXED_ARGS {
+            return error(format!(
+                "`{}` takes {} arguments, but Emacs allows at most {}.\nTake `&[LispObject]` instead.",
+                lisp_name,
+                params.len(),
+                MAX_FIXED_ARGS
+            ));
+        }
+
+        let min = parse_argument(attr, "min")
+            .and_then(parse_string_literal)
+            .and_then(|min| min.parse::<usize>().ok());
+        match min {
+            Some(min) if min > params.len() => error(format!(
+                "`{}` requires {} arguments, but takes only {}.",
+                lisp_name,
+                min,
+                params.len()
+            )),
+            _ => Ok(()),
+        }
+    }
+
     fn lint_nomangle(&mut self, line: &str) -> Result<(), LintMsg> {
         if !(line.starts_with("pub extern \"C\" ") || line.starts_with("pub unsafe extern \"C\" "))
         {
@@ -324,11 +643,232 @@ impl<'a> ModuleParser<'a> {
     }
 }
 
+// Whether LINE starts with the attribute NAME, under any path, as in
+// `#[lisp_fn]`, `#[lisp_fn(min = "1")]` or `#[emacs::lisp_fn]`.  Only
+// the last segment of the path is compared, and as a whole, so
+// `#[lisp_fn_impl]` or `#[my_lisp_fn]` don't match `lisp_fn`.
+fn is_attribute(line: &str, name: &str) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+    let attr = match line.strip_prefix("#[") {
+        Some(attr) => attr.trim_start(),
+        None => return false,
+    };
+    let path_end = attr
+        .find(|c: char| !(is_word(c) || c == ':'))
+        .unwrap_or(attr.len());
+    let path = attr[..path_end].trim_start_matches("::");
+
+    let segments = path.split("::");
+    let valid_path = segments
+        .clone()
+        .all(|segment| !segment.is_empty() && segment.chars().all(is_word));
+    valid_path && segments.last() == Some(name)
+}
+
+// Split LINE, a `#[cfg_attr(COND, ATTR, ...)]` attribute, into COND and
+// the first ATTR that exports a function, as `#[ATTR]`.  Return None if
+// LINE is another attribute or none of its ATTRs export.
+fn parse_cfg_attr(line: &str) -> Option<(String, String)> {
+    if !is_attribute(line, "cfg_attr") {
+        return None;
+    }
+
+    let args = &line[line.find('(')? + 1..line.rfind(")]")?];
+    let args = split_arguments(args);
+    let (cond, attrs) = args.split_first()?;
+
+    attrs
+        .iter()
+        .map(|attr| format!("#[{}]", attr))
+        .find(|attr| is_attribute(attr, "lisp_fn") || is_attribute(attr, "no_mangle"))
+        .map(|attr| (cond.to_string(), attr))
+}
+
+// Split the arguments of an attribute at the commas that aren't nested
+// in brackets or string literals, as `all(unix, x)` or `"a, b"` are.
+fn split_arguments(args: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (i, c) in args.char_indices() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    let last = args[start..].trim();
+    if !last.is_empty() {
+        parts.push(last);
+    }
+    parts
+}
+
+// The cfg attribute for an export under both the cfg attribute CFG, if
+// any, and the condition COND of a cfg_attr.
+fn add_cfg(cfg: Option<String>, cond: &str) -> String {
+    let cfg = match cfg {
+        Some(cfg) => cfg,
+        None => return format!("#[cfg({})]", cond),
+    };
+
+    match cfg
+        .strip_prefix("#[cfg(")
+        .and_then(|cfg| cfg.strip_suffix(")]"))
+    {
+        Some(outer) => format!("#[cfg(all({}, {}))]", outer, cond),
+        None => format!("{} #[cfg({})]", cfg, cond),
+    }
+}
+
+// Parse the Lisp symbol name out of the `name = "..."` argument of a
+// lisp_fn attribute, skipping `c_name`.
+fn parse_lisp_name(line: &str) -> Option<String> {
+    parse_argument(line, "name").and_then(parse_string_literal)
+}
+
+// Find the `KEY = value` argument of an attribute in LINE, however it is
+// spaced around the `=`, and return the source from the start of its
+// value.  KEY must be a whole word, so `name` doesn't match `c_name`.
+fn parse_argument<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+    line.match_indices(key).find_map(|(begin, _)| {
+        if line[..begin].chars().next_back().map_or(false, is_word) {
+            return None;
+        }
+
+        let value = line[begin + key.len()..].trim_start().strip_prefix('=')?;
+        Some(value.trim_start())
+    })
+}
+
+// Parse the string literal at the start of SRC, either quoted with
+// backslash escapes or raw, as in `r"..."` or `r#"..."#`.
+fn parse_string_literal(src: &str) -> Option<String> {
+    if let Some(raw) = src.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let body = raw[hashes..].strip_prefix('"')?;
+        let terminator = format!("\"{}", "#".repeat(hashes));
+        let end = body.find(&terminator)?;
+        return Some(body[..end].to_string());
+    }
+
+    let mut value = String::new();
+    let mut chars = src.strip_prefix('"')?.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(value),
+            '\\' => value.push(chars.next()?),
+            c => value.push(c),
+        }
+    }
+
+    None
+}
+
+// The parameters of the function declared by LINE, split at the commas
+// that aren't nested in brackets, as in `(a: Vec<(u8, u8)>)`, or None
+// if its parameter list can't be found.
+fn parse_parameters(line: &str) -> Option<Vec<&str>> {
+    let decl = &line[line.find("fn ")? + 3..];
+
+    // The parameter list is the first parenthesis outside generics.
+    let mut depth = 0;
+    let mut start = None;
+    for (i, c) in decl.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            '(' if depth == 0 => {
+                start = Some(i + 1);
+                break;
+            }
+            _ => (),
+        }
+    }
+    let decl = &decl[start?..];
+
+    let mut params = Vec::new();
+    let mut depth = 0;
+    let mut begin = 0;
+    let mut prev = ' ';
+    for (i, c) in decl.char_indices() {
+        match c {
+            ')' if depth == 0 => {
+                params.push(&decl[begin..i]);
+                return Some(
+                    params
+                        .into_iter()
+                        .map(str::trim)
+                        .filter(|param| !param.is_empty())
+                        .collect(),
+                );
+            }
+            '(' | '[' | '{' | '<' => depth += 1,
+            // Not the arrow of a function type, as in `impl Fn() -> T`.
+            '>' if prev != '-' => depth -= 1,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                params.push(&decl[begin..i]);
+                begin = i + 1;
+            }
+            _ => (),
+        }
+        prev = c;
+    }
+
+    None
+}
+
+// Whether PARAM takes the arguments of a `MANY` function, as a slice of
+// LispObjects.
+fn is_lisp_object_slice(param: &str) -> bool {
+    let ty = match param.find(':') {
+        Some(colon) => param[colon + 1..].trim(),
+        None => return false,
+    };
+    let ty: String = ty.chars().filter(|c| !c.is_whitespace()).collect();
+    ty == "&[LispObject]" || ty == "&mut[LispObject]"
+}
+
+// Parse the function name out of a line declaring an async function.
+fn parse_async_fn_name(line: &str) -> Option<String> {
+    if line.contains("async fn ") {
+        get_function_name(line)
+    } else {
+        None
+    }
+}
+
 // Parse the function name out of a line of source
 fn get_function_name(line: &str) -> Option<String> {
-    if let Some(pos) = line.find('(') {
-        if let Some(fnpos) = line.find("fn ") {
-            let name = line[(fnpos + 3)..pos].trim();
+    if let Some(fnpos) = line.find("fn ") {
+        if let Some(pos) = line[fnpos..].find('(') {
+            let name = line[(fnpos + 3)..(fnpos + pos)].trim();
+            // Leave out generic parameters, as in `foo<T>`.
+            let name = name.split('<').next().unwrap_or(name).trim_end();
             return Some(name.to_string());
         }
     }
@@ -336,7 +876,8 @@ fn get_function_name(line: &str) -> Option<String> {
     None
 }
 
-fn handle_file(mod_path: &PathBuf) -> Result<Option<ModuleData>, BuildError> {
+// Parse the module at MOD_PATH, in lint mode if LINT.
+fn handle_file(mod_path: &PathBuf, lint: bool) -> Result<Option<ModuleData>, BuildError> {
     if let Some(mod_info) = ModuleInfo::from_path(mod_path) {
         let fp = match File::open(mod_info.path.clone()) {
             Ok(f) => f,
@@ -349,7 +890,11 @@ fn handle_file(mod_path: &PathBuf) -> Result<Option<ModuleData>, BuildError> {
             }
         };
 
-        let mut parser = ModuleParser::new(&mod_info);
+        let mut parser = if lint {
+            ModuleParser::linting(&mod_info)
+        } else {
+            ModuleParser::new(&mod_info)
+        };
         let mod_data = parser.run(BufReader::new(fp))?;
         Ok(Some(mod_data))
     } else {
@@ -376,7 +921,7 @@ fn ignore(path: &str, additional_ignored_paths: &Vec<&str>) -> bool {
 }
 
 /// Find modules in PATH which should contain the src directory of a crate
-fn find_crate_modules(path: &PathBuf) -> Result<Vec<ModuleData>, BuildError> {
+fn find_crate_modules(path: &PathBuf, lint: bool) -> Result<Vec<ModuleData>, BuildError> {
     let mut modules: Vec<ModuleData> = Vec::new();
     let ignored_paths = vec!["lib.rs"];
 
@@ -385,12 +930,83 @@ fn find_crate_modules(path: &PathBuf) -> Result<Vec<ModuleData>, BuildError> {
         let mod_path = entry?.path();
 
         if !ignore(path_as_str(mod_path.file_name()), &ignored_paths) {
-            if let Some(mod_data) = handle_file(&mod_path)? {
+            if let Some(mod_data) = handle_file(&mod_path, lint)? {
                 modules.push(mod_data);
             }
         }
     }
 
+    // Directory order varies between file systems, keep the generated
+    // files the same from build to build.
+    modules.sort_by(|a, b| a.info.name.cmp(&b.info.name));
+
+    Ok(modules)
+}
+
+/// Find the modules of the crate at CRATE_PATH: those in SRC_DIR, and
+/// those in the directories NG_BINDGEN_EXTRA_DIRS lists relative to the
+/// crate, separated like PATH, that the crate has.  It is an error for
+/// two modules to have the same name, or two lisp_fns the same C name.
+fn find_all_crate_modules(
+    crate_path: &Path,
+    src_dir: &Path,
+) -> Result<Vec<ModuleData>, BuildError> {
+    // Rerun hints replace cargo's default of rerunning on any change, so
+    // the manifest is watched for its src-dir, and the directories for
+    // added modules.
+    println!(
+        "cargo:rerun-if-changed={}",
+        crate_path.join("Cargo.toml").display()
+    );
+    println!("cargo:rerun-if-changed={}", src_dir.display());
+
+    println!("cargo:rerun-if-env-changed=NG_BINDGEN_EXTRA_DIRS");
+    let extra_dirs = extra_dirs();
+
+    for dir in &extra_dirs {
+        // New modules show up as changes to the directory.  Cargo always
+        // reruns for a path that doesn't exist, so skip those.
+        let dir = crate_path.join(dir);
+        if dir.is_dir() {
+            println!("cargo:rerun-if-changed={}", dir.display());
+        }
+    }
+
+    let modules = find_modules_in_dirs(crate_path, src_dir, &extra_dirs, false)?;
+    match duplicate_lints(&modules).into_iter().next() {
+        Some(lint) => Err(lint.into()),
+        None => Ok(modules),
+    }
+}
+
+// The directories NG_BINDGEN_EXTRA_DIRS lists.
+fn extra_dirs() -> Vec<PathBuf> {
+    match env::var_os("NG_BINDGEN_EXTRA_DIRS") {
+        Some(dirs) => env::split_paths(&dirs)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+fn find_modules_in_dirs(
+    crate_path: &Path,
+    src_dir: &Path,
+    extra_dirs: &[PathBuf],
+    lint: bool,
+) -> Result<Vec<ModuleData>, BuildError> {
+    let mut modules = find_crate_modules(&src_dir.to_path_buf(), lint)?;
+    for dir in extra_dirs {
+        // NG_BINDGEN_EXTRA_DIRS applies to every crate, and only some
+        // have each of its directories.
+        let dir = crate_path.join(dir);
+        if dir.is_dir() {
+            modules.extend(find_crate_modules(&dir, lint)?);
+        }
+    }
+
+    modules.sort_by(|a, b| a.info.name.cmp(&b.info.name));
+
     Ok(modules)
 }
 
@@ -400,6 +1016,8 @@ fn generate_crate_c_export_file(
     mut out_file: &File,
     modules: &Vec<ModuleData>,
 ) -> Result<(), BuildError> {
+    write!(out_file, "{}", GENERATED_HEADER)?;
+
     for mod_data in modules {
         for (cfg, func) in &mod_data.c_exports {
             if let Some(cfg) = cfg {
@@ -411,7 +1029,7 @@ fn generate_crate_c_export_file(
                 mod_data.info.name, func
             )?;
         }
-        for (cfg, func) in &mod_data.lisp_fns {
+        for (cfg, func, _, _) in &mod_data.lisp_fns {
             if let Some(cfg) = cfg {
                 write!(out_file, "{}\n", cfg)?;
             }
@@ -427,12 +1045,49 @@ fn generate_crate_c_export_file(
     Ok(())
 }
 
+/// First line of every file ng-bindgen writes.  It is always the same,
+/// without a date, so unchanged output stays byte for byte the same and
+/// isn't rewritten.
+const GENERATED_HEADER: &str = "// @generated by ng-bindgen, do not edit.\n";
+
 /// First we have to generate the include file for the main crate which
 /// will be stored in OUT_DIR. It only contains the rust_init_syms
 /// that runs the crates *_init_syms functions.
+///
+/// The crates are initialized in the order of their directories.  The
+/// calls for crates only built with a feature are gated on it, so the
+/// file is the same whatever features are enabled, and is only rewritten
+/// when crates are added or removed.
 pub fn generate_include_files(crates_dir: PathBuf) -> Result<(), BuildError> {
-    let out_path: PathBuf = [&env_var("OUT_DIR")].iter().collect();
-    let mut out_file = File::create(out_path.join("c_exports.rs"))?;
+    let main_path: PathBuf = [&env_var("CARGO_MANIFEST_DIR")].iter().collect();
+    let out_dir: PathBuf = [&env_var("OUT_DIR")].iter().collect();
+
+    // Rerun hints replace cargo's default of rerunning on any change, so
+    // the crates directory has to be watched for added or removed crates,
+    // and the manifest for the features they are gated on.
+    println!("cargo:rerun-if-changed={}", crates_dir.display());
+    println!(
+        "cargo:rerun-if-changed={}",
+        main_path.join("Cargo.toml").display()
+    );
+
+    generate_include_files_to(&main_path, crates_dir, out_dir)
+}
+
+/// Like `generate_include_files`, for the main crate at MAIN_PATH, but
+/// write c_exports.rs to the given OUT_DIR rather than the one cargo
+/// sets, and print no rerun hints, so it can be used outside of a build
+/// script.
+pub fn generate_include_files_to(
+    main_path: &Path,
+    crates_dir: PathBuf,
+    out_dir: PathBuf,
+) -> Result<(), BuildError> {
+    let gated_crates = feature_gated_crates(&read_manifest(main_path)?);
+    let out_path = out_dir.join("c_exports.rs");
+    let mut out_file: Vec<u8> = Vec::new();
+
+    write!(out_file, "{}", GENERATED_HEADER)?;
 
     // Add main rust_init_syms function to the main c_exports file
     write!(
@@ -440,66 +1095,132 @@ pub fn generate_include_files(crates_dir: PathBuf) -> Result<(), BuildError> {
         "#[no_mangle]\npub extern \"C\" fn rust_init_syms() {{\n"
     )?;
 
-    // Iterate crates path and generate include files
-    for entry in fs::read_dir(crates_dir).unwrap() {
-        let crate_path = entry?.path();
+    let mut crate_paths = fs::read_dir(crates_dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    crate_paths.sort();
 
+    // Iterate crates path and generate include files
+    for crate_path in crate_paths {
         if build_ignored_crates(&crate_path) {
             continue;
         };
         // generate_crate_exports(&crate_path)?;
-        let crate_name = get_crate_name(&crate_path);
+        let crate_name = match get_crate_name(&crate_path) {
+            Ok(crate_name) => crate_name,
+            Err(BuildError::IOError(e)) => {
+                println!("cargo:warning=Skipping {}: {}", crate_path.display(), e);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
 
         // Call a crate's init_syms function in the main c_exports file
+        if let Some(features) = crate_features(&gated_crates, &crate_path) {
+            write!(out_file, "{}\n", feature_cfg(features))?;
+        }
         let crate_init_syms = format!("{}::{}_init_syms();\n", crate_name, crate_name);
         write!(out_file, "{}", crate_init_syms)?;
     }
 
     write!(out_file, "}}\n")?;
 
+    if fs::read(&out_path).ok().as_ref() != Some(&out_file) {
+        fs::write(&out_path, out_file)?;
+    }
+
     Ok(())
 }
 
-/// Return true when crate is supposed to be ignored.
-/// The list of ignored crates depends on the activated features.
+/// Return true for crates that are never built on their own, whose
+/// exports are part of the crates using them.
 fn build_ignored_crates(path: &PathBuf) -> bool {
-    let mut ignored_crates = vec!["lisp_util", "lisp_macros", "emacs"];
+    let ignored_crates = ["lisp_util", "lisp_macros", "emacs"];
+    let crate_path = path_as_str(path.file_name());
 
-    #[cfg(not(feature = "libgit"))]
-    ignored_crates.push("git");
+    ignored_crates.iter().any(|ignored| *ignored == crate_path)
+}
 
-    #[cfg(not(feature = "javascript"))]
-    ignored_crates.push("js");
+/// The crates, by directory, that the main crate with MANIFEST only
+/// builds with a feature, as optional dependencies, and the features
+/// that enable each.  An optional dependency is a feature of its own,
+/// unless a feature enables it as `dep:NAME`.
+fn feature_gated_crates(manifest: &Manifest) -> Vec<(String, Vec<String>)> {
+    let mut gated_crates = Vec::new();
+
+    for (name, dep) in &manifest.dependencies {
+        let path = match dep.detail().and_then(|detail| detail.path.as_ref()) {
+            Some(path) if dep.optional() => path,
+            _ => continue,
+        };
 
-    #[cfg(not(feature = "ng-module"))]
-    ignored_crates.push("ng_module");
+        let dep_feature = format!("dep:{}", name);
+        let mut features: Vec<String> = manifest
+            .features
+            .iter()
+            .filter(|(_, enables)| enables.contains(&dep_feature))
+            .map(|(feature, _)| feature.clone())
+            .collect();
+        if features.is_empty() {
+            features.push(name.clone());
+        }
 
-    #[cfg(not(feature = "window-system-webrender"))]
-    ignored_crates.push("webrender");
+        let dir = path_as_str(Path::new(path).file_name()).to_string();
+        gated_crates.push((dir, features));
+    }
 
-    let crate_path = path_as_str(path.file_name()).to_string();
+    gated_crates
+}
 
-    for ignored in ignored_crates {
-        if ignored == crate_path {
-            return true;
-        }
-    }
+/// The features of GATED_CRATES that the crate at PATH is only built
+/// with, if any.
+fn crate_features<'a>(
+    gated_crates: &'a Vec<(String, Vec<String>)>,
+    path: &PathBuf,
+) -> Option<&'a Vec<String>> {
+    let crate_path = path_as_str(path.file_name());
+
+    gated_crates
+        .iter()
+        .find(|(dir, _)| dir == crate_path)
+        .map(|(_, features)| features)
+}
+
+// The cfg attribute for code built with any of FEATURES.
+fn feature_cfg(features: &Vec<String>) -> String {
+    let features: Vec<String> = features
+        .iter()
+        .map(|feature| format!("feature = \"{}\"", feature))
+        .collect();
 
-    false
+    match &features[..] {
+        [feature] => format!("#[cfg({})]", feature),
+        _ => format!("#[cfg(any({}))]", features.join(", ")),
+    }
 }
 
 /// Create c_exports.rs that holds a crate's generated bindings.
 /// We call generate_crate_c_export_file to add regular functions bindings
 /// and write_lisp_fns to create the include file for each module which holds
-/// the lisp_fns.
+/// the lisp_fns.  Cargo is told to rerun the build script when a module
+/// changes or one is added.
 pub fn generate_crate_exports(path: &PathBuf) -> Result<(), BuildError> {
-    let modules = find_crate_modules(&path.join("src"))?;
+    // Fail before anything is written, not halfway through.
+    let crate_name = get_crate_name(path)?;
+    let src_dir = get_src_dir(path)?;
+    let modules = find_all_crate_modules(path, &src_dir)?;
+    for mod_data in &modules {
+        println!("cargo:rerun-if-changed={}", mod_data.info.path.display());
+    }
 
     let _ = fs::create_dir(path.join("out"));
     let mut out_file = File::create(path.join("out").join("c_exports.rs"))?;
     generate_crate_c_export_file(&out_file, &modules)?;
 
-    let crate_name = get_crate_name(path);
+    report_exports(&crate_name, &modules);
+    report_shadowed_builtins(&modules)?;
+    report_orphaned_async_streams(&modules);
+
     write!(
         out_file,
         "#[no_mangle]\npub extern \"C\" fn {}_init_syms() {{\n",
@@ -513,12 +1234,275 @@ pub fn generate_crate_exports(path: &PathBuf) -> Result<(), BuildError> {
     Ok(())
 }
 
-fn get_crate_name(path: &PathBuf) -> String {
-    let manifest = Manifest::from_path(path.join("Cargo.toml")).unwrap();
-    match manifest.package {
+/// Check the modules of the crate at PATH for lint problems without
+/// writing anything, as a pre-commit hook would.  Unlike the build,
+/// which stops at the first problem, every one is returned, along with
+/// modules that share a name and lisp_fns that share a C name.  Source
+/// that can't be parsed, or read, is still an error.
+pub fn lint_crate(path: &PathBuf) -> Result<Vec<LintMsg>, BuildError> {
+    let src_dir = get_src_dir(path)?;
+    let mut modules = find_modules_in_dirs(path, &src_dir, &extra_dirs(), true)?;
+
+    let mut lints = duplicate_lints(&modules);
+    for mod_data in modules.iter_mut() {
+        lints.append(&mut mod_data.lints);
+    }
+    lints.sort_by(|a, b| (&a.modname, a.lineno).cmp(&(&b.modname, b.lineno)));
+
+    Ok(lints)
+}
+
+// Lints for the modules that have the name of an earlier one, which
+// would write the same exports file, and for the lisp_fns that have the
+// C name of an earlier one, which would be defined twice.  lisp_fns
+// under different cfgs can share a name, as for different platforms.
+fn duplicate_lints(modules: &Vec<ModuleData>) -> Vec<LintMsg> {
+    let mut lints = Vec::new();
+    let mut seen_modules: Vec<&ModuleInfo> = Vec::new();
+    let mut seen: Vec<(&Option<String>, &str, &str, u32)> = Vec::new();
+
+    for mod_data in modules {
+        let info = &mod_data.info;
+        match seen_modules.iter().find(|other| other.name == info.name) {
+            Some(first) => lints.push(LintMsg::new(
+                &info.name,
+                1,
+                format!(
+                    "Module {} is defined by both {} and {}",
+                    info.name,
+                    first.path.to_string_lossy(),
+                    info.path.to_string_lossy()
+                ),
+            )),
+            None => seen_modules.push(info),
+        }
+
+        for (cfg, func, _, lineno) in &mod_data.lisp_fns {
+            let first = seen.iter().find(|(other_cfg, other, _, _)| {
+                other == func && (cfg.is_none() || other_cfg.is_none() || cfg == *other_cfg)
+            });
+
+            match first {
+                Some((_, _, modname, first_lineno)) => lints.push(LintMsg::new(
+                    &mod_data.info.name,
+                    *lineno,
+                    format!(
+                        "lisp_fn has the C name `{}` of the one in {} on line {}",
+                        func, modname, first_lineno
+                    ),
+                )),
+                None => seen.push((cfg, func, &mod_data.info.name, *lineno)),
+            }
+        }
+    }
+
+    lints
+}
+
+/// Warn about `#[no_mangle]` functions that were skipped, so they don't
+/// surface as undefined symbols at link time.  With NG_BINDGEN_VERBOSE
+/// set, also report what was exported.
+fn report_exports(crate_name: &str, modules: &Vec<ModuleData>) {
+    for warning in skipped_export_warnings(modules) {
+        println!("cargo:warning={}", warning);
+    }
+
+    println!("cargo:rerun-if-env-changed=NG_BINDGEN_VERBOSE");
+    if env::var_os("NG_BINDGEN_VERBOSE").is_some() {
+        let c_exports: usize = modules.iter().map(|m| m.c_exports.len()).sum();
+        let lisp_fns: usize = modules.iter().map(|m| m.lisp_fns.len()).sum();
+        println!(
+            "cargo:warning={}: exported {} C functions and {} lisp_fns from {} modules",
+            crate_name,
+            c_exports,
+            lisp_fns,
+            modules.len()
+        );
+    }
+}
+
+fn skipped_export_warnings(modules: &Vec<ModuleData>) -> Vec<String> {
+    modules
+        .iter()
+        .flat_map(|mod_data| {
+            mod_data.skipped_exports.iter().map(move |(lineno, attr)| {
+                format!(
+                    "{}:{}: indented {} function is not exported",
+                    mod_data.info.path.display(),
+                    lineno,
+                    attr
+                )
+            })
+        })
+        .collect()
+}
+
+/// Warn about lisp_fns that would be registered under the name of a
+/// builtin defined in C, shadowing it or clashing with it at init_syms
+/// time.  The builtins are read from the file NG_BINDGEN_C_BUILTINS
+/// names, one symbol name a line.  Without it, nothing is checked.
+fn report_shadowed_builtins(modules: &Vec<ModuleData>) -> Result<(), BuildError> {
+    println!("cargo:rerun-if-env-changed=NG_BINDGEN_C_BUILTINS");
+    let path = match env::var_os("NG_BINDGEN_C_BUILTINS") {
+        Some(path) => PathBuf::from(path),
+        None => return Ok(()),
+    };
+    println!("cargo:rerun-if-changed={}", path.display());
+
+    let builtins = match fs::read_to_string(&path) {
+        Ok(builtins) => builtins,
+        Err(e) => {
+            return Err(io::Error::new(
+                e.kind(),
+                format!("Failed to read {}: {}", path.to_string_lossy(), e),
+            )
+            .into());
+        }
+    };
+    let builtins: HashSet<&str> = builtins
+        .lines()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .collect();
+
+    for warning in shadowed_builtin_warnings(modules, &builtins) {
+        println!("cargo:warning={}", warning);
+    }
+
+    Ok(())
+}
+
+fn shadowed_builtin_warnings(modules: &Vec<ModuleData>, builtins: &HashSet<&str>) -> Vec<String> {
+    modules
+        .iter()
+        .flat_map(|mod_data| {
+            mod_data
+                .lisp_fns
+                .iter()
+                .filter_map(move |(_, func, lisp_name, lineno)| {
+                    // The C name clashes with the F function of a builtin
+                    // whose Lisp name it would be made from.
+                    let c_lisp_name = func.replace('_', "-");
+                    let names = [lisp_name.as_str(), c_lisp_name.as_str()];
+                    let symbol = names.iter().find(|name| builtins.contains(*name))?;
+
+                    Some(format!(
+                        "{}:{}: lisp_fn in module {} shadows the C builtin `{}`",
+                        mod_data.info.path.display(),
+                        lineno,
+                        mod_data.info.name,
+                        symbol
+                    ))
+                })
+        })
+        .collect()
+}
+
+/// Warn about `#[async_stream]` functions that aren't async functions of
+/// their module, whose `call_` lisp_fn would be exported without the
+/// function it calls.  Only checked with NG_BINDGEN_CHECK_ASYNC_STREAMS
+/// set.
+fn report_orphaned_async_streams(modules: &Vec<ModuleData>) {
+    println!("cargo:rerun-if-env-changed=NG_BINDGEN_CHECK_ASYNC_STREAMS");
+    if env::var_os("NG_BINDGEN_CHECK_ASYNC_STREAMS").is_none() {
+        return;
+    }
+
+    for warning in orphaned_async_stream_warnings(modules) {
+        println!("cargo:warning={}", warning);
+    }
+}
+
+fn orphaned_async_stream_warnings(modules: &Vec<ModuleData>) -> Vec<String> {
+    modules
+        .iter()
+        .flat_map(|mod_data| {
+            mod_data
+                .async_streams
+                .iter()
+                .filter(move |(func, _)| !mod_data.async_fns.contains(func))
+                .map(move |(func, lineno)| {
+                    format!(
+                        "{}:{}: call_{} is exported, but module {} has no async fn {}",
+                        mod_data.info.path.display(),
+                        lineno,
+                        func,
+                        mod_data.info.name,
+                        func
+                    )
+                })
+        })
+        .collect()
+}
+
+// The manifest of the crate at PATH.
+fn read_manifest(path: &Path) -> Result<Manifest, BuildError> {
+    let manifest_path = path.join("Cargo.toml");
+    match Manifest::from_path(&manifest_path) {
+        Ok(manifest) => Ok(manifest),
+        Err(e) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to read {}: {}", manifest_path.to_string_lossy(), e),
+        )
+        .into()),
+    }
+}
+
+/// The name of the crate at PATH, from its Cargo.toml.  A directory
+/// without a readable manifest isn't a crate.
+fn get_crate_name(path: &PathBuf) -> Result<String, BuildError> {
+    let manifest = read_manifest(path)?;
+
+    Ok(match manifest.package {
         Some(package) => package.name,
         None => path_as_str(path.file_name()).to_string(),
+    })
+}
+
+/// The directory the modules of the crate at PATH are in: `src`, unless
+/// `src-dir` under `[package.metadata.ng-bindgen]` in its Cargo.toml
+/// names another, relative to the crate.  It is an error for the
+/// directory not to exist, rather than a crate that silently exports
+/// nothing.
+fn get_src_dir(path: &Path) -> Result<PathBuf, BuildError> {
+    let manifest = read_manifest(path)?;
+    let src_dir = manifest
+        .package
+        .as_ref()
+        .and_then(|package| package.metadata.as_ref())
+        .and_then(|metadata| metadata.get("ng-bindgen"))
+        .and_then(|metadata| metadata.get("src-dir"));
+
+    let src_dir = match src_dir {
+        Some(dir) => match dir.as_str() {
+            Some(dir) => path.join(dir),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{}: package.metadata.ng-bindgen.src-dir must be a string",
+                        path.join("Cargo.toml").to_string_lossy()
+                    ),
+                )
+                .into());
+            }
+        },
+        None => path.join("src"),
+    };
+
+    if !src_dir.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "Source directory {} of crate {} does not exist",
+                src_dir.to_string_lossy(),
+                path.to_string_lossy()
+            ),
+        )
+        .into());
     }
+
+    Ok(src_dir)
 }
 
 /// Export lisp functions defined in rust by using the macro `export_lisp_fns`
@@ -529,53 +1513,804 @@ fn write_lisp_fns(
     modules: &Vec<ModuleData>,
 ) -> Result<(), BuildError> {
     for mod_data in modules {
-        let exports_path: PathBuf = crate_path.join([&mod_data.info.name, "_exports.rs"].concat());
+        for init_syms in write_module_exports(crate_path, mod_data)? {
+            write!(out_file, "    {}\n", init_syms)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the exports file of a single module to CRATE_PATH and return
+/// the calls of its init functions for the crate's *_init_syms.
+fn write_module_exports(
+    crate_path: &Path,
+    mod_data: &ModuleData,
+) -> Result<Vec<String>, BuildError> {
+    let exports_path: PathBuf = crate_path.join([&mod_data.info.name, "_exports.rs"].concat());
+    let mut init_syms = Vec::new();
+
+    let mut exports = String::new();
+
+    // Add lisp_fns, sorted by Lisp name whether or not they have a cfg
+    if !mod_data.lisp_fns.is_empty() {
+        let mut lisp_fns: Vec<_> = mod_data.lisp_fns.iter().collect();
+        lisp_fns.sort_by(|(_, a_func, a_name, _), (_, b_func, b_name, _)| {
+            a_name.cmp(b_name).then_with(|| a_func.cmp(b_func))
+        });
+
+        exports += &format!(
+            "export_lisp_fns! {{\n    {}\n}}\n",
+            lisp_fns
+                .into_iter()
+                .map(|lisp_fn| match lisp_fn {
+                    (Some(cfg), func, _, _) => format!("{} {}", cfg, func),
+                    (_, func, _, _) => format!("{}", func),
+                })
+                .collect::<Vec<String>>()
+                .join(",\n    ")
+        );
+
+        init_syms.push(format!("{}::rust_init_syms();", mod_data.info.name));
+    }
+
+    // Add protected_statics
+    if !mod_data.protected_statics.is_empty() {
+        exports += &format!(
+            "protect_statics_from_GC! {{ {} }}\n",
+            mod_data.protected_statics.join(", ")
+        );
+
+        init_syms.push(format!("{}::rust_static_syms();", mod_data.info.name));
+    }
 
-        // Start with a clean slate
+    // Don't leave the exports of an earlier build behind
+    if exports.is_empty() {
         if exports_path.exists() {
             fs::remove_file(&exports_path)?;
         }
+    } else {
+        fs::write(&exports_path, [GENERATED_HEADER, &exports].concat())?;
+    }
 
-        // Add lisp_fns
-        if !mod_data.lisp_fns.is_empty() {
-            let mut file = File::create(&exports_path)?;
-            write!(
-                file,
-                "export_lisp_fns! {{\n    {}\n}}\n",
-                mod_data
-                    .lisp_fns
-                    .iter()
-                    .map(|lisp_fn| match lisp_fn {
-                        (Some(cfg), func) => format!("{} {}", cfg, func),
-                        (_, func) => format!("{}", func),
-                    })
-                    .collect::<Vec<String>>()
-                    .join(",\n    ")
-            )?;
+    Ok(init_syms)
+}
+
+/// Regenerate the exports file of the module at MODULE_SRC in
+/// CRATE_OUT_DIR, without walking the rest of its crate, e.g. after the
+/// module was edited.  Return the lines the crate's *_init_syms needs
+/// to call the module's init functions.
+pub fn generate_module_exports(
+    crate_out_dir: &Path,
+    module_src: &Path,
+) -> Result<Vec<String>, BuildError> {
+    match handle_file(&module_src.to_path_buf(), false)? {
+        Some(mod_data) => write_module_exports(crate_out_dir, &mod_data),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a Rust module", module_src.to_string_lossy()),
+        )
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            write!(out_file, "    {}::rust_init_syms();\n", mod_data.info.name)?;
+    // Parse SRC as the source of the module NAME.
+    fn parse_module(name: &str, src: &str) -> Result<ModuleData, BuildError> {
+        let info = ModuleInfo::synthetic(name);
+        ModuleParser::new(&info).run(io::Cursor::new(src))
+    }
+
+    #[test]
+    fn lisp_name_skips_c_name() {
+        assert_eq!(
+            parse_lisp_name(r#"#[lisp_fn(c_name = "foo_bar", name = "foo-bar*")]"#),
+            Some("foo-bar*".to_string())
+        );
+        assert_eq!(parse_lisp_name(r#"#[lisp_fn(c_name = "foo_bar")]"#), None);
+    }
+
+    #[test]
+    fn arguments_spaced_any_way() {
+        for line in [
+            r#"#[lisp_fn(c_name="foo")]"#,
+            r#"#[lisp_fn(c_name = "foo")]"#,
+            r#"#[lisp_fn(c_name  =  "foo", name="foo-bar")]"#,
+        ]
+        .iter()
+        {
+            let c_name = parse_argument(line, "c_name").and_then(parse_string_literal);
+            assert_eq!(c_name, Some("foo".to_string()));
         }
 
-        // Add protected_statics
-        if !mod_data.protected_statics.is_empty() {
-            let mut file = OpenOptions::new()
-                .create(true)
-                .write(true)
-                .append(true)
-                .open(exports_path)?;
-            write!(
-                file,
-                "protect_statics_from_GC! {{ {} }}\n",
-                mod_data.protected_statics.join(", ")
-            )?;
+        assert_eq!(
+            parse_lisp_name(r#"#[lisp_fn(c_name="foo", name ="foo-bar")]"#),
+            Some("foo-bar".to_string())
+        );
+        assert_eq!(parse_argument(r#"#[lisp_fn(names = "foo")]"#, "name"), None);
+    }
 
-            write!(
-                out_file,
-                "    {}::rust_static_syms();\n",
-                mod_data.info.name
-            )?;
+    #[test]
+    fn c_name_escapes() {
+        assert_eq!(
+            parse_string_literal(r#""foo\"bar", name = "foo-bar")]"#),
+            Some(r#"foo"bar"#.to_string())
+        );
+        assert_eq!(
+            parse_string_literal(r##"r#"foo"bar"#)]"##),
+            Some(r#"foo"bar"#.to_string())
+        );
+        assert_eq!(
+            parse_string_literal(r#""foo_bar")]"#),
+            Some("foo_bar".to_string())
+        );
+        assert_eq!(parse_string_literal(r#""foo_bar)]"#), None);
+    }
+
+    #[test]
+    fn lisp_fn_on_signature_line() {
+        let src = r#"#[lisp_fn(min = "1")] pub fn foo_bar(x: LispObject) -> bool {
+    x.is_nil()
+}
+
+#[lisp_fn]
+pub fn baz() {}
+
+include!(concat!(env!("CARGO_MANIFEST_DIR"), "/out/foo_exports.rs"));
+"#;
+
+        let mod_data = parse_module("foo", src).expect("failed to parse module");
+
+        assert_eq!(
+            mod_data.lisp_fns,
+            vec![
+                (None, "foo_bar".to_string(), "foo-bar".to_string(), 1),
+                (None, "baz".to_string(), "baz".to_string(), 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn lisp_fn_with_c_name() {
+        let src = r#"#[lisp_fn(c_name = "foo_bar_internal", name = "foo-bar")]
+pub fn foo_bar() {}
+
+include!(concat!(env!("CARGO_MANIFEST_DIR"), "/out/foo_exports.rs"));
+"#;
+
+        let mod_data = parse_module("foo", src).expect("failed to parse module");
+
+        assert_eq!(
+            mod_data.lisp_fns,
+            vec![(
+                None,
+                "foo_bar_internal".to_string(),
+                "foo-bar".to_string(),
+                1
+            )]
+        );
+    }
+
+    #[test]
+    fn lisp_fn_with_where_clause() {
+        let src = r#"#[lisp_fn]
+pub fn foo_bar<T>(x: T) -> Result<LispObject, Error>
+where
+    T: Into<LispObject>,
+{
+    Ok(x.into())
+}
+
+#[lisp_fn]
+pub fn baz() {}
+
+include!(concat!(env!("CARGO_MANIFEST_DIR"), "/out/foo_exports.rs"));
+"#;
+
+        let mod_data = parse_module("foo", src).expect("failed to parse module");
+
+        assert_eq!(
+            mod_data.lisp_fns,
+            vec![
+                (None, "foo_bar".to_string(), "foo-bar".to_string(), 1),
+                (None, "baz".to_string(), "baz".to_string(), 9),
+            ]
+        );
+    }
+
+    #[test]
+    fn lisp_fn_with_other_attributes() {
+        let src = r#"#[lisp_fn]
+#[allow(clippy::needless_pass_by_value)]
+// Not a doc comment.
+pub fn foo_bar(x: LispObject) {}
+
+#[lisp_fn] #[inline]
+pub fn baz() {}
+
+include!(concat!(env!("CARGO_MANIFEST_DIR"), "/out/foo_exports.rs"));
+"#;
+
+        let mod_data = parse_module("foo", src).expect("failed to parse module");
+
+        assert_eq!(
+            mod_data.lisp_fns,
+            vec![
+                (None, "foo_bar".to_string(), "foo-bar".to_string(), 1),
+                (None, "baz".to_string(), "baz".to_string(), 6),
+            ]
+        );
+
+        match parse_module("foo", "#[lisp_fn]\npub(crate) fn foo() {}\n") {
+            Err(BuildError::Lint(lint)) => assert_eq!(lint.lineno, 2),
+            _ => panic!("lisp_fn that can't be exported wasn't an error"),
         }
     }
 
-    Ok(())
+    #[test]
+    fn lisp_fn_within_symbol_limits() {
+        let src = r#"#[lisp_fn(min = "2")]
+pub fn foo(a: LispObject, b: Vec<(u8, u8)>, c: impl Fn(u8) -> u8) {}
+
+#[lisp_fn(name = "1+")]
+pub fn one_plus(args: &mut [LispObject]) {}
+
+#[lisp_fn(unevalled = "true")]
+pub fn bar(args: LispObject) {}
+
+include!(concat!(env!("CARGO_MANIFEST_DIR"), "/out/foo_exports.rs"));
+"#;
+
+        let mod_data = parse_module("foo", src).expect("failed to parse module");
+        assert_eq!(mod_data.lisp_fns.len(), 3);
+
+        for (src, lineno) in [
+            ("#[lisp_fn(name = \"foo bar\")]\npub fn foo() {}\n", 1),
+            ("#[lisp_fn(name = \"1.5\")]\npub fn foo() {}\n", 1),
+            (
+                "#[lisp_fn]\npub fn foo(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H, i: I) {}\n",
+                1,
+            ),
+            (
+                "\n#[lisp_fn(min = \"2\")]\npub fn foo(a: LispObject) {}\n",
+                2,
+            ),
+        ]
+        .iter()
+        {
+            match parse_module("foo", src) {
+                Err(BuildError::Lint(lint)) => assert_eq!(lint.lineno, *lineno),
+                _ => panic!("lisp_fn over Emacs's limits wasn't an error: {}", src),
+            }
+        }
+    }
+
+    #[test]
+    fn async_stream_with_c_name() {
+        let src = r#"#[async_stream]
+pub async fn echo(s: String) -> String {
+    s
+}
+
+#[async_stream(c_name = "foo")]
+pub async fn data_echo(e: UserData) -> UserData {
+    e
+}
+
+include!(concat!(env!("CARGO_MANIFEST_DIR"), "/out/foo_exports.rs"));
+"#;
+
+        let mod_data = parse_module("foo", src).expect("failed to parse module");
+
+        assert_eq!(
+            mod_data.lisp_fns,
+            vec![
+                (None, "call_echo".to_string(), "call-echo".to_string(), 1),
+                (None, "foo".to_string(), "call-data-echo".to_string(), 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn attribute_paths() {
+        assert!(is_attribute("#[lisp_fn]", "lisp_fn"));
+        assert!(is_attribute(r#"#[lisp_fn(min = "1")]"#, "lisp_fn"));
+        assert!(is_attribute("#[emacs::lisp_fn]", "lisp_fn"));
+        assert!(is_attribute(
+            "#[::lisp_macros::async_stream]",
+            "async_stream"
+        ));
+        assert!(is_attribute("#[no_mangle] pub extern", "no_mangle"));
+
+        assert!(!is_attribute("#[lisp_fn_impl]", "lisp_fn"));
+        assert!(!is_attribute("#[my_lisp_fn]", "lisp_fn"));
+        assert!(!is_attribute("#[lisp_fn::helper]", "lisp_fn"));
+        assert!(!is_attribute("#[emacs:lisp_fn]", "lisp_fn"));
+        assert!(!is_attribute("#[doc = \"no_mangle\"]", "no_mangle"));
+    }
+
+    #[test]
+    fn qualified_attributes_export_like_bare_ones() {
+        let bare = r#"#[lisp_fn(min = "1")]
+pub fn foo_bar(x: LispObject) {}
+
+#[no_mangle]
+pub extern "C" fn wr_foo() {}
+
+include!(concat!(env!("CARGO_MANIFEST_DIR"), "/out/foo_exports.rs"));
+"#;
+        let qualified = r#"#[emacs::lisp_fn(min = "1")]
+pub fn foo_bar(x: LispObject) {}
+
+#[::core::prelude::v1::no_mangle]
+pub extern "C" fn wr_foo() {}
+
+include!(concat!(env!("CARGO_MANIFEST_DIR"), "/out/foo_exports.rs"));
+"#;
+
+        let (bare, qualified) = match (parse_module("foo", bare), parse_module("foo", qualified)) {
+            (Ok(bare), Ok(qualified)) => (bare, qualified),
+            _ => panic!("failed to parse module"),
+        };
+
+        assert_eq!(qualified.lisp_fns, bare.lisp_fns);
+        assert_eq!(qualified.c_exports, bare.c_exports);
+        assert_eq!(qualified.c_exports, vec![(None, "wr_foo".to_string())]);
+    }
+
+    #[test]
+    fn cfg_attr_arguments() {
+        assert_eq!(
+            parse_cfg_attr(r#"#[cfg_attr(feature = "x", lisp_fn(min = "1"))]"#),
+            Some((
+                r#"feature = "x""#.to_string(),
+                r#"#[lisp_fn(min = "1")]"#.to_string()
+            ))
+        );
+        assert_eq!(
+            parse_cfg_attr(
+                r#"#[cfg_attr(all(unix, feature = "a,b"), allow(dead_code), no_mangle)]"#
+            ),
+            Some((
+                r#"all(unix, feature = "a,b")"#.to_string(),
+                "#[no_mangle]".to_string()
+            ))
+        );
+        assert_eq!(parse_cfg_attr("#[cfg_attr(test, derive(Debug))]"), None);
+        assert_eq!(parse_cfg_attr("#[cfg(test)]"), None);
+    }
+
+    #[test]
+    fn cfg_attr_exports() {
+        let src = r#"#[cfg_attr(feature = "x", lisp_fn)]
+pub fn foo_bar() {}
+
+#[cfg(unix)]
+#[cfg_attr(feature = "x", lisp_fn(c_name = "baz_internal", name = "baz"))]
+pub fn baz() {}
+
+#[cfg_attr(test, derive(Debug))]
+pub struct Qux;
+
+#[cfg_attr(feature = "x", no_mangle)]
+pub extern "C" fn wr_foo() {}
+
+#[cfg_attr(feature = "y", no_mangle)] pub extern "C" fn wr_bar() {
+    wr_foo();
+}
+
+#[no_mangle] pub extern "C" fn wr_baz() {}
+
+include!(concat!(env!("CARGO_MANIFEST_DIR"), "/out/foo_exports.rs"));
+"#;
+
+        let mod_data = parse_module("foo", src).expect("failed to parse module");
+
+        assert_eq!(
+            mod_data.lisp_fns,
+            vec![
+                (
+                    Some(r#"#[cfg(feature = "x")]"#.to_string()),
+                    "foo_bar".to_string(),
+                    "foo-bar".to_string(),
+                    1
+                ),
+                (
+                    Some(r#"#[cfg(all(unix, feature = "x"))]"#.to_string()),
+                    "baz_internal".to_string(),
+                    "baz".to_string(),
+                    5
+                ),
+            ]
+        );
+        assert_eq!(
+            mod_data.c_exports,
+            vec![
+                (
+                    Some(r#"#[cfg(feature = "x")]"#.to_string()),
+                    "wr_foo".to_string()
+                ),
+                (
+                    Some(r#"#[cfg(feature = "y")]"#.to_string()),
+                    "wr_bar".to_string()
+                ),
+                (None, "wr_baz".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_mangle_export_keeps_cfg() {
+        let src = r#"#[cfg(unix)]
+#[no_mangle]
+pub extern "C" fn wr_foo() {}
+"#;
+
+        let mod_data = parse_module("foo", src).expect("failed to parse module");
+
+        assert_eq!(
+            mod_data.c_exports,
+            vec![(Some("#[cfg(unix)]".to_string()), "wr_foo".to_string())]
+        );
+        assert!(mod_data.lisp_fns.is_empty());
+    }
+
+    #[test]
+    fn exports_for_one_module() {
+        let dir = tempfile::tempdir().unwrap();
+        let module_src = dir.path().join("foo.rs");
+        fs::write(
+            &module_src,
+            r#"#[lisp_fn]
+pub fn foo_bar() {}
+
+include!(concat!(env!("CARGO_MANIFEST_DIR"), "/out/foo_exports.rs"));
+"#,
+        )
+        .unwrap();
+
+        let init_syms = generate_module_exports(dir.path(), &module_src)
+            .expect("failed to generate module exports");
+
+        assert_eq!(init_syms, vec!["foo::rust_init_syms();".to_string()]);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("foo_exports.rs")).unwrap(),
+            [GENERATED_HEADER, "export_lisp_fns! {\n    foo_bar\n}\n"].concat()
+        );
+    }
+
+    #[test]
+    fn include_files_to_out_dir() {
+        let main_dir = tempfile::tempdir().unwrap();
+        let crates_dir = tempfile::tempdir().unwrap();
+        let out_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            main_dir.path().join("Cargo.toml"),
+            "[package]\nname = \"main\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+
+        let crate_dir = crates_dir.path().join("foo");
+        fs::create_dir(&crate_dir).unwrap();
+        fs::write(
+            crate_dir.join("Cargo.toml"),
+            "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+
+        generate_include_files_to(
+            main_dir.path(),
+            crates_dir.path().into(),
+            out_dir.path().into(),
+        )
+        .expect("failed to generate include files");
+
+        let exports = fs::read_to_string(out_dir.path().join("c_exports.rs")).unwrap();
+        assert!(exports.starts_with(GENERATED_HEADER));
+        assert!(exports.contains("foo::foo_init_syms();\n"));
+    }
+
+    #[test]
+    fn lint_crate_collects_every_problem() {
+        let crate_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            crate_dir.path().join("Cargo.toml"),
+            "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        fs::create_dir(crate_dir.path().join("src")).unwrap();
+        fs::write(
+            crate_dir.path().join("src/bar.rs"),
+            r#"#[lisp_fn]
+pub fn foo_bar() {}
+
+include!(concat!(env!("CARGO_MANIFEST_DIR"), "/out/bar_exports.rs"));
+"#,
+        )
+        .unwrap();
+        fs::write(
+            crate_dir.path().join("src/foo.rs"),
+            r#"#[no_mangle]
+pub fn wr_foo() {}
+
+#[lisp_fn]
+fn foo() {}
+
+#[lisp_fn]
+pub fn foo_bar() {}
+"#,
+        )
+        .unwrap();
+
+        let lints = lint_crate(&crate_dir.path().to_path_buf()).expect("failed to lint crate");
+        let lines: Vec<_> = lints
+            .iter()
+            .map(|lint| (lint.modname.as_str(), lint.lineno))
+            .collect();
+        assert_eq!(lines, vec![("foo", 2), ("foo", 5), ("foo", 7), ("foo", 8)]);
+
+        fs::write(crate_dir.path().join("src/foo.rs"), "#[no_mangle]\n").unwrap();
+        match lint_crate(&crate_dir.path().to_path_buf()) {
+            Err(BuildError::IOError(_)) => (),
+            _ => panic!("unparseable module wasn't an error"),
+        }
+    }
+
+    #[test]
+    fn feature_gated_crates_are_sorted_and_gated() {
+        let main_dir = tempfile::tempdir().unwrap();
+        let crates_dir = tempfile::tempdir().unwrap();
+        let out_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            main_dir.path().join("Cargo.toml"),
+            r#"[package]
+name = "main"
+version = "0.1.0"
+
+[dependencies]
+foo = { path = "crates/foo" }
+git = { path = "crates/git", optional = true }
+js = { path = "crates/js", optional = true }
+wrterm = { path = "crates/webrender", optional = true }
+
+[features]
+libgit = ["git"]
+javascript = ["dep:js"]
+webrender = ["dep:wrterm"]
+wayland = ["dep:wrterm"]
+window-system-webrender = ["webrender"]
+"#,
+        )
+        .unwrap();
+
+        for (dir, name) in [
+            ("js", "js"),
+            ("foo", "foo"),
+            ("git", "git"),
+            ("webrender", "wrterm"),
+        ]
+        .iter()
+        {
+            let crate_dir = crates_dir.path().join(dir);
+            fs::create_dir(&crate_dir).unwrap();
+            fs::write(
+                crate_dir.join("Cargo.toml"),
+                format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\n", name),
+            )
+            .unwrap();
+        }
+
+        generate_include_files_to(
+            main_dir.path(),
+            crates_dir.path().into(),
+            out_dir.path().into(),
+        )
+        .expect("failed to generate include files");
+
+        let exports = fs::read_to_string(out_dir.path().join("c_exports.rs")).unwrap();
+        assert!(exports.contains(concat!(
+            "foo::foo_init_syms();\n",
+            "#[cfg(feature = \"git\")]\n",
+            "git::git_init_syms();\n",
+            "#[cfg(feature = \"javascript\")]\n",
+            "js::js_init_syms();\n",
+            "#[cfg(any(feature = \"wayland\", feature = \"webrender\"))]\n",
+            "wrterm::wrterm_init_syms();\n",
+        )));
+    }
+
+    #[test]
+    fn directories_without_a_manifest_are_skipped() {
+        let main_dir = tempfile::tempdir().unwrap();
+        let crates_dir = tempfile::tempdir().unwrap();
+        let out_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            main_dir.path().join("Cargo.toml"),
+            "[package]\nname = \"main\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+
+        fs::create_dir(crates_dir.path().join("stray")).unwrap();
+        fs::create_dir(crates_dir.path().join("broken")).unwrap();
+        fs::write(crates_dir.path().join("broken/Cargo.toml"), "[package").unwrap();
+
+        generate_include_files_to(
+            main_dir.path(),
+            crates_dir.path().into(),
+            out_dir.path().into(),
+        )
+        .expect("failed to generate include files");
+
+        let exports = fs::read_to_string(out_dir.path().join("c_exports.rs")).unwrap();
+        assert!(!exports.contains("_init_syms();"));
+    }
+
+    #[test]
+    fn indented_export_is_reported() {
+        let src = r#"#[no_mangle]
+pub extern "C" fn foo() {}
+
+mod bar {
+    #[no_mangle]
+    pub extern "C" fn bar() {}
+}
+
+impl Foo {
+    #[lisp_fn]
+    pub fn baz() {}
+}
+"#;
+
+        let mod_data = parse_module("foo", src).expect("failed to parse module");
+
+        assert_eq!(mod_data.c_exports, vec![(None, "foo".to_string())]);
+        assert!(mod_data.lisp_fns.is_empty());
+        assert_eq!(
+            skipped_export_warnings(&vec![mod_data]),
+            vec![
+                "foo.rs:5: indented #[no_mangle] function is not exported".to_string(),
+                "foo.rs:10: indented #[lisp_fn] function is not exported".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn shadowed_builtins_are_reported() {
+        let src = r#"#[lisp_fn(name = "car")]
+pub fn wr_car() {}
+
+#[lisp_fn(
+    min = "1",
+    c_name = "buffer_list"
+)]
+pub fn buffers(frame: LispObject) {}
+
+#[lisp_fn]
+pub fn cdr_safe_p() {}
+
+include!(concat!(env!("CARGO_MANIFEST_DIR"), "/out/foo_exports.rs"));
+"#;
+
+        let mod_data = parse_module("foo", src).expect("failed to parse module");
+
+        let builtins: HashSet<&str> = ["car", "buffer-list", "cdr-safe"].iter().copied().collect();
+        assert_eq!(
+            shadowed_builtin_warnings(&vec![mod_data], &builtins),
+            vec![
+                "foo.rs:1: lisp_fn in module foo shadows the C builtin `car`".to_string(),
+                "foo.rs:4: lisp_fn in module foo shadows the C builtin `buffer-list`".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn orphaned_async_streams_are_reported() {
+        let src = r#"#[async_stream]
+pub async fn echo(s: String) -> String {
+    s
+}
+
+#[async_stream]
+pub fn renamed(s: String) -> String {
+    s
+}
+
+include!(concat!(env!("CARGO_MANIFEST_DIR"), "/out/foo_exports.rs"));
+"#;
+
+        let mod_data = parse_module("foo", src).expect("failed to parse module");
+
+        assert_eq!(
+            orphaned_async_stream_warnings(&vec![mod_data]),
+            vec![
+                "foo.rs:6: call_renamed is exported, but module foo has no async fn renamed"
+                    .to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn modules_of_extra_dirs() {
+        let crate_dir = tempfile::tempdir().unwrap();
+        for dir in ["src", "generated", "more"].iter() {
+            fs::create_dir(crate_dir.path().join(dir)).unwrap();
+        }
+        fs::write(crate_dir.path().join("src/lib.rs"), "").unwrap();
+        fs::write(crate_dir.path().join("src/foo.rs"), "").unwrap();
+        fs::write(crate_dir.path().join("generated/lib.rs"), "").unwrap();
+        fs::write(crate_dir.path().join("generated/bar.rs"), "").unwrap();
+
+        let src_dir = crate_dir.path().join("src");
+        let modules = find_modules_in_dirs(
+            crate_dir.path(),
+            &src_dir,
+            &[PathBuf::from("generated")],
+            false,
+        )
+        .expect("failed to find modules");
+        let names: Vec<_> = modules.iter().map(|m| m.info.name.as_str()).collect();
+        assert_eq!(names, vec!["bar", "foo"]);
+
+        // Directories the crate doesn't have are skipped.
+        let extra_dirs = [PathBuf::from("generated"), PathBuf::from("missing")];
+        let modules = find_modules_in_dirs(crate_dir.path(), &src_dir, &extra_dirs, false)
+            .expect("failed to find modules");
+        assert_eq!(modules.len(), 2);
+
+        fs::write(crate_dir.path().join("more/foo.rs"), "").unwrap();
+        let extra_dirs = [PathBuf::from("generated"), PathBuf::from("more")];
+        let modules = find_modules_in_dirs(crate_dir.path(), &src_dir, &extra_dirs, false)
+            .expect("failed to find modules");
+        let lints = duplicate_lints(&modules);
+        assert_eq!(lints.len(), 1);
+        assert_eq!(lints[0].modname, "foo");
+    }
+
+    #[test]
+    fn src_dir_from_manifest() {
+        let crate_dir = tempfile::tempdir().unwrap();
+        let manifest = crate_dir.path().join("Cargo.toml");
+        fs::write(
+            &manifest,
+            "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        assert!(get_src_dir(crate_dir.path()).is_err());
+
+        fs::create_dir(crate_dir.path().join("src")).unwrap();
+        assert_eq!(
+            get_src_dir(crate_dir.path()).ok(),
+            Some(crate_dir.path().join("src"))
+        );
+
+        fs::write(
+            &manifest,
+            concat!(
+                "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n",
+                "[package.metadata.ng-bindgen]\nsrc-dir = \"lib\"\n",
+            ),
+        )
+        .unwrap();
+        assert!(get_src_dir(crate_dir.path()).is_err());
+
+        fs::create_dir(crate_dir.path().join("lib")).unwrap();
+        assert_eq!(
+            get_src_dir(crate_dir.path()).ok(),
+            Some(crate_dir.path().join("lib"))
+        );
+    }
+
+    #[test]
+    fn include_path_of_joined_lines() {
+        let info = ModuleInfo::synthetic("wrterm");
+        let mut parser = ModuleParser::new(&info);
+
+        let path = parser.parse_include_path(
+            r#"include!(concat!(env!("CARGO_MANIFEST_DIR"),"/out/wrterm_exports.rs"));"#,
+        );
+        assert_eq!(path.ok(), Some("/out/wrterm_exports.rs".to_string()));
+    }
 }
@@ -2897,6 +2897,10 @@ killed.  */
   /* Transfer any clipboards we own to the clipboard manager.  */
   x_clipboard_manager_save_all ();
 #endif
+#ifdef USE_WEBRENDER
+  /* Likewise for the clipboard of webrender frames.  */
+  wr_clipboard_manager_save_all ();
+#endif
 
   shut_down_emacs (0, (STRINGP (arg) && !feof (stdin)) ? arg : Qnil);
 
@@ -355,7 +355,6 @@ static bool readable_events (int);
 static Lisp_Object read_char_x_menu_prompt (Lisp_Object,
                                             Lisp_Object, bool *);
 static Lisp_Object read_char_minibuf_menu_prompt (int, Lisp_Object);
-static Lisp_Object make_lispy_event (struct input_event *);
 static Lisp_Object make_lispy_movement (struct frame *, Lisp_Object,
                                         enum scroll_bar_part,
                                         Lisp_Object, Lisp_Object,
@@ -5754,7 +5753,7 @@ coords_in_menu_bar_window (struct frame *f, int x, int y)
    are received; this function stores the location of button presses
    in order to build drag events when the button is released.  */
 
-static Lisp_Object
+Lisp_Object
 make_lispy_event (struct input_event *event)
 {
   int i;
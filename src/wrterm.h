@@ -135,6 +135,7 @@ extern int wr_put_pixel(WRImage *ximg, int x, int y, unsigned long pixel);
 extern bool wr_load_image (struct frame *f, struct image *img,
 			   Lisp_Object spec_file, Lisp_Object spec_data);
 extern bool wr_can_use_native_image_api (Lisp_Object type);
+extern void wr_clipboard_manager_save_all (void);
 
 extern void wr_transform_image(struct frame *f, struct image *img, int width, int height, double rotation);
 